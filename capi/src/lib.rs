@@ -0,0 +1,334 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+//! C bindings to the texture compiler, for hosts that cannot link Rust
+//! directly such as the C++ editor side of the SDK.
+//!
+//! Every function operates on a handle created by [txc_new] and freed by
+//! [txc_free]. Handles are not thread safe; drive each one from a single
+//! thread. Strings are nul terminated UTF-8. Functions returning an `int`
+//! report 0 on success and -1 on failure, with a message available
+//! through [txc_last_error] until the next call on the same handle. A
+//! panic inside the compiler is caught at the boundary and reported like
+//! any other failure instead of unwinding into the host and aborting it.
+
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::iter;
+use std::os::raw::c_char;
+use std::os::raw::c_int;
+use std::path::PathBuf;
+use std::ptr;
+
+use texturec_compiler::encode::Encoding;
+use texturec_compiler::encode::Quality;
+use texturec_compiler::filter;
+use texturec_compiler::mipmap::MipFilter;
+use texturec_compiler::output::Container;
+use texturec_compiler::params::ParameterMap;
+use texturec_compiler::pipeline::CancelToken;
+use texturec_compiler::pipeline::ExecutorKind;
+use texturec_compiler::pipeline::PassDelegate;
+use texturec_compiler::pipeline::PassStats;
+use texturec_compiler::pipeline::PipelineDelegate;
+use texturec_compiler::texture::Format;
+use texturec_compiler::Compiler;
+use texturec_compiler::Config;
+
+/// A delegate discarding all progress notifications.
+struct Silent;
+
+impl PassDelegate for Silent {
+    fn on_progress(&self, _stats: PassStats) {}
+
+    fn on_end(&self) {}
+}
+
+impl PipelineDelegate for Silent {
+    type Pass = Silent;
+
+    fn on_start_pass(&self, _index: usize, _count: usize, _name: &str) -> Silent {
+        Silent
+    }
+}
+
+/// An opaque texture compilation, holding its configuration, the last
+/// error message and the bytes of the last output.
+pub struct TxcCompiler {
+    compiler: Compiler,
+    error: Option<CString>,
+    output: Vec<u8>,
+}
+
+/// Reads a nul terminated UTF-8 argument.
+///
+/// # Safety
+///
+/// `value` must be null or point to a nul terminated string.
+unsafe fn str_arg<'a>(value: *const c_char) -> Option<&'a str> {
+    if value.is_null() {
+        return None;
+    }
+    CStr::from_ptr(value).to_str().ok()
+}
+
+/// Records an error message on the handle and returns -1.
+fn fail(handle: &mut TxcCompiler, message: String) -> c_int {
+    handle.error = Some(CString::new(message).unwrap_or_default());
+    -1
+}
+
+/// Renders the payload of a caught panic as an error message.
+///
+/// Unwinding across an `extern "C"` boundary aborts the process, so the
+/// entry points reaching filter and parameter code catch panics and
+/// report them through [txc_last_error] like any other failure.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => format!("panic: {}", message),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => format!("panic: {}", message),
+            None => "panic".into(),
+        },
+    }
+}
+
+/// Creates a compilation rendering a `width` by `height` texture in the
+/// named [format](Format) to the given output path. The container is
+/// inferred from the output extension and the pipeline starts out empty,
+/// single threaded and encoding raw texels.
+///
+/// Returns null when the format name is unknown or an argument is not
+/// valid UTF-8.
+///
+/// # Safety
+///
+/// `format` and `output` must be nul terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn txc_new(
+    width: u32,
+    height: u32,
+    format: *const c_char,
+    output: *const c_char,
+) -> *mut TxcCompiler {
+    let format = match str_arg(format).and_then(Format::from_name) {
+        Some(v) => v,
+        None => return ptr::null_mut(),
+    };
+    let output = match str_arg(output) {
+        Some(v) => PathBuf::from(v),
+        None => return ptr::null_mut(),
+    };
+    let config = Config {
+        width,
+        height,
+        format,
+        npot: false,
+        layers: 1,
+        cubemap: false,
+        mipmaps: false,
+        mip_filter: MipFilter::default(),
+        alpha_coverage: None,
+        mip_passes: Vec::new(),
+        container: Container::from_path(&output),
+        output,
+        encoding: Encoding::Raw,
+        quality: Quality::Normal,
+        supercompress: false,
+        filters: Vec::new(),
+        params: ParameterMap::new(),
+        pass_params: Vec::new(),
+        n_threads: 1,
+        executor: ExecutorKind::default(),
+        debug: false,
+        deterministic: false,
+        seed: None,
+        strict: false,
+        tiling: None,
+        cache: false,
+        max_memory: None,
+        checkpoint: None,
+        cancel: CancelToken::new(),
+    };
+    Box::into_raw(Box::new(TxcCompiler {
+        compiler: Compiler::new(config),
+        error: None,
+        output: Vec::new(),
+    }))
+}
+
+/// Frees a handle created by [txc_new]. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn txc_free(handle: *mut TxcCompiler) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Appends a filter pass to the pipeline. Fails when no filter with the
+/// given name is registered.
+///
+/// # Safety
+///
+/// `handle` must be a live handle and `name` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn txc_add_filter(handle: *mut TxcCompiler, name: *const c_char) -> c_int {
+    let handle = &mut *handle;
+    let name = match str_arg(name) {
+        Some(v) => v,
+        None => return fail(handle, "invalid filter name".into()),
+    };
+    if !filter::filters().iter().any(|info| info.name == name) {
+        return fail(handle, format!("unknown filter '{}'", name));
+    }
+    handle.compiler.config_mut().filters.push(name.into());
+    0
+}
+
+/// Sets a shared parameter, replacing any previous parameter with the
+/// same name. Values use the command line syntax: `2.0`, `true`,
+/// `0.5,0.5`, a path to a source image, or anything else as a string.
+///
+/// # Safety
+///
+/// `handle` must be a live handle; `name` and `value` must be nul
+/// terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn txc_set_param(
+    handle: *mut TxcCompiler,
+    name: *const c_char,
+    value: *const c_char,
+) -> c_int {
+    let handle = &mut *handle;
+    let name = match str_arg(name) {
+        Some(v) => v,
+        None => return fail(handle, "invalid parameter name".into()),
+    };
+    let value = match str_arg(value) {
+        Some(v) => v,
+        None => return fail(handle, "invalid parameter value".into()),
+    };
+    // Parsing a texture parameter decodes the source image, which may
+    // panic on a malformed file deep inside a decoder.
+    let parsed = std::panic::catch_unwind(|| {
+        ParameterMap::parse(iter::once((name, OsStr::new(value))))
+    });
+    let parsed = match parsed {
+        Ok(Ok(v)) => v,
+        Ok(Err(e)) => return fail(handle, e.to_string()),
+        Err(payload) => return fail(handle, panic_message(payload.as_ref())),
+    };
+    let merged = handle.compiler.config_mut().params.merged(&parsed);
+    handle.compiler.config_mut().params = merged;
+    0
+}
+
+/// Sets the number of render threads used by [txc_run].
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn txc_set_threads(handle: *mut TxcCompiler, n_threads: u32) -> c_int {
+    let handle = &mut *handle;
+    handle.compiler.config_mut().n_threads = n_threads as usize;
+    0
+}
+
+/// Runs the pipeline, writes the output file and loads its bytes so
+/// [txc_output_data] can hand them back without a second disk round trip.
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn txc_run(handle: *mut TxcCompiler) -> c_int {
+    let handle = &mut *handle;
+    handle.error = None;
+    handle.output.clear();
+    let compiler = &handle.compiler;
+    let report = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        compiler.run(&Silent)
+    }));
+    let report = match report {
+        Ok(Ok(v)) => v,
+        Ok(Err(e)) => return fail(handle, e.to_string()),
+        Err(payload) => return fail(handle, panic_message(payload.as_ref())),
+    };
+    if let Some(path) = report.outputs.first() {
+        match std::fs::read(path) {
+            Ok(data) => handle.output = data,
+            Err(e) => return fail(handle, format!("io error: {}", e)),
+        }
+    }
+    0
+}
+
+/// Returns the message of the last failed call on this handle, or null
+/// when the last call succeeded. The pointer stays valid until the next
+/// call on the same handle.
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn txc_last_error(handle: *const TxcCompiler) -> *const c_char {
+    match &(*handle).error {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Returns the bytes of the output written by the last successful
+/// [txc_run], sized by [txc_output_size]. The pointer stays valid until
+/// the next call on the same handle.
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn txc_output_data(handle: *const TxcCompiler) -> *const u8 {
+    (*handle).output.as_ptr()
+}
+
+/// Returns the size in bytes of the output of the last successful
+/// [txc_run].
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn txc_output_size(handle: *const TxcCompiler) -> usize {
+    (*handle).output.len()
+}
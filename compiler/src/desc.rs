@@ -0,0 +1,373 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Serializable description of a compilation pipeline, as stored in
+//! ".texpipe" documents saved by editors.
+//!
+//! Descriptions load from and save to JSON or YAML, picked from the file
+//! extension, so build systems can generate them programmatically instead
+//! of shelling out long command lines.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::encode::Encoding;
+use crate::encode::Quality;
+use crate::params::Parameter;
+use crate::params::ParameterError;
+use crate::mipmap::MipFilter;
+use crate::output::Container;
+use crate::params::ParameterMap;
+use crate::pipeline::CancelToken;
+use crate::pipeline::ExecutorKind;
+use crate::texture::Format;
+use crate::Config;
+
+/// A serializable filter parameter value.
+///
+/// Texture parameters are stored as the path of their source image and only
+/// loaded when the description is turned into a configuration.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterDesc {
+    /// A floating point parameter.
+    Float(f64),
+
+    /// An integer parameter.
+    Int(i64),
+
+    /// A boolean parameter.
+    Bool(bool),
+
+    /// A 2 components vector parameter.
+    Vector2([f64; 2]),
+
+    /// A 3 components vector parameter.
+    Vector3([f64; 3]),
+
+    /// A 4 components vector parameter.
+    Vector4([f64; 4]),
+
+    /// A normalized RGBA color parameter.
+    Color([f64; 4]),
+
+    /// A string parameter.
+    String(String),
+
+    /// A texture parameter referenced by path.
+    Texture(PathBuf),
+}
+
+impl ParameterDesc {
+    /// Turns this description into a parameter, loading texture paths.
+    pub fn into_parameter(self, name: &str) -> Result<Parameter, ParameterError> {
+        match self {
+            ParameterDesc::Float(v) => Ok(Parameter::Float(v)),
+            ParameterDesc::Int(v) => Ok(Parameter::Int(v)),
+            ParameterDesc::Bool(v) => Ok(Parameter::Bool(v)),
+            ParameterDesc::Vector2(v) => Ok(Parameter::Vector2(v)),
+            ParameterDesc::Vector3(v) => Ok(Parameter::Vector3(v)),
+            ParameterDesc::Vector4(v) => Ok(Parameter::Vector4(v)),
+            ParameterDesc::Color(v) => Ok(Parameter::Color(v)),
+            ParameterDesc::String(v) => Ok(Parameter::String(v)),
+            ParameterDesc::Texture(path) => {
+                let texture = crate::import::load_parameter(&path)
+                    .map_err(|e| ParameterError::InvalidTexture(name.into(), e))?;
+                Ok(Parameter::Texture(texture))
+            }
+        }
+    }
+
+    /// Builds a description of a parameter, None for texture parameters:
+    /// a decoded texture no longer knows the path it came from and cannot
+    /// be stored in a document.
+    pub fn from_parameter(param: &Parameter) -> Option<ParameterDesc> {
+        match param {
+            Parameter::Float(v) => Some(ParameterDesc::Float(*v)),
+            Parameter::Int(v) => Some(ParameterDesc::Int(*v)),
+            Parameter::Bool(v) => Some(ParameterDesc::Bool(*v)),
+            Parameter::Vector2(v) => Some(ParameterDesc::Vector2(*v)),
+            Parameter::Vector3(v) => Some(ParameterDesc::Vector3(*v)),
+            Parameter::Vector4(v) => Some(ParameterDesc::Vector4(*v)),
+            Parameter::Color(v) => Some(ParameterDesc::Color(*v)),
+            Parameter::String(v) => Some(ParameterDesc::String(v.clone())),
+            Parameter::Texture(_) => None,
+        }
+    }
+}
+
+/// A serializable description of a compilation pipeline.
+///
+/// This mirrors [Config](crate::Config) with all textures referenced by path,
+/// so it can be saved and loaded by editors and handed to the compiler.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PipelineDesc {
+    /// Width in texels of the output texture.
+    pub width: u32,
+
+    /// Height in texels of the output texture.
+    pub height: u32,
+
+    /// Format of the output texture.
+    pub format: Format,
+
+    /// If set, keeps the exact output size instead of rounding it up to
+    /// the next power of two.
+    #[serde(default)]
+    pub npot: bool,
+
+    /// Number of layers of the output texture array, 1 for a plain 2D
+    /// texture; see [layers](crate::Config::layers).
+    #[serde(default = "default_layers")]
+    pub layers: u32,
+
+    /// If set, assembles the six rendered layers into a cubemap; see
+    /// [cubemap](crate::Config::cubemap).
+    #[serde(default)]
+    pub cubemap: bool,
+
+    /// If set, generates and stores a full mip chain; see
+    /// [mipmaps](crate::Config::mipmaps).
+    #[serde(default)]
+    pub mipmaps: bool,
+
+    /// The downsampling filter generating mip levels (box by default); see
+    /// [mip_filter](crate::Config::mip_filter).
+    #[serde(default)]
+    pub mip_filter: MipFilter,
+
+    /// If set, keeps alpha test coverage at this reference value constant
+    /// across the mip chain; see
+    /// [alpha_coverage](crate::Config::alpha_coverage).
+    #[serde(default)]
+    pub alpha_coverage: Option<f64>,
+
+    /// Names of the filters re-run on every generated mip level below the
+    /// base; see [mip_passes](crate::Config::mip_passes).
+    #[serde(default)]
+    pub mip_passes: Vec<String>,
+
+    /// Path of the output texture file.
+    pub output: PathBuf,
+
+    /// Container format the output texture is written in, picked from the
+    /// output extension when not set.
+    #[serde(default)]
+    pub container: Option<Container>,
+
+    /// Encoding of the texel payload stored in the output container.
+    #[serde(default = "default_encoding")]
+    pub encoding: Encoding,
+
+    /// Effort spent searching block compression endpoints.
+    #[serde(default = "default_quality")]
+    pub quality: Quality,
+
+    /// If set, supercompresses the payload of containers that support it.
+    #[serde(default)]
+    pub supercompress: bool,
+
+    /// Names of the filters to run in order, with the same `:buffer`
+    /// publishing suffix as [filters](crate::Config::filters).
+    pub filters: Vec<String>,
+
+    /// Parameters shared by all filters.
+    #[serde(default)]
+    pub params: HashMap<String, ParameterDesc>,
+
+    /// Per pass parameter overrides, zipped with
+    /// [filters](PipelineDesc::filters).
+    #[serde(default)]
+    pub pass_params: Vec<HashMap<String, ParameterDesc>>,
+}
+
+fn default_encoding() -> Encoding {
+    Encoding::Raw
+}
+
+fn default_quality() -> Quality {
+    Quality::Normal
+}
+
+fn default_layers() -> u32 {
+    1
+}
+
+/// Describes every non texture parameter of a map.
+fn desc_params(params: &ParameterMap) -> HashMap<String, ParameterDesc> {
+    params
+        .iter()
+        .filter_map(|(name, param)| {
+            ParameterDesc::from_parameter(param).map(|desc| (name.clone(), desc))
+        })
+        .collect()
+}
+
+/// Errors raised when loading or saving a pipeline description.
+#[derive(Debug)]
+pub enum DescError {
+    /// The document could not be read or written.
+    Io(std::io::Error),
+
+    /// The document is not valid JSON.
+    Json(serde_json::Error),
+
+    /// The document is not valid YAML.
+    Yaml(serde_yaml::Error),
+}
+
+impl fmt::Display for DescError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DescError::Io(e) => write!(f, "io error: {}", e),
+            DescError::Json(e) => write!(f, "json error: {}", e),
+            DescError::Yaml(e) => write!(f, "yaml error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DescError {}
+
+/// Returns true when a description path holds YAML rather than JSON.
+fn is_yaml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|v| v.to_str()),
+        Some("yaml") | Some("yml")
+    )
+}
+
+impl PipelineDesc {
+    /// Loads a description from a JSON or YAML document.
+    ///
+    /// "yaml" and "yml" extensions parse as YAML; anything else, including
+    /// the usual ".texpipe", parses as JSON.
+    pub fn load(path: &Path) -> Result<PipelineDesc, DescError> {
+        let source = std::fs::read_to_string(path).map_err(DescError::Io)?;
+        match is_yaml(path) {
+            true => serde_yaml::from_str(&source).map_err(DescError::Yaml),
+            false => serde_json::from_str(&source).map_err(DescError::Json),
+        }
+    }
+
+    /// Saves this description as a JSON or YAML document, picked from the
+    /// extension like [load](PipelineDesc::load).
+    pub fn save(&self, path: &Path) -> Result<(), DescError> {
+        let source = match is_yaml(path) {
+            true => serde_yaml::to_string(self).map_err(DescError::Yaml)?,
+            false => serde_json::to_string_pretty(self).map_err(DescError::Json)?,
+        };
+        std::fs::write(path, source).map_err(DescError::Io)
+    }
+
+    /// Describes an existing configuration, so tools can store and diff
+    /// pipelines they assembled in memory.
+    ///
+    /// Texture parameters are omitted, see
+    /// [from_parameter](ParameterDesc::from_parameter).
+    pub fn from_config(config: &Config) -> PipelineDesc {
+        PipelineDesc {
+            width: config.width,
+            height: config.height,
+            format: config.format,
+            npot: config.npot,
+            layers: config.layers,
+            cubemap: config.cubemap,
+            mipmaps: config.mipmaps,
+            mip_filter: config.mip_filter,
+            alpha_coverage: config.alpha_coverage,
+            mip_passes: config.mip_passes.clone(),
+            output: config.output.clone(),
+            container: Some(config.container),
+            encoding: config.encoding,
+            quality: config.quality,
+            supercompress: config.supercompress,
+            filters: config.filters.clone(),
+            params: desc_params(&config.params),
+            pass_params: config.pass_params.iter().map(desc_params).collect(),
+        }
+    }
+
+    /// Builds a compiler configuration from this description.
+    ///
+    /// Texture parameters are loaded from the paths stored in the document.
+    pub fn into_config(self, n_threads: usize) -> Result<Config, ParameterError> {
+        let container = self
+            .container
+            .unwrap_or_else(|| Container::from_path(&self.output));
+        let mut params = ParameterMap::new();
+        for (name, value) in self.params {
+            let value = value.into_parameter(&name)?;
+            params.insert(name, value);
+        }
+        let mut pass_params = Vec::with_capacity(self.pass_params.len());
+        for overrides in self.pass_params {
+            let mut map = ParameterMap::new();
+            for (name, value) in overrides {
+                let value = value.into_parameter(&name)?;
+                map.insert(name, value);
+            }
+            pass_params.push(map);
+        }
+        Ok(Config {
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            npot: self.npot,
+            layers: self.layers,
+            cubemap: self.cubemap,
+            mipmaps: self.mipmaps,
+            mip_filter: self.mip_filter,
+            alpha_coverage: self.alpha_coverage,
+            mip_passes: self.mip_passes,
+            output: self.output,
+            container,
+            encoding: self.encoding,
+            supercompress: self.supercompress,
+            quality: self.quality,
+            filters: self.filters,
+            params,
+            pass_params,
+            n_threads,
+            executor: ExecutorKind::default(),
+            debug: false,
+            deterministic: false,
+            seed: None,
+            strict: false,
+            tiling: None,
+            cache: false,
+            max_memory: None,
+            checkpoint: None,
+            cancel: CancelToken::new(),
+        })
+    }
+}
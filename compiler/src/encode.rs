@@ -0,0 +1,1151 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+//! Block compression of the rendered texel payload.
+//!
+//! Encoding runs after the pipeline: the final render target is encoded
+//! into the payload stored in the output container, so textures can be
+//! uploaded to GPUs without runtime transcoding. Container writers stream
+//! blocks through [encode_into] as they are produced, so the encoded
+//! payload never has to sit in memory as a whole.
+
+use std::fmt;
+use std::io::Write;
+
+use crate::texture::Format;
+use crate::texture::Texture;
+
+/// The encoding of the texel payload stored in the output container.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Uncompressed texels, as rendered.
+    Raw,
+
+    /// BC1 (DXT1): 4x4 RGB blocks, 4 bits per texel, 1 bit alpha.
+    Bc1,
+
+    /// BC3 (DXT5): BC1 color plus a smooth 8 bits alpha block.
+    Bc3,
+
+    /// BC4: single channel 4x4 blocks, 4 bits per texel.
+    Bc4,
+
+    /// BC5: two BC4 channels, for tangent space normal maps.
+    Bc5,
+
+    /// BC6H: unsigned half float RGB blocks, for HDR maps.
+    Bc6h,
+
+    /// BC7: high quality RGBA blocks, for albedo maps.
+    Bc7,
+
+    /// ASTC LDR with 4x4 blocks, 8 bits per texel.
+    Astc4x4,
+
+    /// ASTC LDR with 5x5 blocks, 5.12 bits per texel.
+    Astc5x5,
+
+    /// ASTC LDR with 6x6 blocks, 3.56 bits per texel.
+    Astc6x6,
+
+    /// ASTC LDR with 8x8 blocks, 2 bits per texel.
+    Astc8x8,
+
+    /// ETC2 RGB blocks, 4 bits per texel.
+    Etc2Rgb,
+
+    /// ETC2 RGB plus an EAC alpha block, 8 bits per texel.
+    Etc2Rgba,
+
+    /// EAC single channel 11 bits blocks.
+    EacR11,
+
+    /// EAC two channels 11 bits blocks, for tangent space normal maps.
+    EacRg11,
+}
+
+impl Encoding {
+    /// Returns the name of this encoding as used by the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Raw => "raw",
+            Encoding::Bc1 => "bc1",
+            Encoding::Bc3 => "bc3",
+            Encoding::Bc4 => "bc4",
+            Encoding::Bc5 => "bc5",
+            Encoding::Bc6h => "bc6h",
+            Encoding::Bc7 => "bc7",
+            Encoding::Astc4x4 => "astc4x4",
+            Encoding::Astc5x5 => "astc5x5",
+            Encoding::Astc6x6 => "astc6x6",
+            Encoding::Astc8x8 => "astc8x8",
+            Encoding::Etc2Rgb => "etc2rgb",
+            Encoding::Etc2Rgba => "etc2rgba",
+            Encoding::EacR11 => "eacr11",
+            Encoding::EacRg11 => "eacrg11",
+        }
+    }
+
+    /// Parses an encoding from its command line name.
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name {
+            "raw" => Some(Encoding::Raw),
+            "bc1" => Some(Encoding::Bc1),
+            "bc3" => Some(Encoding::Bc3),
+            "bc4" => Some(Encoding::Bc4),
+            "bc5" => Some(Encoding::Bc5),
+            "bc6h" => Some(Encoding::Bc6h),
+            "bc7" => Some(Encoding::Bc7),
+            "astc4x4" => Some(Encoding::Astc4x4),
+            "astc5x5" => Some(Encoding::Astc5x5),
+            "astc6x6" => Some(Encoding::Astc6x6),
+            "astc8x8" => Some(Encoding::Astc8x8),
+            "etc2rgb" => Some(Encoding::Etc2Rgb),
+            "etc2rgba" => Some(Encoding::Etc2Rgba),
+            "eacr11" => Some(Encoding::EacR11),
+            "eacrg11" => Some(Encoding::EacRg11),
+            _ => None,
+        }
+    }
+
+    /// Returns the size in bytes of a single encoded block.
+    pub fn block_size(self) -> usize {
+        match self {
+            Encoding::Raw => 0,
+            Encoding::Bc1 | Encoding::Bc4 | Encoding::Etc2Rgb | Encoding::EacR11 => 8,
+            Encoding::Bc3
+            | Encoding::Bc5
+            | Encoding::Bc6h
+            | Encoding::Bc7
+            | Encoding::Astc4x4
+            | Encoding::Astc5x5
+            | Encoding::Astc6x6
+            | Encoding::Astc8x8
+            | Encoding::Etc2Rgba
+            | Encoding::EacRg11 => 16,
+        }
+    }
+
+    /// Returns the footprint in texels of a single encoded block.
+    pub fn block_dims(self) -> (u32, u32) {
+        match self {
+            Encoding::Astc5x5 => (5, 5),
+            Encoding::Astc6x6 => (6, 6),
+            Encoding::Astc8x8 => (8, 8),
+            _ => (4, 4),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl serde::Serialize for Encoding {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Encoding {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Encoding, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Encoding::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format_args!("unknown payload encoding '{}'", name)))
+    }
+}
+
+/// The effort spent searching block endpoints.
+///
+/// Only BC6H and BC7 refine their endpoints; the other encodings always
+/// use their bounding box endpoints.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Quality {
+    /// Bounding box endpoints only.
+    Fast,
+
+    /// One least squares refinement of the endpoints.
+    Normal,
+
+    /// Several least squares refinements of the endpoints.
+    Slow,
+}
+
+impl Quality {
+    /// Returns the name of this quality as used by the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Quality::Fast => "fast",
+            Quality::Normal => "normal",
+            Quality::Slow => "slow",
+        }
+    }
+
+    /// Parses a quality from its command line name.
+    pub fn from_name(name: &str) -> Option<Quality> {
+        match name {
+            "fast" => Some(Quality::Fast),
+            "normal" => Some(Quality::Normal),
+            "slow" => Some(Quality::Slow),
+            _ => None,
+        }
+    }
+
+    /// Number of least squares refinement passes of this quality.
+    fn refinements(self) -> usize {
+        match self {
+            Quality::Fast => 0,
+            Quality::Normal => 1,
+            Quality::Slow => 4,
+        }
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl serde::Serialize for Quality {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Quality {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Quality, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Quality::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format_args!("unknown encoding quality '{}'", name)))
+    }
+}
+
+/// Errors raised while encoding the texel payload.
+#[derive(Debug)]
+pub enum EncodeError {
+    /// The encoding does not support the texture format (encoding, format).
+    Unsupported(Encoding, Format),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::Unsupported(encoding, format) => {
+                write!(f, "encoding {} does not support format {}", encoding, format)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// A 4x4 block of normalized texels, clamped at the texture edges.
+fn fetch_block(texture: &dyn Texture, bx: u32, by: u32) -> [[f32; 4]; 16] {
+    let mut block = [[0.0f32; 4]; 16];
+    for dy in 0..4 {
+        for dx in 0..4 {
+            let x = (bx * 4 + dx).min(texture.width() - 1);
+            let y = (by * 4 + dy).min(texture.height() - 1);
+            block[(dy * 4 + dx) as usize] = texture.get(x, y).normalize();
+        }
+    }
+    block
+}
+
+/// Packs normalized RGB into a RGB565 word.
+fn pack_565(rgb: [f32; 3]) -> u16 {
+    let r = (rgb[0].clamp(0.0, 1.0) * 31.0 + 0.5) as u16;
+    let g = (rgb[1].clamp(0.0, 1.0) * 63.0 + 0.5) as u16;
+    let b = (rgb[2].clamp(0.0, 1.0) * 31.0 + 0.5) as u16;
+    (r << 11) | (g << 5) | b
+}
+
+/// Unpacks a RGB565 word into normalized RGB.
+fn unpack_565(word: u16) -> [f32; 3] {
+    [
+        ((word >> 11) & 31) as f32 / 31.0,
+        ((word >> 5) & 63) as f32 / 63.0,
+        (word & 31) as f32 / 31.0,
+    ]
+}
+
+fn distance2(lhs: [f32; 3], rhs: [f32; 3]) -> f32 {
+    let dr = lhs[0] - rhs[0];
+    let dg = lhs[1] - rhs[1];
+    let db = lhs[2] - rhs[2];
+    dr * dr + dg * dg + db * db
+}
+
+/// Encodes a block as a 8 bytes BC1 color block.
+fn encode_bc1_block(block: &[[f32; 4]; 16]) -> [u8; 8] {
+    // Bounding box endpoints: not optimal but stable and fast.
+    let mut min = [1.0f32; 3];
+    let mut max = [0.0f32; 3];
+    for texel in block {
+        for channel in 0..3 {
+            min[channel] = min[channel].min(texel[channel]);
+            max[channel] = max[channel].max(texel[channel]);
+        }
+    }
+    let mut c0 = pack_565(max);
+    let mut c1 = pack_565(min);
+    if c0 < c1 {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+    let e0 = unpack_565(c0);
+    let e1 = unpack_565(c1);
+    // c0 > c1 selects the opaque 4 color mode.
+    let palette = [
+        e0,
+        e1,
+        [
+            (2.0 * e0[0] + e1[0]) / 3.0,
+            (2.0 * e0[1] + e1[1]) / 3.0,
+            (2.0 * e0[2] + e1[2]) / 3.0,
+        ],
+        [
+            (e0[0] + 2.0 * e1[0]) / 3.0,
+            (e0[1] + 2.0 * e1[1]) / 3.0,
+            (e0[2] + 2.0 * e1[2]) / 3.0,
+        ],
+    ];
+    let mut indices = 0u32;
+    for (i, texel) in block.iter().enumerate() {
+        let rgb = [texel[0], texel[1], texel[2]];
+        let mut best = 0u32;
+        let mut best_dist = f32::INFINITY;
+        for (index, candidate) in palette.iter().enumerate() {
+            let dist = distance2(rgb, *candidate);
+            if dist < best_dist {
+                best_dist = dist;
+                best = index as u32;
+            }
+        }
+        indices |= best << (i * 2);
+    }
+    let mut out = [0u8; 8];
+    out[0..2].copy_from_slice(&c0.to_le_bytes());
+    out[2..4].copy_from_slice(&c1.to_le_bytes());
+    out[4..8].copy_from_slice(&indices.to_le_bytes());
+    out
+}
+
+/// Encodes one channel of a block as a 8 bytes BC4 alpha block.
+fn encode_bc4_block(block: &[[f32; 4]; 16], channel: usize) -> [u8; 8] {
+    let mut min = 1.0f32;
+    let mut max = 0.0f32;
+    for texel in block {
+        min = min.min(texel[channel]);
+        max = max.max(texel[channel]);
+    }
+    let a0 = (max.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+    let a1 = (min.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+    // a0 > a1 selects the 8 interpolated values mode.
+    let (a0, a1) = if a0 > a1 { (a0, a1) } else { (a1, a0) };
+    let mut palette = [0.0f32; 8];
+    palette[0] = a0 as f32 / 255.0;
+    palette[1] = a1 as f32 / 255.0;
+    for (i, value) in palette.iter_mut().enumerate().skip(2) {
+        let w = (i - 1) as f32 / 7.0;
+        *value = (1.0 - w) * a0 as f32 / 255.0 + w * a1 as f32 / 255.0;
+    }
+    let mut indices = 0u64;
+    for (i, texel) in block.iter().enumerate() {
+        let mut best = 0u64;
+        let mut best_dist = f32::INFINITY;
+        for (index, candidate) in palette.iter().enumerate() {
+            let dist = (texel[channel] - candidate).abs();
+            if dist < best_dist {
+                best_dist = dist;
+                best = index as u64;
+            }
+        }
+        indices |= best << (i * 3);
+    }
+    let mut out = [0u8; 8];
+    out[0] = a0;
+    out[1] = a1;
+    out[2..8].copy_from_slice(&indices.to_le_bytes()[0..6]);
+    out
+}
+
+/// Interpolation weights of 4 bits block indices, in 1/64th units.
+const WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// A 128 bits block assembled LSB first, as the BC6H/BC7 layouts expect.
+struct BlockWriter {
+    data: [u8; 16],
+    position: usize,
+}
+
+impl BlockWriter {
+    fn new() -> BlockWriter {
+        BlockWriter {
+            data: [0; 16],
+            position: 0,
+        }
+    }
+
+    fn push(&mut self, value: u64, bits: usize) {
+        for i in 0..bits {
+            if value >> i & 1 != 0 {
+                self.data[(self.position + i) / 8] |= 1 << ((self.position + i) % 8);
+            }
+        }
+        self.position += bits;
+    }
+}
+
+/// Refines a pair of endpoints by least squares fitting the block against
+/// the interpolation weights of its current best indices.
+fn refine_endpoints<const N: usize>(
+    block: &[[f32; N]; 16],
+    e0: &mut [f32; N],
+    e1: &mut [f32; N],
+    indices: &[usize; 16],
+) {
+    let mut alpha = 0.0f32;
+    let mut beta = 0.0f32;
+    let mut gamma = 0.0f32;
+    for index in indices {
+        let t = WEIGHTS4[*index] as f32 / 64.0;
+        alpha += (1.0 - t) * (1.0 - t);
+        beta += t * (1.0 - t);
+        gamma += t * t;
+    }
+    let det = alpha * gamma - beta * beta;
+    if det.abs() < 1e-6 {
+        return;
+    }
+    for channel in 0..N {
+        let mut p = 0.0f32;
+        let mut q = 0.0f32;
+        for (texel, index) in block.iter().zip(indices) {
+            let t = WEIGHTS4[*index] as f32 / 64.0;
+            p += texel[channel] * (1.0 - t);
+            q += texel[channel] * t;
+        }
+        e0[channel] = ((gamma * p - beta * q) / det).clamp(0.0, 1e9);
+        e1[channel] = ((alpha * q - beta * p) / det).clamp(0.0, 1e9);
+    }
+}
+
+/// Picks the nearest palette index for every texel of a block.
+fn pick_indices<const N: usize>(block: &[[f32; N]; 16], palette: &[[f32; N]; 16]) -> [usize; 16] {
+    let mut indices = [0usize; 16];
+    for (out, texel) in indices.iter_mut().zip(block) {
+        let mut best_dist = f32::INFINITY;
+        for (index, candidate) in palette.iter().enumerate() {
+            let mut dist = 0.0f32;
+            for channel in 0..N {
+                let delta = texel[channel] - candidate[channel];
+                dist += delta * delta;
+            }
+            if dist < best_dist {
+                best_dist = dist;
+                *out = index;
+            }
+        }
+    }
+    indices
+}
+
+/// Interpolates a 16 entries palette between two endpoints.
+fn build_palette<const N: usize>(e0: [f32; N], e1: [f32; N]) -> [[f32; N]; 16] {
+    let mut palette = [[0.0f32; N]; 16];
+    for (entry, weight) in palette.iter_mut().zip(WEIGHTS4) {
+        let t = weight as f32 / 64.0;
+        for channel in 0..N {
+            entry[channel] = e0[channel] * (1.0 - t) + e1[channel] * t;
+        }
+    }
+    palette
+}
+
+/// Searches endpoints for a block: bounding box plus the refinement passes
+/// of the given quality.
+fn search_endpoints<const N: usize>(
+    block: &[[f32; N]; 16],
+    quality: Quality,
+) -> ([f32; N], [f32; N]) {
+    let mut e0 = [f32::INFINITY; N];
+    let mut e1 = [f32::NEG_INFINITY; N];
+    for texel in block {
+        for channel in 0..N {
+            e0[channel] = e0[channel].min(texel[channel]);
+            e1[channel] = e1[channel].max(texel[channel]);
+        }
+    }
+    for _ in 0..quality.refinements() {
+        let indices = pick_indices(block, &build_palette(e0, e1));
+        refine_endpoints(block, &mut e0, &mut e1, &indices);
+    }
+    (e0, e1)
+}
+
+/// Encodes a block as a 16 bytes BC7 mode 6 block (one subset, 7.1 bits
+/// endpoints, 4 bits indices).
+fn encode_bc7_block(block: &[[f32; 4]; 16], quality: Quality) -> [u8; 16] {
+    let (e0, e1) = search_endpoints(block, quality);
+    // Quantize each endpoint to 7 bits plus a shared p bit (the decoded
+    // 8 bits value is endpoint << 1 | p).
+    let quantize = |endpoint: [f32; 4]| -> ([u64; 4], u64) {
+        let mut best = ([0u64; 4], 0u64);
+        let mut best_err = u32::MAX;
+        for p in 0..2u64 {
+            let mut quantized = [0u64; 4];
+            let mut err = 0u32;
+            for (out, value) in quantized.iter_mut().zip(endpoint) {
+                let c = (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u64;
+                *out = (c.saturating_sub(p) + 1) >> 1;
+                if *out > 127 {
+                    *out = 127;
+                }
+                let decoded = *out << 1 | p;
+                err += (decoded.abs_diff(c) * decoded.abs_diff(c)) as u32;
+            }
+            if err < best_err {
+                best_err = err;
+                best = (quantized, p);
+            }
+        }
+        best
+    };
+    let (mut q0, mut p0) = quantize(e0);
+    let (mut q1, mut p1) = quantize(e1);
+    // Build the palette the decoder will see and pick the final indices.
+    let decoded = |q: [u64; 4], p: u64| -> [f32; 4] {
+        let mut out = [0.0f32; 4];
+        for (value, q) in out.iter_mut().zip(q) {
+            *value = (q << 1 | p) as f32 / 255.0;
+        }
+        out
+    };
+    let mut indices = pick_indices(block, &build_palette(decoded(q0, p0), decoded(q1, p1)));
+    // The anchor index omits its highest bit, which must therefore be zero;
+    // swapping the endpoints inverts every index.
+    if indices[0] >= 8 {
+        std::mem::swap(&mut q0, &mut q1);
+        std::mem::swap(&mut p0, &mut p1);
+        for index in &mut indices {
+            *index = 15 - *index;
+        }
+    }
+    let mut writer = BlockWriter::new();
+    writer.push(1 << 6, 7);
+    for channel in 0..4 {
+        writer.push(q0[channel], 7);
+        writer.push(q1[channel], 7);
+    }
+    writer.push(p0, 1);
+    writer.push(p1, 1);
+    writer.push(indices[0] as u64, 3);
+    for index in &indices[1..] {
+        writer.push(*index as u64, 4);
+    }
+    writer.data
+}
+
+/// Converts a non negative float to unsigned half float bits.
+fn f32_to_half(value: f32) -> u16 {
+    let value = value.clamp(0.0, 65504.0);
+    let bits = value.to_bits();
+    let exponent = (bits >> 23) as i32 - 127;
+    if exponent < -14 {
+        // Subnormal halves are flushed to zero.
+        return 0;
+    }
+    (((exponent + 15) as u32) << 10 | (bits >> 13) & 0x3FF) as u16
+}
+
+/// Converts unsigned half float bits to a float.
+fn half_to_f32(half: u16) -> f32 {
+    if half & 0x7C00 == 0 {
+        return 0.0;
+    }
+    let exponent = (half >> 10) as u32 & 0x1F;
+    f32::from_bits((exponent + 127 - 15) << 23 | ((half & 0x3FF) as u32) << 13)
+}
+
+/// Quantizes unsigned half float bits to a 10 bits BC6H endpoint.
+fn bc6h_quantize(half: u16) -> u64 {
+    if half == 0 {
+        return 0;
+    }
+    // Endpoints live in the 16 bits space the decoder unquantizes to;
+    // the final half is (interpolated * 31) >> 6.
+    let comp = (half as u64 * 64).div_ceil(31);
+    ((comp + 31) >> 6).min(1023)
+}
+
+/// Unquantizes a 10 bits BC6H endpoint back to its 16 bits space.
+fn bc6h_unquantize(endpoint: u64) -> u64 {
+    if endpoint == 0 {
+        0
+    } else if endpoint == 1023 {
+        0xFFFF
+    } else {
+        (endpoint << 16 | 0x8000) >> 10
+    }
+}
+
+/// Encodes a block as a 16 bytes BC6H mode 11 block (one region, unsigned,
+/// 10 bits endpoints, 4 bits indices).
+fn encode_bc6h_block(block: &[[f32; 4]; 16], quality: Quality) -> [u8; 16] {
+    let mut rgb = [[0.0f32; 3]; 16];
+    for (out, texel) in rgb.iter_mut().zip(block) {
+        out.copy_from_slice(&texel[0..3]);
+    }
+    let (e0, e1) = search_endpoints(&rgb, quality);
+    let mut q0 = [0u64; 3];
+    let mut q1 = [0u64; 3];
+    for channel in 0..3 {
+        q0[channel] = bc6h_quantize(f32_to_half(e0[channel]));
+        q1[channel] = bc6h_quantize(f32_to_half(e1[channel]));
+    }
+    // Build the palette the decoder will see and pick the final indices.
+    let palette_of = |q0: [u64; 3], q1: [u64; 3]| -> [[f32; 3]; 16] {
+        let mut palette = [[0.0f32; 3]; 16];
+        for (entry, weight) in palette.iter_mut().zip(WEIGHTS4) {
+            for channel in 0..3 {
+                let a = bc6h_unquantize(q0[channel]);
+                let b = bc6h_unquantize(q1[channel]);
+                let comp = (a * (64 - weight as u64) + b * weight as u64 + 32) >> 6;
+                entry[channel] = half_to_f32(((comp * 31) >> 6) as u16);
+            }
+        }
+        palette
+    };
+    let mut indices = pick_indices(&rgb, &palette_of(q0, q1));
+    // Same anchor constraint as BC7: the first index omits its highest bit.
+    if indices[0] >= 8 {
+        std::mem::swap(&mut q0, &mut q1);
+        for index in &mut indices {
+            *index = 15 - *index;
+        }
+    }
+    let mut writer = BlockWriter::new();
+    writer.push(0x03, 5);
+    for endpoint in q0 {
+        writer.push(endpoint, 10);
+    }
+    for endpoint in q1 {
+        writer.push(endpoint, 10);
+    }
+    writer.push(indices[0] as u64, 3);
+    for index in &indices[1..] {
+        writer.push(*index as u64, 4);
+    }
+    writer.data
+}
+
+/// ETC1/ETC2 intensity modifiers: positive small and large values per
+/// table codeword; pixel indices 2 and 3 select their negations.
+const ETC_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+/// EAC intensity modifiers per table codeword.
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// Encodes a block as a 8 bytes ETC2 RGB block in the ETC1 compatible
+/// individual mode: two 2x4 subblocks with their own RGB444 base color
+/// and intensity table.
+fn encode_etc2_rgb_block(block: &[[f32; 4]; 16]) -> [u8; 8] {
+    // Subblock 0 covers columns 0-1, subblock 1 columns 2-3 (flip bit 0).
+    let mut bases = [[0u32; 3]; 2];
+    for (sub, base) in bases.iter_mut().enumerate() {
+        let mut sum = [0.0f32; 3];
+        for y in 0..4 {
+            for x in 0..2 {
+                let texel = block[y * 4 + sub * 2 + x];
+                for (acc, channel) in sum.iter_mut().zip(texel) {
+                    *acc += channel;
+                }
+            }
+        }
+        for (out, acc) in base.iter_mut().zip(sum) {
+            *out = ((acc / 8.0).clamp(0.0, 1.0) * 15.0 + 0.5) as u32;
+        }
+    }
+    let mut codewords = [0u32; 2];
+    let mut indices = [0u32; 16];
+    for (sub, base) in bases.iter().enumerate() {
+        // The decoder expands RGB444 by bit replication.
+        let expanded = [base[0] * 17, base[1] * 17, base[2] * 17];
+        let mut best_err = f32::INFINITY;
+        for (codeword, modifiers) in ETC_MODIFIERS.iter().enumerate() {
+            let mut err = 0.0f32;
+            let mut sub_indices = [0u32; 8];
+            for y in 0..4 {
+                for x in 0..2 {
+                    let texel = block[y * 4 + sub * 2 + x];
+                    let mut best_dist = f32::INFINITY;
+                    let mut best_index = 0u32;
+                    for index in 0..4u32 {
+                        // Indices 0/1 add the small/large modifier, 2/3
+                        // subtract it.
+                        let modifier = if index < 2 {
+                            modifiers[index as usize]
+                        } else {
+                            -modifiers[index as usize - 2]
+                        };
+                        let mut dist = 0.0f32;
+                        for (channel, value) in expanded.iter().enumerate() {
+                            let decoded =
+                                (*value as i32 + modifier).clamp(0, 255) as f32 / 255.0;
+                            let delta = texel[channel] - decoded;
+                            dist += delta * delta;
+                        }
+                        if dist < best_dist {
+                            best_dist = dist;
+                            best_index = index;
+                        }
+                    }
+                    err += best_dist;
+                    sub_indices[y * 2 + x] = best_index;
+                }
+            }
+            if err < best_err {
+                best_err = err;
+                codewords[sub] = codeword as u32;
+                for y in 0..4 {
+                    for x in 0..2 {
+                        indices[y * 4 + sub * 2 + x] = sub_indices[y * 2 + x];
+                    }
+                }
+            }
+        }
+    }
+    let mut out = [0u8; 8];
+    out[0] = (bases[0][0] << 4 | bases[1][0]) as u8;
+    out[1] = (bases[0][1] << 4 | bases[1][1]) as u8;
+    out[2] = (bases[0][2] << 4 | bases[1][2]) as u8;
+    out[3] = (codewords[0] << 5 | codewords[1] << 2) as u8;
+    // Index bits are stored column major, msb and lsb planes separately.
+    let mut msb = 0u32;
+    let mut lsb = 0u32;
+    for x in 0..4 {
+        for y in 0..4 {
+            let pixel = x * 4 + y;
+            let index = indices[y * 4 + x];
+            msb |= (index >> 1 & 1) << pixel;
+            lsb |= (index & 1) << pixel;
+        }
+    }
+    out[4..8].copy_from_slice(&(msb << 16 | lsb).to_be_bytes());
+    out
+}
+
+/// Encodes one channel of a block as a 8 bytes EAC block.
+///
+/// `eleven` selects the 11 bits value space of R11/RG11 blocks; alpha
+/// blocks of ETC2 RGBA work in the plain 8 bits space.
+fn encode_eac_block(block: &[[f32; 4]; 16], channel: usize, eleven: bool) -> [u8; 8] {
+    let mut sum = 0.0f32;
+    for texel in block {
+        sum += texel[channel];
+    }
+    let base = ((sum / 16.0).clamp(0.0, 1.0) * 255.0 + 0.5) as i32;
+    let mut best = (0usize, 1i32, [0u64; 16]);
+    let mut best_err = f32::INFINITY;
+    for (table, modifiers) in EAC_MODIFIERS.iter().enumerate() {
+        for multiplier in 1..16i32 {
+            let mut err = 0.0f32;
+            let mut indices = [0u64; 16];
+            for (out, texel) in indices.iter_mut().zip(block) {
+                let mut best_dist = f32::INFINITY;
+                for (index, modifier) in modifiers.iter().enumerate() {
+                    let decoded = if eleven {
+                        (base * 8 + 4 + modifier * multiplier * 8).clamp(0, 2047) as f32 / 2047.0
+                    } else {
+                        (base + modifier * multiplier).clamp(0, 255) as f32 / 255.0
+                    };
+                    let dist = (texel[channel] - decoded).abs();
+                    if dist < best_dist {
+                        best_dist = dist;
+                        *out = index as u64;
+                    }
+                }
+                err += best_dist;
+            }
+            if err < best_err {
+                best_err = err;
+                best = (table, multiplier, indices);
+            }
+        }
+    }
+    let (table, multiplier, indices) = best;
+    let mut out = [0u8; 8];
+    out[0] = base as u8;
+    out[1] = ((multiplier as u8) << 4) | table as u8;
+    // Index bits are stored column major, msb first.
+    let mut word = 0u64;
+    for x in 0..4usize {
+        for y in 0..4usize {
+            let pixel = x * 4 + y;
+            word |= indices[y * 4 + x] << (45 - 3 * pixel);
+        }
+    }
+    out[2..8].copy_from_slice(&word.to_be_bytes()[2..8]);
+    out
+}
+
+/// Interpolation levels of the 4 levels ASTC weight quantization, in the
+/// 0..64 space the decoder interpolates endpoints with.
+const ASTC_WEIGHTS: [u32; 4] = [0, 21, 43, 64];
+
+/// Computes the bilinear infill coefficients of one texel over the 4x4
+/// weight grid, exactly as the decoder derives them: four (grid index,
+/// coefficient) pairs whose coefficients sum to 16.
+///
+/// Grid points past the right and bottom edges only ever carry a zero
+/// coefficient; their indices are clamped so lookups stay in bounds.
+fn astc_infill(x: u32, y: u32, dims: (u32, u32)) -> [(usize, u32); 4] {
+    let coeff = |t: u32, dim: u32| -> (usize, u32) {
+        let d = (1024 + dim / 2) / (dim - 1);
+        let g = (d * t * 3 + 32) >> 6;
+        ((g >> 4) as usize, g & 0xF)
+    };
+    let (js, fs) = coeff(x, dims.0);
+    let (jt, ft) = coeff(y, dims.1);
+    let w11 = (fs * ft + 8) >> 4;
+    let base = jt * 4 + js;
+    [
+        (base, 16 + w11 - fs - ft),
+        ((base + 1).min(15), fs - w11),
+        ((base + 4).min(15), ft - w11),
+        ((base + 5).min(15), w11),
+    ]
+}
+
+/// Encodes a block as a 16 bytes ASTC block of a single fixed layout, like
+/// the one-subset BC7 encoder: one partition, single plane, LDR RGBA
+/// direct endpoints at 8 bits and a 4x4 grid of 2 bits weights.
+///
+/// The 4x4 grid maps one weight per texel on 4x4 blocks; the larger
+/// footprints infill it bilinearly, so gradients inside a block survive at
+/// every supported size. Endpoints start from the bounding box of the
+/// footprint and are refined by the least squares passes of the quality,
+/// like the BC encoders.
+fn encode_astc_block(
+    texture: &dyn Texture,
+    bx: u32,
+    by: u32,
+    dims: (u32, u32),
+    quality: Quality,
+) -> [u8; 16] {
+    // Fetch the footprint, clamping reads at the edges like fetch_block.
+    let count = (dims.0 * dims.1) as usize;
+    let mut block = [[0.0f32; 4]; 64];
+    for dy in 0..dims.1 {
+        for dx in 0..dims.0 {
+            let x = (bx * dims.0 + dx).min(texture.width() - 1);
+            let y = (by * dims.1 + dy).min(texture.height() - 1);
+            block[(dy * dims.0 + dx) as usize] = texture.get(x, y).normalize();
+        }
+    }
+    let block = &block[..count];
+    let mut e0 = [f32::INFINITY; 4];
+    let mut e1 = [f32::NEG_INFINITY; 4];
+    for texel in block {
+        for channel in 0..4 {
+            e0[channel] = e0[channel].min(texel[channel]);
+            e1[channel] = e1[channel].max(texel[channel]);
+        }
+    }
+    // Ideal interpolation factor of every texel: its projection onto the
+    // endpoint axis.
+    let factors = |e0: [f32; 4], e1: [f32; 4], out: &mut [f32]| {
+        let mut axis = [0.0f32; 4];
+        let mut len = 0.0f32;
+        for channel in 0..4 {
+            axis[channel] = e1[channel] - e0[channel];
+            len += axis[channel] * axis[channel];
+        }
+        for (factor, texel) in out.iter_mut().zip(block) {
+            *factor = match len > 1e-10 {
+                true => {
+                    let mut dot = 0.0f32;
+                    for channel in 0..4 {
+                        dot += (texel[channel] - e0[channel]) * axis[channel];
+                    }
+                    (dot / len).clamp(0.0, 1.0)
+                }
+                false => 0.0,
+            };
+        }
+    };
+    let mut ts = [0.0f32; 64];
+    for _ in 0..quality.refinements() {
+        factors(e0, e1, &mut ts[..count]);
+        let mut alpha = 0.0f32;
+        let mut beta = 0.0f32;
+        let mut gamma = 0.0f32;
+        for t in &ts[..count] {
+            alpha += (1.0 - t) * (1.0 - t);
+            beta += t * (1.0 - t);
+            gamma += t * t;
+        }
+        let det = alpha * gamma - beta * beta;
+        if det.abs() < 1e-6 {
+            break;
+        }
+        for channel in 0..4 {
+            let mut p = 0.0f32;
+            let mut q = 0.0f32;
+            for (texel, t) in block.iter().zip(&ts[..count]) {
+                p += texel[channel] * (1.0 - t);
+                q += texel[channel] * t;
+            }
+            e0[channel] = ((gamma * p - beta * q) / det).clamp(0.0, 1.0);
+            e1[channel] = ((alpha * q - beta * p) / det).clamp(0.0, 1.0);
+        }
+    }
+    factors(e0, e1, &mut ts[..count]);
+    // Grid weights: accumulate every texel's ideal factor through the
+    // transposed infill coefficients the decoder interpolates with, then
+    // quantize each grid point to the nearest of the four levels.
+    let mut num = [0.0f32; 16];
+    let mut den = [0.0f32; 16];
+    for (i, t) in ts[..count].iter().enumerate() {
+        let x = i as u32 % dims.0;
+        let y = i as u32 / dims.0;
+        for (index, coeff) in astc_infill(x, y, dims) {
+            num[index] += coeff as f32 * t;
+            den[index] += coeff as f32;
+        }
+    }
+    let mut weights = [0u64; 16];
+    for (weight, (num, den)) in weights.iter_mut().zip(num.iter().zip(den)) {
+        let target = match den > 0.0 {
+            true => num / den * 64.0,
+            false => 0.0,
+        };
+        let mut best_dist = f32::INFINITY;
+        for (index, level) in ASTC_WEIGHTS.iter().enumerate() {
+            let dist = (target - *level as f32).abs();
+            if dist < best_dist {
+                best_dist = dist;
+                *weight = index as u64;
+            }
+        }
+    }
+    let quantize = |endpoint: [f32; 4]| -> [u64; 4] {
+        endpoint.map(|value| (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u64)
+    };
+    let mut q0 = quantize(e0);
+    let mut q1 = quantize(e1);
+    // LDR RGBA direct blue-contracts the endpoints when the first sums
+    // brighter than the second; swapping them and inverting the weights
+    // keeps the plain decode path instead.
+    if q0[0] + q0[1] + q0[2] > q1[0] + q1[1] + q1[2] {
+        std::mem::swap(&mut q0, &mut q1);
+        for weight in &mut weights {
+            *weight = 3 - *weight;
+        }
+    }
+    let mut writer = BlockWriter::new();
+    // Block mode 0x042: single plane, 4x4 weight grid, 4 levels weights.
+    writer.push(0x042, 11);
+    // One partition, color endpoint mode 12 (LDR RGBA direct).
+    writer.push(0, 2);
+    writer.push(12, 4);
+    // 8 bits per value is the widest quantization the 79 bits left below
+    // the weight stream can hold, which is what the decoder infers.
+    for channel in 0..4 {
+        writer.push(q0[channel], 8);
+        writer.push(q1[channel], 8);
+    }
+    // The weight stream fills the block from the top down, bit-reversed.
+    let mut out = writer.data;
+    for (i, weight) in weights.iter().enumerate() {
+        for bit in 0..2 {
+            if weight >> bit & 1 != 0 {
+                let position = 127 - (i * 2 + bit);
+                out[position / 8] |= 1 << (position % 8);
+            }
+        }
+    }
+    out
+}
+
+/// Checks that an encoding can hold the given texture format.
+///
+/// Callers of [encode_into] must validate the pair through this first.
+pub fn check(format: Format, encoding: Encoding) -> Result<(), EncodeError> {
+    match encoding {
+        Encoding::Raw => return Ok(()),
+        Encoding::Bc1
+        | Encoding::Bc3
+        | Encoding::Bc4
+        | Encoding::Bc5
+        | Encoding::Bc7
+        | Encoding::Astc4x4
+        | Encoding::Astc5x5
+        | Encoding::Astc6x6
+        | Encoding::Astc8x8
+        | Encoding::Etc2Rgb
+        | Encoding::Etc2Rgba
+        | Encoding::EacR11
+        | Encoding::EacRg11 => {
+            if !matches!(
+                format,
+                Format::RGBA8 | Format::L8 | Format::RGBA16 | Format::R16 | Format::RG8 | Format::RG16
+            ) {
+                return Err(EncodeError::Unsupported(encoding, format));
+            }
+        }
+        Encoding::Bc6h => {
+            if !matches!(format, Format::RGBAF32 | Format::F32 | Format::RGBA16F) {
+                return Err(EncodeError::Unsupported(encoding, format));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the size in bytes of the encoded payload of a texture.
+///
+/// Containers rely on this to write their headers and level indices before
+/// the payload is produced.
+pub fn encoded_size(texture: &crate::texture::OutputTexture, encoding: Encoding) -> u64 {
+    match encoding {
+        Encoding::Raw => texture.data().len() as u64,
+        _ => {
+            let dims = encoding.block_dims();
+            let blocks_x = texture.width().div_ceil(dims.0) as u64;
+            let blocks_y = texture.height().div_ceil(dims.1) as u64;
+            blocks_x * blocks_y * encoding.block_size() as u64
+        }
+    }
+}
+
+/// Streams the encoded texel payload of a texture into a writer.
+///
+/// Raw writes the payload as stored; block encodings walk the texture in
+/// 4x4 blocks, clamping reads at the edges of non multiple-of-4 sizes.
+/// Blocks are written as they are encoded, so only one block is ever held
+/// in memory; the number of bytes written always matches [encoded_size].
+/// The format must have been validated through [check].
+pub fn encode_into(
+    texture: &crate::texture::OutputTexture,
+    encoding: Encoding,
+    quality: Quality,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    if encoding == Encoding::Raw {
+        return out.write_all(texture.data());
+    }
+    let dims = encoding.block_dims();
+    let blocks_x = texture.width().div_ceil(dims.0);
+    let blocks_y = texture.height().div_ceil(dims.1);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            if let Encoding::Astc4x4
+            | Encoding::Astc5x5
+            | Encoding::Astc6x6
+            | Encoding::Astc8x8 = encoding
+            {
+                out.write_all(&encode_astc_block(texture, bx, by, dims, quality))?;
+                continue;
+            }
+            let block = fetch_block(texture, bx, by);
+            match encoding {
+                Encoding::Bc1 => out.write_all(&encode_bc1_block(&block))?,
+                Encoding::Bc3 => {
+                    out.write_all(&encode_bc4_block(&block, 3))?;
+                    out.write_all(&encode_bc1_block(&block))?;
+                }
+                Encoding::Bc4 => out.write_all(&encode_bc4_block(&block, 0))?,
+                Encoding::Bc5 => {
+                    out.write_all(&encode_bc4_block(&block, 0))?;
+                    out.write_all(&encode_bc4_block(&block, 1))?;
+                }
+                Encoding::Bc6h => out.write_all(&encode_bc6h_block(&block, quality))?,
+                Encoding::Bc7 => out.write_all(&encode_bc7_block(&block, quality))?,
+                Encoding::Etc2Rgb => out.write_all(&encode_etc2_rgb_block(&block))?,
+                Encoding::Etc2Rgba => {
+                    out.write_all(&encode_eac_block(&block, 3, false))?;
+                    out.write_all(&encode_etc2_rgb_block(&block))?;
+                }
+                Encoding::EacR11 => out.write_all(&encode_eac_block(&block, 0, true))?,
+                Encoding::EacRg11 => {
+                    out.write_all(&encode_eac_block(&block, 0, true))?;
+                    out.write_all(&encode_eac_block(&block, 1, true))?;
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encodes the full texel payload of a texture in memory.
+///
+/// A convenience around [check] and [encode_into] for callers that need
+/// the whole payload at once; container writers stream instead.
+pub fn encode(
+    texture: &crate::texture::OutputTexture,
+    encoding: Encoding,
+    quality: Quality,
+) -> Result<Vec<u8>, EncodeError> {
+    check(texture.format(), encoding)?;
+    let mut data = Vec::with_capacity(encoded_size(texture, encoding) as usize);
+    encode_into(texture, encoding, quality, &mut data)
+        .expect("Writing to a vector cannot fail");
+    Ok(data)
+}
@@ -0,0 +1,170 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The alpha bleed filter: extends the color of visible texels into nearby
+//! fully transparent ones, keeping their alpha at zero.
+//!
+//! Without this pass, bilinear filtering and mipmapping average in the
+//! (usually black) color hidden behind transparent texels and draw dark
+//! fringes around sprite edges.
+//!
+//! # Parameters
+//!
+//! * `radius`: how far in texels colors are extended (default 4).
+//! * `threshold`: the normalized alpha above which a texel counts as
+//!   visible (default 0.0, i.e. any non zero alpha).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "alpha_bleed",
+    description: "Extends the color of visible texels into nearby fully transparent ones.",
+    params: &[
+        ParameterSchema {
+            name: "radius",
+            ty: ParameterType::Int,
+            default: Some("4"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "threshold",
+            ty: ParameterType::Float,
+            default: Some("0.0"),
+            required: false,
+        },
+    ],
+};
+
+/// The alpha bleed filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let radius = match params.get("radius") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("radius"))?,
+            None => 4,
+        };
+        if radius < 1 {
+            return Err(FilterError::InvalidParameter("radius"));
+        }
+        let threshold = match params.get("threshold") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("threshold"))? as f32,
+            None => 0.0,
+        };
+        if !(0.0..1.0).contains(&threshold) {
+            return Err(FilterError::InvalidParameter("threshold"));
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            radius,
+            threshold,
+            format: frame.format,
+        })
+    }
+}
+
+/// The alpha bleed filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    radius: i64,
+    threshold: f32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let texel = self.previous.get(x, y);
+        let rgba = texel.normalize();
+        if rgba[3] > self.threshold {
+            return texel;
+        }
+        // Average the visible texels of the nearest ring that has any, so
+        // the bled color follows the closest edge.
+        for ring in 1..=self.radius {
+            let mut sum = [0.0f32; 3];
+            let mut count = 0u32;
+            for dy in -ring..=ring {
+                for dx in -ring..=ring {
+                    if dx.abs() != ring && dy.abs() != ring {
+                        continue;
+                    }
+                    let sx = x as i64 + dx;
+                    let sy = y as i64 + dy;
+                    if !(0..self.previous.width() as i64).contains(&sx)
+                        || !(0..self.previous.height() as i64).contains(&sy)
+                    {
+                        continue;
+                    }
+                    let sample = self.previous.get(sx as u32, sy as u32).normalize();
+                    if sample[3] > self.threshold {
+                        sum[0] += sample[0];
+                        sum[1] += sample[1];
+                        sum[2] += sample[2];
+                        count += 1;
+                    }
+                }
+            }
+            if count > 0 {
+                let count = count as f32;
+                return Texel::from_normalized_dithered(
+                    self.format,
+                    [sum[0] / count, sum[1] / count, sum[2] / count, rgba[3]],
+                    x,
+                    y,
+                );
+            }
+        }
+        texel
+    }
+}
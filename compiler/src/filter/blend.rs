@@ -0,0 +1,197 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The blend filter: combines the previous pass with a texture using a
+//! blend mode.
+//!
+//! # Parameters
+//!
+//! * `base`: the texture blended over the previous pass.
+//! * `mode`: the blend mode, one of "multiply", "screen", "overlay", "add",
+//!   "subtract", "lighten" or "darken" (default "multiply").
+//! * `opacity`: how much of the blended result to keep, the rest being the
+//!   previous pass (default 1.0).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "blend",
+    description: "Combines the previous pass with a texture using a blend mode.",
+    params: &[
+        ParameterSchema {
+            name: "base",
+            ty: ParameterType::Texture,
+            default: None,
+            required: true,
+        },
+        ParameterSchema {
+            name: "mode",
+            ty: ParameterType::String,
+            default: Some("multiply"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "opacity",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+    ],
+};
+
+/// The Photoshop style blend modes.
+enum Mode {
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+    Subtract,
+    Lighten,
+    Darken,
+}
+
+impl Mode {
+    fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "multiply" => Some(Mode::Multiply),
+            "screen" => Some(Mode::Screen),
+            "overlay" => Some(Mode::Overlay),
+            "add" => Some(Mode::Add),
+            "subtract" => Some(Mode::Subtract),
+            "lighten" => Some(Mode::Lighten),
+            "darken" => Some(Mode::Darken),
+            _ => None,
+        }
+    }
+
+    fn blend(&self, bottom: f32, top: f32) -> f32 {
+        match self {
+            Mode::Multiply => bottom * top,
+            Mode::Screen => 1.0 - (1.0 - bottom) * (1.0 - top),
+            Mode::Overlay => {
+                if bottom < 0.5 {
+                    2.0 * bottom * top
+                } else {
+                    1.0 - 2.0 * (1.0 - bottom) * (1.0 - top)
+                }
+            }
+            Mode::Add => (bottom + top).min(1.0),
+            Mode::Subtract => (bottom - top).max(0.0),
+            Mode::Lighten => bottom.max(top),
+            Mode::Darken => bottom.min(top),
+        }
+    }
+}
+
+/// The blend filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let base = frame.require_input(params, "base")?;
+        let mode = match params.get("mode") {
+            Some(v) => v
+                .as_string()
+                .and_then(Mode::from_name)
+                .ok_or(FilterError::InvalidParameter("mode"))?,
+            None => Mode::Multiply,
+        };
+        let opacity = match params.get("opacity") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("opacity"))? as f32,
+            None => 1.0,
+        };
+        if !(0.0..=1.0).contains(&opacity) {
+            return Err(FilterError::InvalidParameter("opacity"));
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            base,
+            mode,
+            opacity,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The blend filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    base: Arc<dyn Texture>,
+    mode: Mode,
+    opacity: f32,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let u = x as f64 / self.width as f64;
+        let v = y as f64 / self.height as f64;
+        let bottom = self.previous.get(x, y).normalize();
+        let top = self.base.sample(u, v).normalize();
+        let mut rgba = [0.0; 4];
+        for i in 0..3 {
+            let blended = self.mode.blend(bottom[i], top[i]);
+            // The top layer's alpha scales its contribution, as in the usual
+            // "blend then composite" layer model.
+            let weight = self.opacity * top[3];
+            rgba[i] = bottom[i] + (blended - bottom[i]) * weight;
+        }
+        rgba[3] = bottom[3];
+        Texel::from_normalized_dithered(self.format, rgba, x, y)
+    }
+}
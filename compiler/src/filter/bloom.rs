@@ -0,0 +1,226 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The bloom filter: extracts the bright areas of the previous pass, blurs
+//! them and adds them back, baking glow into emissive textures.
+//!
+//! The bright pass keeps `max(0, channel - threshold)`, so with the default
+//! threshold of 1.0 only HDR values bloom. The blur is a separable gaussian
+//! run when the filter function is built, like the gaussian filter.
+//!
+//! # Parameters
+//!
+//! * `threshold`: the brightness above which a channel blooms (default 1.0).
+//! * `intensity`: a multiplier on the added glow (default 1.0).
+//! * `ksize`: the blur kernel size in texels (default 15).
+//! * `sigma`: the standard deviation of the blur (default 4.0).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::filter::Viewport;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "bloom",
+    description: "Extracts the bright areas of the previous pass, blurs them and adds them back.",
+    params: &[
+        ParameterSchema {
+            name: "threshold",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "intensity",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "ksize",
+            ty: ParameterType::Int,
+            default: Some("15"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "sigma",
+            ty: ParameterType::Float,
+            default: Some("4.0"),
+            required: false,
+        },
+    ],
+};
+
+fn gaussian1d(x: f64, sigma: f64) -> f64 {
+    let sigma2 = sigma * sigma;
+    (1.0 / (2.0 * std::f64::consts::PI * sigma2).sqrt()) * (-(x * x) / (2.0 * sigma2)).exp()
+}
+
+/// The bloom filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let threshold = match params.get("threshold") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("threshold"))? as f32,
+            None => 1.0,
+        };
+        if threshold < 0.0 {
+            return Err(FilterError::InvalidParameter("threshold"));
+        }
+        let intensity = match params.get("intensity") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("intensity"))? as f32,
+            None => 1.0,
+        };
+        if intensity < 0.0 {
+            return Err(FilterError::InvalidParameter("intensity"));
+        }
+        let ksize = match params.get("ksize") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("ksize"))?,
+            None => 15,
+        };
+        if ksize < 1 {
+            return Err(FilterError::InvalidParameter("ksize"));
+        }
+        let sigma = match params.get("sigma") {
+            Some(v) => v.as_float().ok_or(FilterError::InvalidParameter("sigma"))?,
+            None => 4.0,
+        };
+        let half = ksize / 2;
+        let mut kernel = Vec::with_capacity(ksize as usize);
+        for d in -half..=half {
+            kernel.push(gaussian1d(d as f64, sigma));
+        }
+        let sum: f64 = kernel.iter().sum();
+        for weight in &mut kernel {
+            *weight /= sum;
+        }
+        // Bright pass then horizontal blur over the viewport, both run once
+        // up front; the per texel function taps the intermediate vertically.
+        let previous = &frame.previous;
+        let viewport = frame.viewport;
+        let (vw, vh) = (viewport.width as usize, viewport.height as usize);
+        let mut bright = Vec::with_capacity(vw * vh);
+        for y in viewport.y..viewport.y + viewport.height {
+            for x in viewport.x..viewport.x + viewport.width {
+                let rgba = previous.get(x, y).normalize();
+                bright.push([
+                    (rgba[0] - threshold).max(0.0) as f64,
+                    (rgba[1] - threshold).max(0.0) as f64,
+                    (rgba[2] - threshold).max(0.0) as f64,
+                ]);
+            }
+        }
+        let mut horizontal = Vec::with_capacity(bright.len());
+        for y in 0..vh {
+            for x in 0..vw {
+                let mut sum = [0.0f64; 3];
+                for (tap, weight) in kernel.iter().enumerate() {
+                    let sx = (x as i64 + tap as i64 - half).clamp(0, vw as i64 - 1) as usize;
+                    let rgb = bright[y * vw + sx];
+                    sum[0] += rgb[0] * weight;
+                    sum[1] += rgb[1] * weight;
+                    sum[2] += rgb[2] * weight;
+                }
+                horizontal.push(sum);
+            }
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            intensity,
+            ksize,
+            kernel,
+            horizontal,
+            viewport,
+            format: frame.format,
+        })
+    }
+}
+
+/// The bloom filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    intensity: f32,
+    ksize: i64,
+    kernel: Vec<f64>,
+    horizontal: Vec<[f64; 3]>,
+    viewport: Viewport,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let half = self.ksize / 2;
+        // Vertical taps clamp to the viewport, like the gaussian filter.
+        let top = self.viewport.y as i64;
+        let bottom = top + self.viewport.height as i64 - 1;
+        let mut glow = [0.0f64; 3];
+        for (tap, weight) in self.kernel.iter().enumerate() {
+            let sy = (y as i64 + tap as i64 - half).clamp(top, bottom) as u32;
+            let index = (sy - self.viewport.y) * self.viewport.width + (x - self.viewport.x);
+            let rgb = self.horizontal[index as usize];
+            glow[0] += rgb[0] * weight;
+            glow[1] += rgb[1] * weight;
+            glow[2] += rgb[2] * weight;
+        }
+        let [r, g, b, a] = self.previous.get(x, y).normalize();
+        let rgba = [
+            r + glow[0] as f32 * self.intensity,
+            g + glow[1] as f32 * self.intensity,
+            b + glow[2] as f32 * self.intensity,
+            a,
+        ];
+        Texel::from_normalized_dithered(self.format, rgba, x, y)
+    }
+}
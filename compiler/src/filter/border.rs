@@ -0,0 +1,201 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The border filter: pads the previous pass with a pixel border, shrinking
+//! the content inward.
+//!
+//! Padding before block compression keeps atlas entries from bleeding into
+//! each other once the encoder averages texels across block boundaries.
+//!
+//! # Parameters
+//!
+//! * `size`: the border thickness in texels (default 1).
+//! * `mode`: how border texels are filled, one of "clamp", "mirror" or
+//!   "constant" (default "clamp").
+//! * `color`: the normalized RGBA fill color for "constant" mode
+//!   (default 0,0,0,0).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "border",
+    description: "Pads the previous pass with a pixel border, shrinking the content inward.",
+    params: &[
+        ParameterSchema {
+            name: "size",
+            ty: ParameterType::Int,
+            default: Some("1"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "mode",
+            ty: ParameterType::String,
+            default: Some("clamp"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "color",
+            ty: ParameterType::Vector4,
+            default: Some("0,0,0,0"),
+            required: false,
+        },
+    ],
+};
+
+/// The border fill modes.
+enum Mode {
+    /// Repeats the edge texel of the content.
+    Clamp,
+
+    /// Mirrors the content across its edge.
+    Mirror,
+
+    /// Fills with a constant color.
+    Constant([f32; 4]),
+}
+
+/// The border filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let size = match params.get("size") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("size"))?,
+            None => 1,
+        };
+        if size < 1
+            || size as u64 * 2 >= frame.width as u64
+            || size as u64 * 2 >= frame.height as u64
+        {
+            return Err(FilterError::InvalidParameter("size"));
+        }
+        let mode = match params.get("mode") {
+            Some(v) => match v.as_string().ok_or(FilterError::InvalidParameter("mode"))? {
+                "clamp" => Mode::Clamp,
+                "mirror" => Mode::Mirror,
+                "constant" => {
+                    let color = match params.get("color") {
+                        Some(v) => {
+                            let v = v
+                                .as_vector4()
+                                .ok_or(FilterError::InvalidParameter("color"))?;
+                            [v[0] as f32, v[1] as f32, v[2] as f32, v[3] as f32]
+                        }
+                        None => [0.0, 0.0, 0.0, 0.0],
+                    };
+                    Mode::Constant(color)
+                }
+                _ => return Err(FilterError::InvalidParameter("mode")),
+            },
+            None => Mode::Clamp,
+        };
+        Ok(Func {
+            previous: frame.previous.clone(),
+            mode,
+            size: size as u32,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The border filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    mode: Mode,
+    size: u32,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let size = self.size as i64;
+        let content_w = (self.width - 2 * self.size) as i64;
+        let content_h = (self.height - 2 * self.size) as i64;
+        // The content shrinks into the inner rectangle; border texels reach
+        // back out of it and are resolved by the fill mode.
+        let cx = (x as i64 - size) * self.width as i64 / content_w;
+        let cy = (y as i64 - size) * self.height as i64 / content_h;
+        let inside =
+            (0..self.width as i64).contains(&cx) && (0..self.height as i64).contains(&cy);
+        if !inside {
+            if let Mode::Constant(color) = self.mode {
+                return Texel::from_normalized_dithered(self.format, color, x, y);
+            }
+        }
+        let (sx, sy) = match self.mode {
+            Mode::Mirror => (
+                mirror(cx, self.width as i64),
+                mirror(cy, self.height as i64),
+            ),
+            _ => (
+                cx.clamp(0, self.width as i64 - 1),
+                cy.clamp(0, self.height as i64 - 1),
+            ),
+        };
+        self.previous.get(sx as u32, sy as u32)
+    }
+}
+
+/// Reflects an out of bounds coordinate back across the nearest edge.
+fn mirror(value: i64, len: i64) -> i64 {
+    if value < 0 {
+        (-value - 1).min(len - 1)
+    } else if value >= len {
+        (2 * len - value - 1).max(0)
+    } else {
+        value
+    }
+}
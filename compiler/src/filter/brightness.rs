@@ -0,0 +1,115 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The brightness filter: adds a constant offset to the RGB channels of the
+//! previous pass, leaving alpha untouched.
+//!
+//! # Parameters
+//!
+//! * `brightness`: the offset to add to each channel, in normalized units.
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "brightness",
+    description: "Adds a constant offset to the RGB channels of the previous pass.",
+    params: &[
+        ParameterSchema {
+            name: "brightness",
+            ty: ParameterType::Float,
+            default: None,
+            required: true,
+        },
+    ],
+};
+
+/// The brightness filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let brightness = params
+            .get("brightness")
+            .ok_or(FilterError::MissingParameter("brightness"))?
+            .as_float()
+            .ok_or(FilterError::InvalidParameter("brightness"))? as f32;
+        Ok(Func {
+            previous: frame.previous.clone(),
+            format: frame.format,
+            brightness,
+        })
+    }
+}
+
+/// The brightness filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    format: Format,
+    brightness: f32,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let [r, g, b, a] = self.previous.get(x, y).normalize();
+        Texel::from_normalized_dithered(
+            self.format,
+            [
+                r + self.brightness,
+                g + self.brightness,
+                b + self.brightness,
+                a,
+            ],
+            x,
+            y,
+        )
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+
+//! The channel pack filter: packs the luminance of up to four source
+//! textures into the channels of the render target, producing
+//! occlusion/roughness/metallic style maps in one pass.
+//!
+//! Sources are sampled with nearest filtering at the render target
+//! resolution, so they do not need matching sizes.
+//!
+//! # Parameters
+//!
+//! * `r`, `g`, `b`, `a`: the texture packed into each output channel.
+//!   Missing color channels pack as 0 and a missing alpha packs as 1.
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "channel_pack",
+    description: "Packs the luminance of up to four source textures into the output channels.",
+    params: &[
+        ParameterSchema {
+            name: "r",
+            ty: ParameterType::Texture,
+            default: None,
+            required: false,
+        },
+        ParameterSchema {
+            name: "g",
+            ty: ParameterType::Texture,
+            default: None,
+            required: false,
+        },
+        ParameterSchema {
+            name: "b",
+            ty: ParameterType::Texture,
+            default: None,
+            required: false,
+        },
+        ParameterSchema {
+            name: "a",
+            ty: ParameterType::Texture,
+            default: None,
+            required: false,
+        },
+    ],
+};
+
+/// The channel pack filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let sources = [
+            frame.input(params, "r")?,
+            frame.input(params, "g")?,
+            frame.input(params, "b")?,
+            frame.input(params, "a")?,
+        ];
+        if sources.iter().all(|v| v.is_none()) {
+            return Err(FilterError::MissingParameter("r"));
+        }
+        Ok(Func {
+            sources,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The channel pack filter function.
+pub struct Func {
+    sources: [Option<Arc<dyn Texture>>; 4],
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let u = x as f64 / self.width as f64;
+        let v = y as f64 / self.height as f64;
+        let mut rgba = [0.0, 0.0, 0.0, 1.0];
+        for (channel, source) in rgba.iter_mut().zip(&self.sources) {
+            if let Some(source) = source {
+                let [r, g, b, _] = source.sample(u, v).normalize();
+                // BT.709 luma, matching the greyscale filter's default.
+                *channel = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            }
+        }
+        Texel::from_normalized_dithered(self.format, rgba, x, y)
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The composite filter: alpha blends a texture onto the previous pass at a
+//! given placement, for decals and watermark stamping.
+//!
+//! The placement is normalized to the render target: an offset of 0.25,0.25
+//! and a scale of 0.5,0.5 stamps the texture centered, at half the target
+//! size. Texels outside the placement keep the previous pass untouched.
+//!
+//! # Parameters
+//!
+//! * `base`: the texture stamped onto the previous pass.
+//! * `offset`: the normalized position of the stamp's top left corner
+//!   (default 0,0).
+//! * `scale`: the normalized size of the stamp (default 1,1).
+//! * `opacity`: a multiplier on the stamp's alpha (default 1.0).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "composite",
+    description: "Alpha blends a texture onto the previous pass at a given placement.",
+    params: &[
+        ParameterSchema {
+            name: "base",
+            ty: ParameterType::Texture,
+            default: None,
+            required: true,
+        },
+        ParameterSchema {
+            name: "offset",
+            ty: ParameterType::Vector2,
+            default: Some("0,0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "scale",
+            ty: ParameterType::Vector2,
+            default: Some("1,1"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "opacity",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+    ],
+};
+
+/// The composite filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let base = frame.require_input(params, "base")?;
+        let offset = match params.get("offset") {
+            Some(v) => v
+                .as_vector2()
+                .ok_or(FilterError::InvalidParameter("offset"))?,
+            None => [0.0, 0.0],
+        };
+        let scale = match params.get("scale") {
+            Some(v) => v
+                .as_vector2()
+                .ok_or(FilterError::InvalidParameter("scale"))?,
+            None => [1.0, 1.0],
+        };
+        if scale[0] <= 0.0 || scale[1] <= 0.0 {
+            return Err(FilterError::InvalidParameter("scale"));
+        }
+        let opacity = match params.get("opacity") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("opacity"))? as f32,
+            None => 1.0,
+        };
+        if !(0.0..=1.0).contains(&opacity) {
+            return Err(FilterError::InvalidParameter("opacity"));
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            base,
+            offset,
+            scale,
+            opacity,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The composite filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    base: Arc<dyn Texture>,
+    offset: [f64; 2],
+    scale: [f64; 2],
+    opacity: f32,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let bottom = self.previous.get(x, y);
+        // Map the target texel into the stamp's own normalized space; texels
+        // outside [0, 1) are not covered by the stamp.
+        let u = ((x as f64 + 0.5) / self.width as f64 - self.offset[0]) / self.scale[0];
+        let v = ((y as f64 + 0.5) / self.height as f64 - self.offset[1]) / self.scale[1];
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return bottom;
+        }
+        let bottom = bottom.normalize();
+        let top = self.base.sample(u, v).normalize();
+        let alpha = top[3] * self.opacity;
+        let rgba = [
+            bottom[0] + (top[0] - bottom[0]) * alpha,
+            bottom[1] + (top[1] - bottom[1]) * alpha,
+            bottom[2] + (top[2] - bottom[2]) * alpha,
+            bottom[3] + (1.0 - bottom[3]) * alpha,
+        ];
+        Texel::from_normalized_dithered(self.format, rgba, x, y)
+    }
+}
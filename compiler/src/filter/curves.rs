@@ -0,0 +1,266 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+
+//! The curves filter: remaps channels of the previous pass through monotone
+//! cubic splines, as a tonal grading tool.
+//!
+//! Control points are strings of comma separated `input:output` pairs in
+//! normalized units, e.g. "0:0,0.4:0.3,1:1". Splines interpolate with the
+//! Fritsch-Carlson monotone cubic scheme, so a monotone set of points never
+//! overshoots.
+//!
+//! # Parameters
+//!
+//! * `points`: the master curve applied to the RGB channels (default
+//!   identity).
+//! * `points_r`, `points_g`, `points_b`: per channel curves applied after
+//!   the master curve (default identity).
+//! * `points_a`: the curve applied to alpha (default identity).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "curves",
+    description: "Remaps channels of the previous pass through monotone cubic splines.",
+    params: &[
+        ParameterSchema {
+            name: "points",
+            ty: ParameterType::String,
+            default: Some("identity"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "points_r",
+            ty: ParameterType::String,
+            default: Some("identity"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "points_g",
+            ty: ParameterType::String,
+            default: Some("identity"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "points_b",
+            ty: ParameterType::String,
+            default: Some("identity"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "points_a",
+            ty: ParameterType::String,
+            default: Some("identity"),
+            required: false,
+        },
+    ],
+};
+
+/// A monotone cubic spline through a set of control points.
+struct Curve {
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    tangents: Vec<f32>,
+}
+
+impl Curve {
+    /// The identity curve.
+    fn identity() -> Curve {
+        Curve {
+            xs: vec![0.0, 1.0],
+            ys: vec![0.0, 1.0],
+            tangents: vec![1.0, 1.0],
+        }
+    }
+
+    /// Parses a curve from comma separated `input:output` pairs.
+    fn parse(value: &str, name: &'static str) -> Result<Curve, FilterError> {
+        let mut points = Vec::new();
+        for pair in value.split(',') {
+            let (x, y) = pair
+                .split_once(':')
+                .ok_or(FilterError::InvalidParameter(name))?;
+            let x: f32 = x.trim().parse().map_err(|_| FilterError::InvalidParameter(name))?;
+            let y: f32 = y.trim().parse().map_err(|_| FilterError::InvalidParameter(name))?;
+            points.push((x, y));
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if points.len() < 2 || points.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(FilterError::InvalidParameter(name));
+        }
+        let xs: Vec<f32> = points.iter().map(|p| p.0).collect();
+        let ys: Vec<f32> = points.iter().map(|p| p.1).collect();
+        // Fritsch-Carlson tangents: averaged secants, zeroed at local
+        // extrema and limited so each segment stays monotone.
+        let secants: Vec<f32> = xs
+            .windows(2)
+            .zip(ys.windows(2))
+            .map(|(x, y)| (y[1] - y[0]) / (x[1] - x[0]))
+            .collect();
+        let mut tangents = Vec::with_capacity(xs.len());
+        tangents.push(secants[0]);
+        for i in 1..xs.len() - 1 {
+            if secants[i - 1] * secants[i] <= 0.0 {
+                tangents.push(0.0);
+            } else {
+                tangents.push((secants[i - 1] + secants[i]) / 2.0);
+            }
+        }
+        tangents.push(secants[secants.len() - 1]);
+        for (i, secant) in secants.iter().enumerate() {
+            if *secant == 0.0 {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+            let alpha = tangents[i] / secant;
+            let beta = tangents[i + 1] / secant;
+            let norm2 = alpha * alpha + beta * beta;
+            if norm2 > 9.0 {
+                let tau = 3.0 / norm2.sqrt();
+                tangents[i] = tau * alpha * secant;
+                tangents[i + 1] = tau * beta * secant;
+            }
+        }
+        Ok(Curve { xs, ys, tangents })
+    }
+
+    /// Evaluates the curve, clamping inputs to its domain.
+    fn eval(&self, value: f32) -> f32 {
+        let last = self.xs.len() - 1;
+        if value <= self.xs[0] {
+            return self.ys[0];
+        }
+        if value >= self.xs[last] {
+            return self.ys[last];
+        }
+        let i = self.xs.partition_point(|x| *x <= value) - 1;
+        let width = self.xs[i + 1] - self.xs[i];
+        let t = (value - self.xs[i]) / width;
+        let t2 = t * t;
+        let t3 = t2 * t;
+        (2.0 * t3 - 3.0 * t2 + 1.0) * self.ys[i]
+            + (t3 - 2.0 * t2 + t) * width * self.tangents[i]
+            + (-2.0 * t3 + 3.0 * t2) * self.ys[i + 1]
+            + (t3 - t2) * width * self.tangents[i + 1]
+    }
+}
+
+/// Reads an optional curve parameter.
+fn parse_param(
+    params: &ParameterMap,
+    name: &'static str,
+) -> Result<Option<Curve>, FilterError> {
+    match params.get(name) {
+        Some(v) => {
+            let value = v.as_string().ok_or(FilterError::InvalidParameter(name))?;
+            Curve::parse(value, name).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// The curves filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        // The curve set is boxed to keep the filter function union small.
+        Ok(Func {
+            previous: frame.previous.clone(),
+            format: frame.format,
+            curves: Box::new(Channels {
+                master: parse_param(params, "points")?.unwrap_or_else(Curve::identity),
+                red: parse_param(params, "points_r")?.unwrap_or_else(Curve::identity),
+                green: parse_param(params, "points_g")?.unwrap_or_else(Curve::identity),
+                blue: parse_param(params, "points_b")?.unwrap_or_else(Curve::identity),
+                alpha: parse_param(params, "points_a")?.unwrap_or_else(Curve::identity),
+            }),
+        })
+    }
+}
+
+/// The curves applied to each channel.
+struct Channels {
+    master: Curve,
+    red: Curve,
+    green: Curve,
+    blue: Curve,
+    alpha: Curve,
+}
+
+/// The curves filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    format: Format,
+    curves: Box<Channels>,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let [r, g, b, a] = self.previous.get(x, y).normalize();
+        let curves = &self.curves;
+        Texel::from_normalized_dithered(
+            self.format,
+            [
+                curves.red.eval(curves.master.eval(r)),
+                curves.green.eval(curves.master.eval(g)),
+                curves.blue.eval(curves.master.eval(b)),
+                curves.alpha.eval(a),
+            ],
+            x,
+            y,
+        )
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The equirect_to_cube filter: projects a lat-long (equirectangular)
+//! texture onto cube faces.
+//!
+//! In "strip" mode the faces render top to bottom in +X, -X, +Y, -Y, +Z,
+//! -Z order, each the square size of the target width; the target must be
+//! at least six times as tall as it is wide (render targets round up to
+//! powers of two, so rows past the sixth face pad with transparent black).
+//! Since the raw payload is face major, containers store the strip as six
+//! consecutive array layers. A single face can also be rendered on its own
+//! square target, which gives per face control of the resolution.
+//!
+//! # Parameters
+//!
+//! * `base`: the equirectangular source texture.
+//! * `face`: "strip" or one of "px", "nx", "py", "ny", "pz", "nz"
+//!   (default "strip").
+//! * `method`: the interpolation method used to sample the source
+//!   (default "bilinear").
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::SampleMethod;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "equirect_to_cube",
+    description: "Projects a lat-long (equirectangular) texture onto cube faces.",
+    params: &[
+        ParameterSchema {
+            name: "base",
+            ty: ParameterType::Texture,
+            default: None,
+            required: true,
+        },
+        ParameterSchema {
+            name: "face",
+            ty: ParameterType::String,
+            default: Some("strip"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "method",
+            ty: ParameterType::String,
+            default: Some("bilinear"),
+            required: false,
+        },
+    ],
+};
+
+/// The cube faces, in storage order.
+#[derive(Copy, Clone)]
+enum Face {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+const FACES: [Face; 6] = [
+    Face::PosX,
+    Face::NegX,
+    Face::PosY,
+    Face::NegY,
+    Face::PosZ,
+    Face::NegZ,
+];
+
+impl Face {
+    fn from_name(name: &str) -> Option<Face> {
+        match name {
+            "px" => Some(Face::PosX),
+            "nx" => Some(Face::NegX),
+            "py" => Some(Face::PosY),
+            "ny" => Some(Face::NegY),
+            "pz" => Some(Face::PosZ),
+            "nz" => Some(Face::NegZ),
+            _ => None,
+        }
+    }
+
+    /// The world direction through a face texel, with a and b the face
+    /// coordinates in [-1, 1].
+    fn direction(self, a: f32, b: f32) -> [f32; 3] {
+        match self {
+            Face::PosX => [1.0, -b, -a],
+            Face::NegX => [-1.0, -b, a],
+            Face::PosY => [a, 1.0, b],
+            Face::NegY => [a, -1.0, -b],
+            Face::PosZ => [a, -b, 1.0],
+            Face::NegZ => [-a, -b, -1.0],
+        }
+    }
+}
+
+/// The equirect_to_cube filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let base = frame.require_input(params, "base")?;
+        let face = match params.get("face") {
+            Some(v) => match v.as_string().ok_or(FilterError::InvalidParameter("face"))? {
+                "strip" => None,
+                name => Some(Face::from_name(name).ok_or(FilterError::InvalidParameter("face"))?),
+            },
+            None => None,
+        };
+        let method = match params.get("method") {
+            Some(v) => v
+                .as_string()
+                .and_then(SampleMethod::from_name)
+                .ok_or(FilterError::InvalidParameter("method"))?,
+            None => SampleMethod::Bilinear,
+        };
+        match face {
+            // The strip stacks six square faces vertically.
+            None if frame.height < frame.width * 6 => {
+                return Err(FilterError::InvalidParameter("face"));
+            }
+            Some(_) if frame.width != frame.height => {
+                return Err(FilterError::InvalidParameter("face"));
+            }
+            _ => (),
+        }
+        Ok(Func {
+            base,
+            face,
+            method,
+            size: frame.width,
+            format: frame.format,
+        })
+    }
+}
+
+/// The equirect_to_cube filter function.
+pub struct Func {
+    base: Arc<dyn Texture>,
+    face: Option<Face>,
+    method: SampleMethod,
+    size: u32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let (face, fy) = match self.face {
+            Some(face) => (face, y),
+            None => {
+                let slice = (y / self.size) as usize;
+                if slice >= FACES.len() {
+                    return Texel::from_normalized(self.format, [0.0; 4]);
+                }
+                (FACES[slice], y % self.size)
+            }
+        };
+        let a = (x as f32 + 0.5) / self.size as f32 * 2.0 - 1.0;
+        let b = (fy as f32 + 0.5) / self.size as f32 * 2.0 - 1.0;
+        let [dx, dy, dz] = face.direction(a, b);
+        let length = (dx * dx + dy * dy + dz * dz).sqrt();
+        // Lat-long lookup: longitude over u, latitude over v.
+        let u = (dz.atan2(dx) + std::f32::consts::PI) / (2.0 * std::f32::consts::PI);
+        let v = (dy / length).acos() / std::f32::consts::PI;
+        Texel::from_normalized_dithered(
+            self.format,
+            self.base.sample_with(u as f64, v as f64, self.method).normalize(),
+            x,
+            y,
+        )
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The gaussian blur filter.
+//!
+//! The gaussian kernel is separable, so the blur runs as a horizontal pass
+//! precomputed when the filter function is built followed by a vertical pass
+//! over that intermediate, with O(k) taps per texel instead of O(k²).
+//!
+//! # Parameters
+//!
+//! * `ksize`: the kernel size in texels (default 5).
+//! * `sigma`: the standard deviation of the gaussian (default 2.0).
+//! * `normalize`: if set, scales the kernel so its weights sum to one
+//!   (default true).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::filter::Viewport;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "gaussian",
+    description: "Blurs the previous pass with a gaussian kernel.",
+    params: &[
+        ParameterSchema {
+            name: "ksize",
+            ty: ParameterType::Int,
+            default: Some("5"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "sigma",
+            ty: ParameterType::Float,
+            default: Some("2.0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "normalize",
+            ty: ParameterType::Bool,
+            default: Some("true"),
+            required: false,
+        },
+    ],
+};
+
+fn gaussian1d(x: f64, sigma: f64) -> f64 {
+    let sigma2 = sigma * sigma;
+    (1.0 / (2.0 * std::f64::consts::PI * sigma2).sqrt()) * (-(x * x) / (2.0 * sigma2)).exp()
+}
+
+/// The gaussian blur filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let ksize = match params.get("ksize") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("ksize"))?,
+            None => 5,
+        };
+        if ksize < 1 {
+            return Err(FilterError::InvalidParameter("ksize"));
+        }
+        let sigma = match params.get("sigma") {
+            Some(v) => v.as_float().ok_or(FilterError::InvalidParameter("sigma"))?,
+            None => 2.0,
+        };
+        let normalize = match params.get("normalize") {
+            Some(v) => v
+                .as_bool()
+                .ok_or(FilterError::InvalidParameter("normalize"))?,
+            None => true,
+        };
+        // The two 1D passes apply the kernel once per axis, so each axis
+        // gets the 1D weights; their product is the full 2D gaussian.
+        let half = ksize / 2;
+        let mut kernel = Vec::with_capacity(ksize as usize);
+        for d in -half..=half {
+            kernel.push(gaussian1d(d as f64, sigma));
+        }
+        if normalize {
+            let sum: f64 = kernel.iter().sum();
+            for weight in &mut kernel {
+                *weight /= sum;
+            }
+        }
+        // Horizontal pass over the viewport, run once up front; the per
+        // texel function then only taps this intermediate vertically.
+        let previous = &frame.previous;
+        let width = previous.width();
+        let viewport = frame.viewport;
+        let mut horizontal =
+            Vec::with_capacity(viewport.width as usize * viewport.height as usize);
+        for y in viewport.y..viewport.y + viewport.height {
+            for x in viewport.x..viewport.x + viewport.width {
+                let mut sum = [0.0f64; 3];
+                for (tap, weight) in kernel.iter().enumerate() {
+                    let sx = (x as i64 + tap as i64 - half).clamp(0, width as i64 - 1) as u32;
+                    let rgba = previous.get(sx, y).normalize();
+                    sum[0] += rgba[0] as f64 * weight;
+                    sum[1] += rgba[1] as f64 * weight;
+                    sum[2] += rgba[2] as f64 * weight;
+                }
+                horizontal.push(sum);
+            }
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            format: frame.format,
+            ksize,
+            kernel,
+            horizontal,
+            viewport,
+        })
+    }
+}
+
+/// The gaussian blur filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    format: Format,
+    ksize: i64,
+    kernel: Vec<f64>,
+    horizontal: Vec<[f64; 3]>,
+    viewport: Viewport,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let half = self.ksize / 2;
+        // Vertical taps clamp to the viewport; with an apron covering the
+        // kernel radius this only happens at the target edges.
+        let top = self.viewport.y as i64;
+        let bottom = top + self.viewport.height as i64 - 1;
+        let mut sum = [0.0f64; 3];
+        for (tap, weight) in self.kernel.iter().enumerate() {
+            let sy = (y as i64 + tap as i64 - half).clamp(top, bottom) as u32;
+            let index = (sy - self.viewport.y) * self.viewport.width + (x - self.viewport.x);
+            let rgb = self.horizontal[index as usize];
+            sum[0] += rgb[0] * weight;
+            sum[1] += rgb[1] * weight;
+            sum[2] += rgb[2] * weight;
+        }
+        let alpha = self.previous.get(x, y).normalize()[3];
+        Texel::from_normalized_dithered(
+            self.format,
+            [sum[0] as f32, sum[1] as f32, sum[2] as f32, alpha],
+            x,
+            y,
+        )
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The greyscale filter: converts the previous pass to luma.
+//!
+//! # Parameters
+//!
+//! * `standard`: the luma standard, one of "bt601", "bt709", "average" or
+//!   "max" (default "bt601").
+//! * `full_range`: if set, skips the studio swing compression to 16-235
+//!   (default false).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "greyscale",
+    description: "Converts the previous pass to luma.",
+    params: &[
+        ParameterSchema {
+            name: "standard",
+            ty: ParameterType::String,
+            default: Some("bt601"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "full_range",
+            ty: ParameterType::Bool,
+            default: Some("false"),
+            required: false,
+        },
+    ],
+};
+
+enum Standard {
+    Bt601,
+    Bt709,
+    Average,
+    Max,
+}
+
+/// The greyscale filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let standard = match params.get("standard") {
+            Some(v) => match v
+                .as_string()
+                .ok_or(FilterError::InvalidParameter("standard"))?
+            {
+                "bt601" => Standard::Bt601,
+                "bt709" => Standard::Bt709,
+                "average" => Standard::Average,
+                "max" => Standard::Max,
+                _ => return Err(FilterError::InvalidParameter("standard")),
+            },
+            None => Standard::Bt601,
+        };
+        let full_range = match params.get("full_range") {
+            Some(v) => v
+                .as_bool()
+                .ok_or(FilterError::InvalidParameter("full_range"))?,
+            None => false,
+        };
+        Ok(Func {
+            previous: frame.previous.clone(),
+            format: frame.format,
+            standard,
+            full_range,
+        })
+    }
+}
+
+/// The greyscale filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    format: Format,
+    standard: Standard,
+    full_range: bool,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let [r, g, b, a] = self.previous.get(x, y).normalize();
+        let luma = match self.standard {
+            Standard::Bt601 => 0.299 * r + 0.587 * g + 0.114 * b,
+            Standard::Bt709 => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+            Standard::Average => (r + g + b) / 3.0,
+            Standard::Max => r.max(g).max(b),
+        };
+        let luma = if self.full_range {
+            luma
+        } else {
+            (16.0 + 219.0 * luma) / 255.0
+        };
+        Texel::from_normalized_dithered(self.format, [luma, luma, luma, a], x, y)
+    }
+}
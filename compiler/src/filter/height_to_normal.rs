@@ -0,0 +1,168 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+
+//! The height to normal filter: bakes a heightmap into a tangent-space
+//! normal map.
+//!
+//! # Parameters
+//!
+//! * `base`: the heightmap texture to bake; the previous pass is used when
+//!   this is not set.
+//! * `strength`: the slope scale applied to the height differences
+//!   (default 1.0).
+//! * `wrap`: if set, neighbour heights wrap around the edges so tileable
+//!   heightmaps bake tileable normal maps (default false).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "height_to_normal",
+    description: "Bakes a heightmap into a tangent-space normal map.",
+    params: &[
+        ParameterSchema {
+            name: "base",
+            ty: ParameterType::Texture,
+            default: None,
+            required: false,
+        },
+        ParameterSchema {
+            name: "strength",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "wrap",
+            ty: ParameterType::Bool,
+            default: Some("false"),
+            required: false,
+        },
+    ],
+};
+
+/// The height to normal filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let source: Arc<dyn Texture> = match frame.input(params, "base")? {
+            Some(v) => v,
+            None => frame.previous.clone(),
+        };
+        let strength = match params.get("strength") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("strength"))?,
+            None => 1.0,
+        };
+        let wrap = match params.get("wrap") {
+            Some(v) => v.as_bool().ok_or(FilterError::InvalidParameter("wrap"))?,
+            None => false,
+        };
+        Ok(Func {
+            source,
+            strength,
+            wrap,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The height to normal filter function.
+pub struct Func {
+    source: Arc<dyn Texture>,
+    strength: f64,
+    wrap: bool,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+impl Func {
+    /// Reads the height under a render target coordinate, wrapping or
+    /// clamping outside of the target.
+    fn height(&self, x: i64, y: i64) -> f64 {
+        let (x, y) = if self.wrap {
+            (x.rem_euclid(self.width as i64), y.rem_euclid(self.height as i64))
+        } else {
+            (x.clamp(0, self.width as i64 - 1), y.clamp(0, self.height as i64 - 1))
+        };
+        let u = (x as f64 + 0.5) / self.width as f64;
+        let v = (y as f64 + 0.5) / self.height as f64;
+        self.source.sample(u, v).normalize()[0] as f64
+    }
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let x = x as i64;
+        let y = y as i64;
+        // Central differences of the height field drive the slope.
+        let dx = (self.height(x + 1, y) - self.height(x - 1, y)) * self.strength;
+        let dy = (self.height(x, y + 1) - self.height(x, y - 1)) * self.strength;
+        let len = (dx * dx + dy * dy + 1.0).sqrt();
+        Texel::from_normalized_dithered(
+            self.format,
+            [
+                (-dx / len * 0.5 + 0.5) as f32,
+                (-dy / len * 0.5 + 0.5) as f32,
+                (1.0 / len * 0.5 + 0.5) as f32,
+                1.0,
+            ],
+            x as u32,
+            y as u32,
+        )
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+
+//! The HSL filter: adjusts hue, saturation and lightness of the previous
+//! pass, leaving alpha untouched.
+//!
+//! # Parameters
+//!
+//! * `hue`: the hue rotation in degrees (default 0.0).
+//! * `saturation`: the saturation multiplier (default 1.0).
+//! * `lightness`: the lightness offset in normalized units (default 0.0).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "hsl",
+    description: "Adjusts hue, saturation and lightness of the previous pass.",
+    params: &[
+        ParameterSchema {
+            name: "hue",
+            ty: ParameterType::Float,
+            default: Some("0.0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "saturation",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "lightness",
+            ty: ParameterType::Float,
+            default: Some("0.0"),
+            required: false,
+        },
+    ],
+};
+
+/// Converts a normalized RGB color to hue (degrees), saturation and lightness.
+fn rgb_to_hsl([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    if max == min {
+        return [0.0, 0.0, lightness];
+    }
+    let chroma = max - min;
+    let saturation = chroma / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = 60.0
+        * if max == r {
+            ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / chroma + 2.0
+        } else {
+            (r - g) / chroma + 4.0
+        };
+    [hue, saturation, lightness]
+}
+
+/// Converts hue (degrees), saturation and lightness back to normalized RGB.
+fn hsl_to_rgb([hue, saturation, lightness]: [f32; 3]) -> [f32; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let [r, g, b] = match hue as u32 {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+    let offset = lightness - chroma / 2.0;
+    [r + offset, g + offset, b + offset]
+}
+
+/// The HSL filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let hue = match params.get("hue") {
+            Some(v) => v.as_float().ok_or(FilterError::InvalidParameter("hue"))? as f32,
+            None => 0.0,
+        };
+        let saturation = match params.get("saturation") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("saturation"))? as f32,
+            None => 1.0,
+        };
+        let lightness = match params.get("lightness") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("lightness"))? as f32,
+            None => 0.0,
+        };
+        Ok(Func {
+            previous: frame.previous.clone(),
+            format: frame.format,
+            hue,
+            saturation,
+            lightness,
+        })
+    }
+}
+
+/// The HSL filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    format: Format,
+    hue: f32,
+    saturation: f32,
+    lightness: f32,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let [r, g, b, a] = self.previous.get(x, y).normalize();
+        let [hue, saturation, lightness] = rgb_to_hsl([r, g, b]);
+        let [r, g, b] = hsl_to_rgb([
+            hue + self.hue,
+            (saturation * self.saturation).clamp(0.0, 1.0),
+            (lightness + self.lightness).clamp(0.0, 1.0),
+        ]);
+        Texel::from_normalized_dithered(self.format, [r, g, b, a], x, y)
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The lut filter: maps the colors of the previous pass through a color
+//! lookup table laid out as a 2D strip of N blocks of NxN texels.
+//!
+//! # Parameters
+//!
+//! * `lut`: the lookup table texture.
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "lut",
+    description: "Maps the colors of the previous pass through a color lookup table.",
+    params: &[
+        ParameterSchema {
+            name: "lut",
+            ty: ParameterType::Texture,
+            default: None,
+            required: true,
+        },
+    ],
+};
+
+/// The lut filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let lut = frame.require_input(params, "lut")?;
+        if lut.width() != lut.height() * lut.height() {
+            return Err(FilterError::InvalidParameter("lut"));
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            lut,
+            format: frame.format,
+        })
+    }
+}
+
+/// The lut filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    lut: Arc<dyn Texture>,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let [r, g, b, a] = self.previous.get(x, y).normalize();
+        let size = self.lut.height();
+        let max = (size - 1) as f32;
+        let red = (r.clamp(0.0, 1.0) * max) as u32;
+        let green = (g.clamp(0.0, 1.0) * max) as u32;
+        let blue = (b.clamp(0.0, 1.0) * max) as u32;
+        let [r, g, b, _] = self.lut.get(blue * size + red, green).normalize();
+        Texel::from_normalized_dithered(self.format, [r, g, b, a], x, y)
+    }
+}
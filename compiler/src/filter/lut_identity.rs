@@ -0,0 +1,96 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The lut_identity generator filter: renders the identity color lookup
+//! table in the 2D strip layout consumed by the lut filter.
+//!
+//! The strip holds N blocks of NxN texels, with red over each block's
+//! columns, green over its rows and blue selecting the block. The target
+//! must be N² wide and N tall (e.g. 256x16); grading the strip in an
+//! external tool and feeding it back through the lut filter then applies
+//! the grade to any texture.
+//!
+//! This filter takes no parameters.
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::Texel;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "lut_identity",
+    description: "Renders the identity color lookup table in the 2D strip layout.",
+    params: &[],
+};
+
+/// The lut_identity generator filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        _params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        if frame.width != frame.height * frame.height {
+            return Err(FilterError::InvalidTargetSize(frame.width, frame.height));
+        }
+        Ok(Func {
+            size: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The lut_identity generator filter function.
+pub struct Func {
+    size: u32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let max = (self.size - 1) as f32;
+        let red = (x % self.size) as f32 / max;
+        let green = y as f32 / max;
+        let blue = (x / self.size) as f32 / max;
+        // The identity table must round trip exactly, so no dithering.
+        Texel::from_normalized(self.format, [red, green, blue, 1.0])
+    }
+}
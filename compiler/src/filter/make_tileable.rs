@@ -0,0 +1,195 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The make_tileable filter: blends the edges of the previous pass so the
+//! result tiles without a visible seam.
+//!
+//! "offset" fades the edges into a half-texture-offset copy, whose own wrap
+//! seam sits at the center where the fade weight is zero. "mirror" cross
+//! fades each edge band with the mirrored opposite edge, meeting halfway at
+//! the very edge so both sides of the seam agree exactly.
+//!
+//! # Parameters
+//!
+//! * `mode`: the blend strategy, "offset" or "mirror" (default "offset").
+//! * `width`: the width of the blended edge band in texels (default an
+//!   eighth of the smaller target dimension).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "make_tileable",
+    description: "Blends the edges of the previous pass so the result tiles without a seam.",
+    params: &[
+        ParameterSchema {
+            name: "mode",
+            ty: ParameterType::String,
+            default: Some("offset"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "width",
+            ty: ParameterType::Int,
+            default: None,
+            required: false,
+        },
+    ],
+};
+
+/// The edge blend strategies.
+enum Mode {
+    Offset,
+    Mirror,
+}
+
+/// The make_tileable filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let mode = match params.get("mode") {
+            Some(v) => match v.as_string().ok_or(FilterError::InvalidParameter("mode"))? {
+                "offset" => Mode::Offset,
+                "mirror" => Mode::Mirror,
+                _ => return Err(FilterError::InvalidParameter("mode")),
+            },
+            None => Mode::Offset,
+        };
+        let width = match params.get("width") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("width"))?,
+            None => (frame.width.min(frame.height) / 8).max(1) as i64,
+        };
+        if width < 1
+            || width as u64 * 2 > frame.width as u64
+            || width as u64 * 2 > frame.height as u64
+        {
+            return Err(FilterError::InvalidParameter("width"));
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            mode,
+            band: width as f32,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The make_tileable filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    mode: Mode,
+    band: f32,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+impl Func {
+    /// The fade weight for a coordinate, 1.0 at the edge falling to 0.0 at
+    /// the inner end of the blend band.
+    fn proximity(&self, value: u32, len: u32) -> f32 {
+        let distance = value.min(len - 1 - value) as f32;
+        (1.0 - distance / self.band).max(0.0)
+    }
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let rgba = match self.mode {
+            Mode::Offset => {
+                let fade = self
+                    .proximity(x, self.width)
+                    .max(self.proximity(y, self.height));
+                if fade == 0.0 {
+                    return self.previous.get(x, y);
+                }
+                let src = self.previous.get(x, y).normalize();
+                let shifted = self
+                    .previous
+                    .get((x + self.width / 2) % self.width, (y + self.height / 2) % self.height)
+                    .normalize();
+                let mut rgba = [0.0f32; 4];
+                for ((out, a), b) in rgba.iter_mut().zip(src).zip(shifted) {
+                    *out = a + (b - a) * fade;
+                }
+                rgba
+            }
+            Mode::Mirror => {
+                // Per axis taps: the texel itself and the mirrored opposite
+                // edge, weighted to meet at one half on the very edge.
+                let tx = self.proximity(x, self.width) * 0.5;
+                let ty = self.proximity(y, self.height) * 0.5;
+                if tx == 0.0 && ty == 0.0 {
+                    return self.previous.get(x, y);
+                }
+                let mx = self.width - 1 - x;
+                let my = self.height - 1 - y;
+                let mut rgba = [0.0f32; 4];
+                for (sx, sy, weight) in [
+                    (x, y, (1.0 - tx) * (1.0 - ty)),
+                    (mx, y, tx * (1.0 - ty)),
+                    (x, my, (1.0 - tx) * ty),
+                    (mx, my, tx * ty),
+                ] {
+                    let sample = self.previous.get(sx, sy).normalize();
+                    for (out, channel) in rgba.iter_mut().zip(sample) {
+                        *out += channel * weight;
+                    }
+                }
+                rgba
+            }
+        };
+        Texel::from_normalized_dithered(self.format, rgba, x, y)
+    }
+}
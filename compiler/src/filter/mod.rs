@@ -0,0 +1,527 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The filter system and all built-in filters.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::sync::RwLock;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// A region of the render target.
+#[derive(Copy, Clone)]
+pub struct Viewport {
+    /// Left edge in texels of the region.
+    pub x: u32,
+
+    /// Top edge in texels of the region.
+    pub y: u32,
+
+    /// Width in texels of the region.
+    pub width: u32,
+
+    /// Height in texels of the region.
+    pub height: u32,
+}
+
+/// The frame buffer a filter function renders against.
+pub struct FrameBuffer {
+    /// Width in texels of the render target.
+    pub width: u32,
+
+    /// Height in texels of the render target.
+    pub height: u32,
+
+    /// Format of the render target.
+    pub format: Format,
+
+    /// Region the pass function is applied to, expanded by the tiling
+    /// apron; the whole target when tiling is off. Filters precomputing
+    /// intermediates should allocate them over this region only.
+    pub viewport: Viewport,
+
+    /// Result of the previous pass.
+    pub previous: Arc<OutputTexture>,
+
+    /// Results of the most recent passes retained by the swap chain, most
+    /// recent first: entry 0 is the previous pass, entry 1 the pass before
+    /// it, and so on up to the chain depth.
+    pub history: Vec<Arc<OutputTexture>>,
+
+    /// Outputs of earlier passes published under a name.
+    pub buffers: HashMap<String, Arc<OutputTexture>>,
+
+    /// If set, stochastic filters must use fixed seeds so two runs with the
+    /// same inputs produce bit-identical output.
+    pub deterministic: bool,
+
+    /// The seed stochastic filters must derive their randomness from, None
+    /// to let each pass draw a fresh one from the operating system.
+    ///
+    /// A set seed implies reproducible passes even when
+    /// [deterministic](FrameBuffer::deterministic) is off.
+    pub seed: Option<u64>,
+}
+
+impl FrameBuffer {
+    /// Resolves an optional texture input of a filter.
+    ///
+    /// The parameter can hold a loaded image or the name of a buffer
+    /// published by an earlier pass; a string naming no published buffer
+    /// fails. Returns None when the parameter is not set.
+    pub fn input(
+        &self,
+        params: &ParameterMap,
+        name: &'static str,
+    ) -> Result<Option<Arc<dyn Texture>>, FilterError> {
+        let param = match params.get(name) {
+            Some(param) => param,
+            None => return Ok(None),
+        };
+        if let Some(texture) = param.as_texture() {
+            return Ok(Some(texture.clone()));
+        }
+        let buffer = param
+            .as_string()
+            .and_then(|v| self.buffers.get(v))
+            .ok_or(FilterError::InvalidParameter(name))?;
+        Ok(Some(buffer.clone()))
+    }
+
+    /// Returns the result of the pass `index` steps back, 0 being the
+    /// previous pass, None past the history the swap chain retains.
+    pub fn earlier(&self, index: usize) -> Option<&Arc<OutputTexture>> {
+        self.history.get(index)
+    }
+
+    /// Resolves a required texture input of a filter.
+    ///
+    /// Same as [input](FrameBuffer::input) but fails when the parameter is
+    /// not set.
+    pub fn require_input(
+        &self,
+        params: &ParameterMap,
+        name: &'static str,
+    ) -> Result<Arc<dyn Texture>, FilterError> {
+        self.input(params, name)?
+            .ok_or(FilterError::MissingParameter(name))
+    }
+}
+
+/// Errors raised when constructing a filter function.
+#[derive(Debug)]
+pub enum FilterError {
+    /// A required parameter is missing (parameter name).
+    MissingParameter(&'static str),
+
+    /// A parameter has an invalid type or value (parameter name).
+    InvalidParameter(&'static str),
+
+    /// The filter does not support the render target format.
+    UnsupportedFormat(Format),
+
+    /// The filter requires a specific render target layout (width, height).
+    InvalidTargetSize(u32, u32),
+
+    /// A filter script failed to load or run (script error message).
+    Script(String),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterError::MissingParameter(name) => write!(f, "missing parameter '{}'", name),
+            FilterError::InvalidParameter(name) => write!(f, "invalid parameter '{}'", name),
+            FilterError::UnsupportedFormat(format) => {
+                write!(f, "unsupported render target format '{}'", format)
+            }
+            FilterError::InvalidTargetSize(width, height) => {
+                write!(f, "unsupported render target size {}x{}", width, height)
+            }
+            FilterError::Script(message) => write!(f, "script error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+/// The type of a filter parameter.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParameterType {
+    /// A floating point parameter.
+    Float,
+
+    /// An integer parameter.
+    Int,
+
+    /// A boolean parameter.
+    Bool,
+
+    /// A 2 components vector parameter.
+    Vector2,
+
+    /// A 3 components vector parameter.
+    Vector3,
+
+    /// A 4 components vector parameter.
+    Vector4,
+
+    /// A normalized RGBA color parameter.
+    Color,
+
+    /// A string parameter.
+    String,
+
+    /// A texture parameter.
+    Texture,
+}
+
+impl fmt::Display for ParameterType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ParameterType::Float => "float",
+            ParameterType::Int => "int",
+            ParameterType::Bool => "bool",
+            ParameterType::Vector2 => "vector2",
+            ParameterType::Vector3 => "vector3",
+            ParameterType::Vector4 => "vector4",
+            ParameterType::Color => "color",
+            ParameterType::String => "string",
+            ParameterType::Texture => "texture",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The schema of a single filter parameter.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ParameterSchema {
+    /// Name of the parameter.
+    pub name: &'static str,
+
+    /// Type of the parameter.
+    #[serde(rename = "type")]
+    pub ty: ParameterType,
+
+    /// Human readable default value; None when the parameter is required or
+    /// its default is computed at run time.
+    pub default: Option<&'static str>,
+
+    /// Whether the filter fails to build without this parameter.
+    pub required: bool,
+}
+
+/// Describes a registered filter to front-ends.
+///
+/// The CLI help and GUI front-ends list the available filters from this
+/// instead of hard-coding them; [filters](crate::filter::filters) returns
+/// one entry per registered filter.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct FilterInfo {
+    /// Name of the filter, as passed to
+    /// [from_name](crate::filter::DynamicFilter::from_name).
+    pub name: &'static str,
+
+    /// One line description of what the filter does.
+    pub description: &'static str,
+
+    /// Schema of the parameters the filter understands.
+    pub params: &'static [ParameterSchema],
+}
+
+/// Constructor trait of all filters.
+pub trait New {
+    /// Creates a new instance of this filter.
+    fn new() -> Self;
+}
+
+/// A per texel function produced by a filter for a single pass.
+pub trait Function: Send + Sync {
+    /// Computes the texel at the given coordinates.
+    fn apply(&self, x: u32, y: u32) -> Texel;
+
+    /// Computes a contiguous horizontal span of texels into `out`, which
+    /// holds one slot per coordinate of `xs`.
+    ///
+    /// The default forwards to [apply](Function::apply) texel by texel;
+    /// filters can override it to amortize per texel setup or use SIMD.
+    fn apply_span(&self, y: u32, xs: Range<u32>, out: &mut [Texel]) {
+        for (texel, x) in out.iter_mut().zip(xs) {
+            *texel = self.apply(x, y);
+        }
+    }
+}
+
+/// Base trait of all filters.
+pub trait Filter {
+    /// The type of function this filter produces.
+    type Function: Function;
+
+    /// Creates the function used to render a pass of this filter.
+    ///
+    /// This resolves parameters against the given frame buffer and fails if
+    /// they are missing, of the wrong type or unsupported by the target format.
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Self::Function, FilterError>;
+}
+
+pub mod alpha_bleed;
+pub mod blend;
+pub mod bloom;
+pub mod border;
+pub mod brightness;
+pub mod channel_pack;
+pub mod composite;
+pub mod contrast;
+pub mod curves;
+pub mod equirect_to_cube;
+pub mod fill;
+pub mod gaussian;
+pub mod greyscale;
+pub mod height_to_normal;
+pub mod hsl;
+pub mod lut;
+pub mod lut_identity;
+pub mod make_tileable;
+pub mod morphology;
+pub mod noise;
+pub mod normal_fix;
+pub mod pattern;
+pub mod premultiply;
+pub mod quantize;
+pub mod resample;
+#[cfg(feature = "lua")]
+pub mod script;
+pub mod toksvig;
+pub mod tonemap;
+pub mod transform;
+pub mod unpremultiply;
+
+/// Object safe form of [Filter], as stored in the registry.
+trait AnyFilter: Send + Sync {
+    /// Creates the function used to render a pass of this filter.
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Box<dyn Function>, FilterError>;
+}
+
+/// Adapts a statically typed filter to [AnyFilter] by boxing its functions.
+struct Erased<F>(F);
+
+impl<F: Filter + Send + Sync> AnyFilter for Erased<F>
+where
+    F::Function: 'static,
+{
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Box<dyn Function>, FilterError> {
+        Ok(Box::new(self.0.new_function(frame, params)?))
+    }
+}
+
+fn construct<F>() -> Arc<dyn AnyFilter>
+where
+    F: Filter + New + Send + Sync + 'static,
+    F::Function: 'static,
+{
+    Arc::new(Erased(F::new()))
+}
+
+/// A registered filter: its description and a shared instance.
+struct Registration {
+    info: &'static FilterInfo,
+    filter: Arc<dyn AnyFilter>,
+}
+
+fn builtin_registry() -> HashMap<&'static str, Registration> {
+    let mut map = HashMap::new();
+    let mut add = |info: &'static FilterInfo, construct: fn() -> Arc<dyn AnyFilter>| {
+        map.insert(
+            info.name,
+            Registration {
+                info,
+                filter: construct(),
+            },
+        );
+    };
+    add(&alpha_bleed::INFO, construct::<alpha_bleed::Filter>);
+    add(&blend::INFO, construct::<blend::Filter>);
+    add(&bloom::INFO, construct::<bloom::Filter>);
+    add(&border::INFO, construct::<border::Filter>);
+    add(&brightness::INFO, construct::<brightness::Filter>);
+    add(&channel_pack::INFO, construct::<channel_pack::Filter>);
+    add(&composite::INFO, construct::<composite::Filter>);
+    add(&contrast::INFO, construct::<contrast::Filter>);
+    add(&curves::INFO, construct::<curves::Filter>);
+    add(&equirect_to_cube::INFO, construct::<equirect_to_cube::Filter>);
+    add(&fill::INFO, construct::<fill::Filter>);
+    add(&gaussian::INFO, construct::<gaussian::Filter>);
+    add(&greyscale::INFO, construct::<greyscale::Filter>);
+    add(&height_to_normal::INFO, construct::<height_to_normal::Filter>);
+    add(&hsl::INFO, construct::<hsl::Filter>);
+    add(&lut::INFO, construct::<lut::Filter>);
+    add(&lut_identity::INFO, construct::<lut_identity::Filter>);
+    add(&make_tileable::INFO, construct::<make_tileable::Filter>);
+    add(&morphology::INFO, construct::<morphology::Filter>);
+    add(&noise::INFO, construct::<noise::Filter>);
+    add(&normal_fix::INFO, construct::<normal_fix::Filter>);
+    add(&pattern::INFO, construct::<pattern::Filter>);
+    add(&premultiply::INFO, construct::<premultiply::Filter>);
+    add(&quantize::INFO, construct::<quantize::Filter>);
+    add(&resample::INFO, construct::<resample::Filter>);
+    #[cfg(feature = "lua")]
+    add(&script::INFO, construct::<script::Filter>);
+    add(&toksvig::INFO, construct::<toksvig::Filter>);
+    add(&tonemap::INFO, construct::<tonemap::Filter>);
+    add(&transform::INFO, construct::<transform::Filter>);
+    add(&unpremultiply::INFO, construct::<unpremultiply::Filter>);
+    map
+}
+
+fn registry() -> &'static RwLock<HashMap<&'static str, Registration>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<&'static str, Registration>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(builtin_registry()))
+}
+
+/// Registers a filter under the name of its info, replacing any previously
+/// registered filter with the same name.
+///
+/// The filters of this crate are pre-registered; downstream crates call this
+/// to make their own filters available to
+/// [from_name](DynamicFilter::from_name) and pipelines referring to filters
+/// by name.
+pub fn register<F>(info: &'static FilterInfo)
+where
+    F: Filter + New + Send + Sync + 'static,
+    F::Function: 'static,
+{
+    registry().write().unwrap().insert(
+        info.name,
+        Registration {
+            info,
+            filter: construct::<F>(),
+        },
+    );
+}
+
+/// Registers a filter instance under the name of its info, replacing any
+/// previously registered filter with the same name.
+///
+/// Unlike [register] this takes the filter by value instead of requiring
+/// [New], so filters carrying state captured at registration time (host
+/// callbacks, precomputed tables) can be injected without a default
+/// constructor. The instance is shared by every pipeline referring to it.
+pub fn register_instance<F>(info: &'static FilterInfo, filter: F)
+where
+    F: Filter + Send + Sync + 'static,
+    F::Function: 'static,
+{
+    registry().write().unwrap().insert(
+        info.name,
+        Registration {
+            info,
+            filter: Arc::new(Erased(filter)),
+        },
+    );
+}
+
+/// Returns the description of every registered filter, sorted by name.
+pub fn filters() -> Vec<&'static FilterInfo> {
+    let registry = registry().read().unwrap();
+    let mut infos: Vec<&'static FilterInfo> = registry.values().map(|v| v.info).collect();
+    infos.sort_by_key(|v| v.name);
+    infos
+}
+
+/// A filter resolved from the registry by name.
+pub struct DynamicFilter {
+    name: &'static str,
+    filter: Arc<dyn AnyFilter>,
+}
+
+impl DynamicFilter {
+    /// Constructs the filter matching the given name, None if no such filter
+    /// is registered.
+    pub fn from_name(name: &str) -> Option<DynamicFilter> {
+        let registry = registry().read().unwrap();
+        let registration = registry.get(name)?;
+        Some(DynamicFilter {
+            name: registration.info.name,
+            filter: registration.filter.clone(),
+        })
+    }
+
+    /// Returns the name of this filter.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl Filter for DynamicFilter {
+    type Function = DynamicFunction;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<DynamicFunction, FilterError> {
+        Ok(DynamicFunction(self.filter.new_function(frame, params)?))
+    }
+}
+
+/// The function of a [DynamicFilter] pass.
+pub struct DynamicFunction(Box<dyn Function>);
+
+impl Function for DynamicFunction {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        self.0.apply(x, y)
+    }
+
+    fn apply_span(&self, y: u32, xs: Range<u32>, out: &mut [Texel]) {
+        self.0.apply_span(y, xs, out)
+    }
+}
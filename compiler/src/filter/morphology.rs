@@ -0,0 +1,215 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The morphology filter: greyscale dilation and erosion of the previous
+//! pass, typically used to grow or shrink masks before channel packing.
+//!
+//! Each channel takes the maximum (dilate) or minimum (erode) over a square
+//! neighborhood. "open" and "close" chain the two, which removes speckles or
+//! fills pinholes smaller than the radius; they scan a doubled neighborhood
+//! per texel, so keep the radius small.
+//!
+//! # Parameters
+//!
+//! * `op`: the operation, one of "dilate", "erode", "open" or "close".
+//! * `radius`: the neighborhood radius in texels, at most 16 (default 1).
+//! * `wrap`: how the neighborhood resolves past the borders, one of
+//!   "clamp", "repeat" or "mirror" (default "clamp"). Tileable masks
+//!   should use "repeat" so shapes grow across the seam.
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+use crate::texture::WrapMode;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "morphology",
+    description: "Greyscale dilation and erosion of the previous pass.",
+    params: &[
+        ParameterSchema {
+            name: "op",
+            ty: ParameterType::String,
+            default: None,
+            required: true,
+        },
+        ParameterSchema {
+            name: "radius",
+            ty: ParameterType::Int,
+            default: Some("1"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "wrap",
+            ty: ParameterType::String,
+            default: Some("clamp"),
+            required: false,
+        },
+    ],
+};
+
+/// The morphological operations.
+#[derive(Clone, Copy)]
+enum Op {
+    Dilate,
+    Erode,
+    Open,
+    Close,
+}
+
+impl Op {
+    fn from_name(name: &str) -> Option<Op> {
+        match name {
+            "dilate" => Some(Op::Dilate),
+            "erode" => Some(Op::Erode),
+            "open" => Some(Op::Open),
+            "close" => Some(Op::Close),
+            _ => None,
+        }
+    }
+}
+
+/// The morphology filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let op = params
+            .get("op")
+            .ok_or(FilterError::MissingParameter("op"))?
+            .as_string()
+            .and_then(Op::from_name)
+            .ok_or(FilterError::InvalidParameter("op"))?;
+        let radius = match params.get("radius") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("radius"))?,
+            None => 1,
+        };
+        if !(1..=16).contains(&radius) {
+            return Err(FilterError::InvalidParameter("radius"));
+        }
+        let wrap = match params.get("wrap") {
+            Some(v) => v
+                .as_string()
+                .and_then(WrapMode::from_name)
+                .ok_or(FilterError::InvalidParameter("wrap"))?,
+            None => WrapMode::Clamp,
+        };
+        Ok(Func {
+            previous: frame.previous.clone(),
+            op,
+            radius,
+            wrap,
+            format: frame.format,
+        })
+    }
+}
+
+/// The morphology filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    op: Op,
+    radius: i64,
+    wrap: WrapMode,
+    format: Format,
+}
+
+impl Func {
+    /// The dilation (maximum) or erosion (minimum) of the previous pass at a
+    /// texel, over a square neighborhood wrapped at the edges.
+    fn extremum(&self, x: i64, y: i64, dilate: bool) -> [f32; 4] {
+        let mut result = if dilate { [0.0f32; 4] } else { [1.0f32; 4] };
+        for dy in -self.radius..=self.radius {
+            for dx in -self.radius..=self.radius {
+                let sx = self.wrap.resolve(x + dx, self.previous.width());
+                let sy = self.wrap.resolve(y + dy, self.previous.height());
+                let rgba = self.previous.get(sx, sy).normalize();
+                for (out, channel) in result.iter_mut().zip(rgba) {
+                    *out = if dilate {
+                        out.max(channel)
+                    } else {
+                        out.min(channel)
+                    };
+                }
+            }
+        }
+        result
+    }
+
+    /// Chains two operations, the first evaluated over the second's
+    /// neighborhood.
+    fn chained(&self, x: i64, y: i64, first_dilate: bool) -> [f32; 4] {
+        let mut result = if first_dilate { [1.0f32; 4] } else { [0.0f32; 4] };
+        for dy in -self.radius..=self.radius {
+            for dx in -self.radius..=self.radius {
+                let inner = self.extremum(x + dx, y + dy, first_dilate);
+                for (out, channel) in result.iter_mut().zip(inner) {
+                    *out = if first_dilate {
+                        out.min(channel)
+                    } else {
+                        out.max(channel)
+                    };
+                }
+            }
+        }
+        result
+    }
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let rgba = match self.op {
+            Op::Dilate => self.extremum(x as i64, y as i64, true),
+            Op::Erode => self.extremum(x as i64, y as i64, false),
+            // Open erodes then dilates, close dilates then erodes.
+            Op::Open => self.chained(x as i64, y as i64, false),
+            Op::Close => self.chained(x as i64, y as i64, true),
+        };
+        Texel::from_normalized_dithered(self.format, rgba, x, y)
+    }
+}
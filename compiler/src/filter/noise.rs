@@ -0,0 +1,323 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The noise generator filter.
+//!
+//! # Parameters
+//!
+//! * `mode`: the noise mode, one of "perlin", "worley" or "random"
+//!   (default "perlin").
+//! * `scale`: the coordinate scale of the noise (default 1.0).
+//! * `octaves`: the number of fractal octaves summed in perlin mode
+//!   (default 1).
+//! * `frequency`: the frequency of the first octave (default 1.0).
+//! * `lacunarity`: the frequency multiplier between octaves (default 2.0).
+//! * `gain`: the amplitude multiplier between octaves (default 0.5).
+//! * `cells`: the number of worley cells across the texture (default 8).
+//! * `distance`: the worley feature distance, one of "f1", "f2" or "f2f1"
+//!   (default "f1").
+
+use noise::NoiseFn;
+use noise::Perlin;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::Texel;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "noise",
+    description: "Generates perlin, worley or random noise.",
+    params: &[
+        ParameterSchema {
+            name: "mode",
+            ty: ParameterType::String,
+            default: Some("perlin"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "scale",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "octaves",
+            ty: ParameterType::Int,
+            default: Some("1"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "frequency",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "lacunarity",
+            ty: ParameterType::Float,
+            default: Some("2.0"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "gain",
+            ty: ParameterType::Float,
+            default: Some("0.5"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "cells",
+            ty: ParameterType::Int,
+            default: Some("8"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "distance",
+            ty: ParameterType::String,
+            default: Some("f1"),
+            required: false,
+        },
+    ],
+};
+
+enum Mode {
+    Perlin(Box<Perlin>),
+    Worley { cells: u32, distance: Distance },
+    Random,
+}
+
+/// The feature distances worley noise can output.
+enum Distance {
+    /// Distance to the nearest feature point.
+    F1,
+
+    /// Distance to the second nearest feature point.
+    F2,
+
+    /// Difference of the two, highlighting cell borders.
+    F2MinusF1,
+}
+
+/// The noise generator filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let seed = match frame.seed {
+            Some(seed) => seed as u32,
+            None if frame.deterministic => 0,
+            None => OsRng.next_u32(),
+        };
+        // The noise source is built once per pass; constructing it per texel
+        // rebuilds the permutation table for every sample.
+        let mode = match params.get("mode") {
+            Some(v) => match v.as_string().ok_or(FilterError::InvalidParameter("mode"))? {
+                "perlin" => Mode::Perlin(Box::new(Perlin::new(seed))),
+                "worley" => {
+                    let cells = match params.get("cells") {
+                        Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("cells"))?,
+                        None => 8,
+                    };
+                    if cells < 1 {
+                        return Err(FilterError::InvalidParameter("cells"));
+                    }
+                    let distance = match params.get("distance") {
+                        Some(v) => match v.as_string() {
+                            Some("f1") => Distance::F1,
+                            Some("f2") => Distance::F2,
+                            Some("f2f1") => Distance::F2MinusF1,
+                            _ => return Err(FilterError::InvalidParameter("distance")),
+                        },
+                        None => Distance::F1,
+                    };
+                    Mode::Worley {
+                        cells: cells as u32,
+                        distance,
+                    }
+                }
+                "random" => Mode::Random,
+                _ => return Err(FilterError::InvalidParameter("mode")),
+            },
+            None => Mode::Perlin(Box::new(Perlin::new(seed))),
+        };
+        let scale = match params.get("scale") {
+            Some(v) => v.as_float().ok_or(FilterError::InvalidParameter("scale"))?,
+            None => 1.0,
+        };
+        let octaves = match params.get("octaves") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("octaves"))?,
+            None => 1,
+        };
+        if octaves < 1 {
+            return Err(FilterError::InvalidParameter("octaves"));
+        }
+        let frequency = match params.get("frequency") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("frequency"))?,
+            None => 1.0,
+        };
+        let lacunarity = match params.get("lacunarity") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("lacunarity"))?,
+            None => 2.0,
+        };
+        let gain = match params.get("gain") {
+            Some(v) => v.as_float().ok_or(FilterError::InvalidParameter("gain"))?,
+            None => 0.5,
+        };
+        Ok(Func {
+            mode,
+            scale,
+            octaves: octaves as u32,
+            frequency,
+            lacunarity,
+            gain,
+            seed,
+            seeded: frame.deterministic || frame.seed.is_some(),
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The noise generator filter function.
+pub struct Func {
+    mode: Mode,
+    scale: f64,
+    octaves: u32,
+    frequency: f64,
+    lacunarity: f64,
+    gain: f64,
+    seed: u32,
+    seeded: bool,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+/// Mixes the seed and texel coordinates into a deterministic pseudo random value.
+fn hash_texel(seed: u32, x: u32, y: u32) -> u32 {
+    let mut hash = seed ^ x.wrapping_mul(0x9e3779b9) ^ y.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7feb352d);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x846ca68b);
+    hash ^= hash >> 16;
+    hash
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let value = match &self.mode {
+            Mode::Perlin(perlin) => {
+                let u = x as f64 / self.width as f64 * self.scale;
+                let v = y as f64 / self.height as f64 * self.scale;
+                // Fractal Brownian motion: octaves of rising frequency and
+                // falling amplitude, normalized back to [-1, 1].
+                let mut frequency = self.frequency;
+                let mut amplitude = 1.0;
+                let mut sum = 0.0;
+                let mut range = 0.0;
+                for _ in 0..self.octaves {
+                    sum += perlin.get([u * frequency, v * frequency]) * amplitude;
+                    range += amplitude;
+                    frequency *= self.lacunarity;
+                    amplitude *= self.gain;
+                }
+                (sum / range + 1.0) / 2.0
+            }
+            Mode::Worley { cells, distance } => {
+                let cells = *cells;
+                let u = x as f64 / self.width as f64 * cells as f64;
+                let v = y as f64 / self.height as f64 * cells as f64;
+                let cell_x = u.floor() as i64;
+                let cell_y = v.floor() as i64;
+                let mut f1 = f64::MAX;
+                let mut f2 = f64::MAX;
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let cx = cell_x + dx;
+                        let cy = cell_y + dy;
+                        // Feature points are hashed from the wrapped cell so
+                        // the pattern tiles.
+                        let hash = hash_texel(
+                            self.seed,
+                            cx.rem_euclid(cells as i64) as u32,
+                            cy.rem_euclid(cells as i64) as u32,
+                        );
+                        let px = cx as f64 + (hash & 0xFFFF) as f64 / 65535.0;
+                        let py = cy as f64 + (hash >> 16) as f64 / 65535.0;
+                        let d = ((u - px) * (u - px) + (v - py) * (v - py)).sqrt();
+                        if d < f1 {
+                            f2 = f1;
+                            f1 = d;
+                        } else if d < f2 {
+                            f2 = d;
+                        }
+                    }
+                }
+                match distance {
+                    Distance::F1 => f1,
+                    Distance::F2 => f2,
+                    Distance::F2MinusF1 => f2 - f1,
+                }
+                .min(1.0)
+            }
+            Mode::Random => {
+                if self.seeded {
+                    hash_texel(self.seed, x, y) as f64 / u32::MAX as f64
+                } else {
+                    OsRng.next_u32() as f64 / u32::MAX as f64
+                }
+            }
+        } as f32;
+        Texel::from_normalized_dithered(self.format, [value, value, value, 1.0], x, y)
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The normal_fix filter: cleans up a normal map in the previous pass.
+//!
+//! The filter decodes each texel into a vector, optionally flips the green
+//! channel (converting between the DirectX and OpenGL conventions),
+//! renormalizes and re-encodes. Resampling and blurring denormalize
+//! vectors, so a renormalize pass usually closes a normal map pipeline.
+//!
+//! # Parameters
+//!
+//! * `flip_green`: flips the Y component (default false).
+//! * `renormalize`: rescales vectors to unit length (default true).
+//! * `signed_input`: the input stores components in [-1, 1] instead of the
+//!   usual [0, 1] encoding (default false).
+//! * `signed_output`: write components in [-1, 1]; only meaningful for
+//!   float formats (default false).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "normal_fix",
+    description: "Cleans up a normal map in the previous pass.",
+    params: &[
+        ParameterSchema {
+            name: "flip_green",
+            ty: ParameterType::Bool,
+            default: Some("false"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "renormalize",
+            ty: ParameterType::Bool,
+            default: Some("true"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "signed_input",
+            ty: ParameterType::Bool,
+            default: Some("false"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "signed_output",
+            ty: ParameterType::Bool,
+            default: Some("false"),
+            required: false,
+        },
+    ],
+};
+
+/// Reads an optional boolean parameter.
+fn parse_bool(
+    params: &ParameterMap,
+    name: &'static str,
+    default: bool,
+) -> Result<bool, FilterError> {
+    match params.get(name) {
+        Some(v) => v.as_bool().ok_or(FilterError::InvalidParameter(name)),
+        None => Ok(default),
+    }
+}
+
+/// The normal_fix filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        Ok(Func {
+            previous: frame.previous.clone(),
+            flip_green: parse_bool(params, "flip_green", false)?,
+            renormalize: parse_bool(params, "renormalize", true)?,
+            signed_input: parse_bool(params, "signed_input", false)?,
+            signed_output: parse_bool(params, "signed_output", false)?,
+            format: frame.format,
+        })
+    }
+}
+
+/// The normal_fix filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    flip_green: bool,
+    renormalize: bool,
+    signed_input: bool,
+    signed_output: bool,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let [r, g, b, a] = self.previous.get(x, y).normalize();
+        let mut normal = if self.signed_input {
+            [r, g, b]
+        } else {
+            [r * 2.0 - 1.0, g * 2.0 - 1.0, b * 2.0 - 1.0]
+        };
+        if self.flip_green {
+            normal[1] = -normal[1];
+        }
+        if self.renormalize {
+            let length =
+                (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+            if length > 0.0 {
+                for component in &mut normal {
+                    *component /= length;
+                }
+            } else {
+                // Degenerate texels become the straight up normal.
+                normal = [0.0, 0.0, 1.0];
+            }
+        }
+        let rgba = if self.signed_output {
+            [normal[0], normal[1], normal[2], a]
+        } else {
+            [
+                normal[0] * 0.5 + 0.5,
+                normal[1] * 0.5 + 0.5,
+                normal[2] * 0.5 + 0.5,
+                a,
+            ]
+        };
+        Texel::from_normalized_dithered(self.format, rgba, x, y)
+    }
+}
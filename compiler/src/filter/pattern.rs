@@ -0,0 +1,185 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+
+//! The pattern filter: generates checkerboard, grid and stripe patterns for
+//! UV debug textures and placeholders.
+//!
+//! # Parameters
+//!
+//! * `mode`: the pattern shape, one of "checker", "grid" or "stripes"
+//!   (default "checker").
+//! * `size`: the cell size in texels (default 8).
+//! * `thickness`: the line thickness in texels of the grid mode (default 1).
+//! * `color1`: the background color (default opaque black).
+//! * `color2`: the foreground color (default opaque white).
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::Texel;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "pattern",
+    description: "Generates checkerboard, grid and stripe patterns.",
+    params: &[
+        ParameterSchema {
+            name: "mode",
+            ty: ParameterType::String,
+            default: Some("checker"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "size",
+            ty: ParameterType::Int,
+            default: Some("8"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "thickness",
+            ty: ParameterType::Int,
+            default: Some("1"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "color1",
+            ty: ParameterType::Vector4,
+            default: Some("0,0,0,1"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "color2",
+            ty: ParameterType::Vector4,
+            default: Some("1,1,1,1"),
+            required: false,
+        },
+    ],
+};
+
+/// The shapes the pattern filter can generate.
+enum Mode {
+    Checker,
+    Grid,
+    Stripes,
+}
+
+/// Reads an optional color parameter.
+fn parse_color(
+    params: &ParameterMap,
+    name: &'static str,
+    default: [f32; 4],
+) -> Result<[f32; 4], FilterError> {
+    match params.get(name) {
+        Some(v) => {
+            let color = v.as_vector4().ok_or(FilterError::InvalidParameter(name))?;
+            Ok([color[0] as f32, color[1] as f32, color[2] as f32, color[3] as f32])
+        }
+        None => Ok(default),
+    }
+}
+
+/// The pattern filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let mode = match params.get("mode") {
+            Some(v) => match v.as_string() {
+                Some("checker") => Mode::Checker,
+                Some("grid") => Mode::Grid,
+                Some("stripes") => Mode::Stripes,
+                _ => return Err(FilterError::InvalidParameter("mode")),
+            },
+            None => Mode::Checker,
+        };
+        let size = match params.get("size") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("size"))?,
+            None => 8,
+        };
+        if size < 1 {
+            return Err(FilterError::InvalidParameter("size"));
+        }
+        let thickness = match params.get("thickness") {
+            Some(v) => v
+                .as_int()
+                .ok_or(FilterError::InvalidParameter("thickness"))?,
+            None => 1,
+        };
+        if thickness < 1 || thickness > size {
+            return Err(FilterError::InvalidParameter("thickness"));
+        }
+        Ok(Func {
+            mode,
+            size: size as u32,
+            thickness: thickness as u32,
+            color1: parse_color(params, "color1", [0.0, 0.0, 0.0, 1.0])?,
+            color2: parse_color(params, "color2", [1.0, 1.0, 1.0, 1.0])?,
+            format: frame.format,
+        })
+    }
+}
+
+/// The pattern filter function.
+pub struct Func {
+    mode: Mode,
+    size: u32,
+    thickness: u32,
+    color1: [f32; 4],
+    color2: [f32; 4],
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let foreground = match self.mode {
+            Mode::Checker => (x / self.size + y / self.size) % 2 == 1,
+            Mode::Grid => x % self.size < self.thickness || y % self.size < self.thickness,
+            Mode::Stripes => x % (self.size * 2) >= self.size,
+        };
+        let color = if foreground { self.color2 } else { self.color1 };
+        Texel::from_normalized_dithered(self.format, color, x, y)
+    }
+}
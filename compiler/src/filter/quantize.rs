@@ -0,0 +1,202 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The quantize filter: reduces the previous pass to a palette of N colors
+//! extracted by median cut.
+//!
+//! Rendering into the indexed [P8](crate::texture::Format::P8) format stores
+//! palette indices; any other format stores the palette colors themselves,
+//! for a posterized look without the indexed storage.
+//!
+//! # Parameters
+//!
+//! * `colors`: the palette size, between 2 and 256 (default 16).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "quantize",
+    description: "Reduces the previous pass to a palette of colors extracted by median cut.",
+    params: &[
+        ParameterSchema {
+            name: "colors",
+            ty: ParameterType::Int,
+            default: Some("16"),
+            required: false,
+        },
+    ],
+};
+
+/// Extracts a palette from the given colors with median cut.
+///
+/// Boxes split on their widest RGB channel at the median until the requested
+/// count is reached; each box then averages into one palette entry.
+fn median_cut(mut colors: Vec<[f32; 4]>, count: usize) -> Vec<[f32; 4]> {
+    colors.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    colors.dedup();
+    let mut boxes = vec![colors];
+    while boxes.len() < count {
+        // Split the box with the widest channel range; stop when every box
+        // is a single color.
+        let mut widest = None;
+        for (index, entries) in boxes.iter().enumerate() {
+            if entries.len() < 2 {
+                continue;
+            }
+            for channel in 0..3 {
+                let mut min = f32::MAX;
+                let mut max = f32::MIN;
+                for entry in entries {
+                    min = min.min(entry[channel]);
+                    max = max.max(entry[channel]);
+                }
+                let range = max - min;
+                if widest.map(|(_, _, r)| range > r).unwrap_or(true) {
+                    widest = Some((index, channel, range));
+                }
+            }
+        }
+        let (index, channel, _) = match widest {
+            Some(v) => v,
+            None => break,
+        };
+        let mut entries = boxes.swap_remove(index);
+        entries.sort_by(|a, b| a[channel].total_cmp(&b[channel]));
+        let upper = entries.split_off(entries.len() / 2);
+        boxes.push(entries);
+        boxes.push(upper);
+    }
+    boxes
+        .iter()
+        .filter(|entries| !entries.is_empty())
+        .map(|entries| {
+            let mut mean = [0.0f32; 4];
+            for entry in entries {
+                for (sum, channel) in mean.iter_mut().zip(entry) {
+                    *sum += channel;
+                }
+            }
+            for sum in &mut mean {
+                *sum /= entries.len() as f32;
+            }
+            mean
+        })
+        .collect()
+}
+
+/// The quantize filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let colors = match params.get("colors") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("colors"))?,
+            None => 16,
+        };
+        if !(2..=256).contains(&colors) {
+            return Err(FilterError::InvalidParameter("colors"));
+        }
+        let previous = &frame.previous;
+        let mut samples =
+            Vec::with_capacity(previous.width() as usize * previous.height() as usize);
+        for y in 0..previous.height() {
+            for x in 0..previous.width() {
+                samples.push(previous.get(x, y).normalize());
+            }
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            palette: median_cut(samples, colors as usize),
+            format: frame.format,
+        })
+    }
+}
+
+/// The quantize filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    palette: Vec<[f32; 4]>,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let rgba = self.previous.get(x, y).normalize();
+        let mut best = 0;
+        let mut best_distance = f32::MAX;
+        for (index, entry) in self.palette.iter().enumerate() {
+            let distance = rgba
+                .iter()
+                .zip(entry)
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum::<f32>();
+            if distance < best_distance {
+                best_distance = distance;
+                best = index;
+            }
+        }
+        let entry = self.palette[best];
+        if self.format == Format::P8 {
+            Texel::P8 {
+                index: best as u8,
+                rgba: [
+                    (entry[0].clamp(0.0, 1.0) * 255.0) as u8,
+                    (entry[1].clamp(0.0, 1.0) * 255.0) as u8,
+                    (entry[2].clamp(0.0, 1.0) * 255.0) as u8,
+                    (entry[3].clamp(0.0, 1.0) * 255.0) as u8,
+                ],
+            }
+        } else {
+            Texel::from_normalized(self.format, entry)
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The resample filter: stretches a source texture to the render target size.
+//!
+//! # Parameters
+//!
+//! * `base`: the source texture to resample.
+//! * `method`: the interpolation method, one of "nearest", "bilinear",
+//!   "bicubic" or "lanczos3" (default "nearest").
+//! * `wrap`: how coordinates past the source borders resolve, one of
+//!   "clamp", "repeat" or "mirror" (default "clamp"). Tileable sources
+//!   should use "repeat" so the kernel samples across the seam.
+//!
+//! Shrinking below the source size stretches the method's kernel over the
+//! minification footprint (see
+//! [sample_scaled](crate::texture::Texture::sample_scaled)), so "bicubic"
+//! and "lanczos3" stay sharp without aliasing on large downscales.
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::SampleMethod;
+use crate::texture::Texel;
+use crate::texture::Texture;
+use crate::texture::WrapMode;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "resample",
+    description: "Stretches a source texture to the render target size.",
+    params: &[
+        ParameterSchema {
+            name: "base",
+            ty: ParameterType::Texture,
+            default: None,
+            required: true,
+        },
+        ParameterSchema {
+            name: "method",
+            ty: ParameterType::String,
+            default: Some("nearest"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "wrap",
+            ty: ParameterType::String,
+            default: Some("clamp"),
+            required: false,
+        },
+    ],
+};
+
+/// The resample filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let base = frame.require_input(params, "base")?;
+        let method = match params.get("method") {
+            Some(v) => v
+                .as_string()
+                .and_then(SampleMethod::from_name)
+                .ok_or(FilterError::InvalidParameter("method"))?,
+            None => SampleMethod::Nearest,
+        };
+        let wrap = match params.get("wrap") {
+            Some(v) => v
+                .as_string()
+                .and_then(WrapMode::from_name)
+                .ok_or(FilterError::InvalidParameter("wrap"))?,
+            None => WrapMode::Clamp,
+        };
+        // Source texels covered per target texel along the larger axis;
+        // above one the target minifies the source.
+        let scale = (base.width() as f64 / frame.width as f64)
+            .max(base.height() as f64 / frame.height as f64);
+        Ok(Func {
+            base,
+            method,
+            wrap,
+            scale,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The resample filter function.
+pub struct Func {
+    base: Arc<dyn Texture>,
+    method: SampleMethod,
+    wrap: WrapMode,
+    scale: f64,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let u = x as f64 / self.width as f64;
+        let v = y as f64 / self.height as f64;
+        let texel = match self.scale > 1.0 && self.method != SampleMethod::Nearest {
+            true => self
+                .base
+                .sample_scaled(u, v, self.method.kernel(), self.scale, self.wrap),
+            false => self.base.sample_with_wrap(u, v, self.method, self.wrap),
+        };
+        Texel::from_normalized_dithered(self.format, texel.normalize(), x, y)
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The script filter (feature "lua"): runs a Lua script for per texel logic
+//! that no built-in filter covers.
+//!
+//! The script must define a global `Main(x, y)` function returning the
+//! normalized RGBA components of the texel at the given coordinates. The
+//! following globals are available to it:
+//!
+//! * `Previous(x, y)`: returns the normalized RGBA components of the
+//!   previous pass; coordinates clamp to the target edges.
+//! * `Buffer(name, x, y)`: same over a published buffer, failing when no
+//!   buffer of that name exists.
+//! * `Parameters`: a table of all scalar parameters of the run, with
+//!   vectors as arrays; texture parameters are not included.
+//! * `Width`, `Height`: the render target size in texels.
+//!
+//! Lua is embedded single-threaded, so the script is evaluated over the
+//! whole viewport when the pass function is built; syntax and runtime
+//! errors both fail the pass with the Lua error message.
+//!
+//! # Parameters
+//!
+//! * `file`: the path of the Lua script to run.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use mlua::Lua;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::filter::Viewport;
+use crate::params::Parameter;
+use crate::params::ParameterMap;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "script",
+    description: "Runs a Lua script for arbitrary per texel logic.",
+    params: &[
+        ParameterSchema {
+            name: "file",
+            ty: ParameterType::String,
+            default: None,
+            required: true,
+        },
+    ],
+};
+
+fn script_error(e: mlua::Error) -> FilterError {
+    FilterError::Script(e.to_string())
+}
+
+/// Samples a texture at clamped coordinates, as exposed to Lua.
+fn sample(texture: &dyn Texture, x: i64, y: i64) -> (f64, f64, f64, f64) {
+    let x = x.clamp(0, texture.width() as i64 - 1) as u32;
+    let y = y.clamp(0, texture.height() as i64 - 1) as u32;
+    let rgba = texture.get(x, y).normalize();
+    (
+        rgba[0] as f64,
+        rgba[1] as f64,
+        rgba[2] as f64,
+        rgba[3] as f64,
+    )
+}
+
+fn parameter_table(lua: &Lua, params: &ParameterMap) -> mlua::Result<mlua::Table> {
+    let table = lua.create_table()?;
+    for (name, param) in params.iter() {
+        match param {
+            Parameter::Float(v) => table.set(name.as_str(), *v)?,
+            Parameter::Int(v) => table.set(name.as_str(), *v)?,
+            Parameter::Bool(v) => table.set(name.as_str(), *v)?,
+            Parameter::Vector2(v) => table.set(name.as_str(), v.to_vec())?,
+            Parameter::Vector3(v) => table.set(name.as_str(), v.to_vec())?,
+            Parameter::Vector4(v) => table.set(name.as_str(), v.to_vec())?,
+            Parameter::Color(v) => table.set(name.as_str(), v.to_vec())?,
+            Parameter::String(v) => table.set(name.as_str(), v.as_str())?,
+            Parameter::Texture(_) => (),
+        }
+    }
+    Ok(table)
+}
+
+fn set_globals(
+    lua: &Lua,
+    frame: &FrameBuffer,
+    params: &ParameterMap,
+) -> mlua::Result<()> {
+    let globals = lua.globals();
+    let previous = frame.previous.clone();
+    globals.set(
+        "Previous",
+        lua.create_function(move |_, (x, y): (i64, i64)| Ok(sample(&*previous, x, y)))?,
+    )?;
+    let buffers: HashMap<String, Arc<OutputTexture>> = frame.buffers.clone();
+    globals.set(
+        "Buffer",
+        lua.create_function(move |_, (name, x, y): (String, i64, i64)| {
+            let buffer = buffers
+                .get(&name)
+                .ok_or_else(|| mlua::Error::runtime(format!("unknown buffer '{}'", name)))?;
+            Ok(sample(&**buffer, x, y))
+        })?,
+    )?;
+    globals.set("Parameters", parameter_table(lua, params)?)?;
+    globals.set("Width", frame.width)?;
+    globals.set("Height", frame.height)?;
+    Ok(())
+}
+
+/// The script filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let file = params
+            .get("file")
+            .ok_or(FilterError::MissingParameter("file"))?
+            .as_string()
+            .ok_or(FilterError::InvalidParameter("file"))?;
+        let source = std::fs::read_to_string(file)
+            .map_err(|e| FilterError::Script(format!("unable to read '{}': {}", file, e)))?;
+        let lua = Lua::new();
+        set_globals(&lua, frame, params).map_err(script_error)?;
+        lua.load(&source).exec().map_err(script_error)?;
+        let main: mlua::Function = lua
+            .globals()
+            .get("Main")
+            .map_err(|_| FilterError::Script("the script defines no Main(x, y) function".into()))?;
+        // Lua states are single-threaded, so the whole viewport is rendered
+        // here and the per texel function only indexes the result.
+        let viewport = frame.viewport;
+        let mut texels =
+            Vec::with_capacity(viewport.width as usize * viewport.height as usize);
+        for y in viewport.y..viewport.y + viewport.height {
+            for x in viewport.x..viewport.x + viewport.width {
+                let (r, g, b, a): (f64, f64, f64, f64) =
+                    main.call((x, y)).map_err(script_error)?;
+                texels.push(Texel::from_normalized_dithered(
+                    frame.format,
+                    [r as f32, g as f32, b as f32, a as f32],
+                    x,
+                    y,
+                ));
+            }
+        }
+        Ok(Func { viewport, texels })
+    }
+}
+
+/// The script filter function.
+pub struct Func {
+    viewport: Viewport,
+    texels: Vec<Texel>,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let index = (y - self.viewport.y) * self.viewport.width + (x - self.viewport.x);
+        self.texels[index as usize]
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The toksvig filter: widens the roughness of the previous pass where a
+//! normal map varies, reducing specular aliasing.
+//!
+//! The length of the average normal over a small footprint measures the
+//! normal variance (Toksvig's factor): flat regions average to unit length,
+//! bumpy ones to shorter vectors. The variance folds into the roughness as
+//! `sqrt(r² + strength · (1 - |n̄|) / |n̄|)`, so minified normal detail
+//! reappears as wider highlights instead of shimmer.
+//!
+//! # Parameters
+//!
+//! * `normals`: the normal map, in the usual [0, 1] encoding.
+//! * `channel`: the roughness channel to widen, one of "r", "g", "b" or
+//!   "a" (default "r").
+//! * `radius`: the averaging footprint radius in texels (default 1).
+//! * `strength`: a multiplier on the folded in variance (default 1.0).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "toksvig",
+    description: "Widens the roughness of the previous pass where a normal map varies.",
+    params: &[
+        ParameterSchema {
+            name: "normals",
+            ty: ParameterType::Texture,
+            default: None,
+            required: true,
+        },
+        ParameterSchema {
+            name: "channel",
+            ty: ParameterType::String,
+            default: Some("r"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "radius",
+            ty: ParameterType::Int,
+            default: Some("1"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "strength",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+    ],
+};
+
+/// The toksvig filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let normals = frame.require_input(params, "normals")?;
+        let channel = match params.get("channel") {
+            Some(v) => match v.as_string() {
+                Some("r") => 0,
+                Some("g") => 1,
+                Some("b") => 2,
+                Some("a") => 3,
+                _ => return Err(FilterError::InvalidParameter("channel")),
+            },
+            None => 0,
+        };
+        let radius = match params.get("radius") {
+            Some(v) => v.as_int().ok_or(FilterError::InvalidParameter("radius"))?,
+            None => 1,
+        };
+        if radius < 1 {
+            return Err(FilterError::InvalidParameter("radius"));
+        }
+        let strength = match params.get("strength") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("strength"))? as f32,
+            None => 1.0,
+        };
+        if strength < 0.0 {
+            return Err(FilterError::InvalidParameter("strength"));
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            normals,
+            channel,
+            radius,
+            strength,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        })
+    }
+}
+
+/// The toksvig filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    normals: Arc<dyn Texture>,
+    channel: usize,
+    radius: i64,
+    strength: f32,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        // Average the decoded normals over the footprint, sampling the map
+        // at the target resolution.
+        let mut mean = [0.0f32; 3];
+        let mut count = 0u32;
+        for dy in -self.radius..=self.radius {
+            for dx in -self.radius..=self.radius {
+                let sx = (x as i64 + dx).clamp(0, self.width as i64 - 1);
+                let sy = (y as i64 + dy).clamp(0, self.height as i64 - 1);
+                let u = (sx as f64 + 0.5) / self.width as f64;
+                let v = (sy as f64 + 0.5) / self.height as f64;
+                let [r, g, b, _] = self.normals.sample(u, v).normalize();
+                mean[0] += r * 2.0 - 1.0;
+                mean[1] += g * 2.0 - 1.0;
+                mean[2] += b * 2.0 - 1.0;
+                count += 1;
+            }
+        }
+        for component in &mut mean {
+            *component /= count as f32;
+        }
+        let length = (mean[0] * mean[0] + mean[1] * mean[1] + mean[2] * mean[2])
+            .sqrt()
+            .clamp(f32::EPSILON, 1.0);
+        let variance = (1.0 - length) / length;
+        let mut rgba = self.previous.get(x, y).normalize();
+        let roughness = rgba[self.channel];
+        rgba[self.channel] = (roughness * roughness + self.strength * variance)
+            .sqrt()
+            .min(1.0);
+        Texel::from_normalized_dithered(self.format, rgba, x, y)
+    }
+}
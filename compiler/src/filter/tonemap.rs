@@ -0,0 +1,174 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The tonemap filter: compresses the HDR range of the previous pass into
+//! [0, 1] so float pipelines can emit LDR end products.
+//!
+//! # Parameters
+//!
+//! * `operator`: the tone mapping operator, one of "reinhard", "aces" or
+//!   "filmic" (default "reinhard").
+//! * `exposure`: a linear multiplier applied before tone mapping
+//!   (default 1.0).
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "tonemap",
+    description: "Compresses the HDR range of the previous pass into [0, 1].",
+    params: &[
+        ParameterSchema {
+            name: "operator",
+            ty: ParameterType::String,
+            default: Some("reinhard"),
+            required: false,
+        },
+        ParameterSchema {
+            name: "exposure",
+            ty: ParameterType::Float,
+            default: Some("1.0"),
+            required: false,
+        },
+    ],
+};
+
+/// The tone mapping operators.
+enum Operator {
+    /// The simple Reinhard operator, `x / (1 + x)`.
+    Reinhard,
+
+    /// The ACES filmic approximation by Krzysztof Narkowicz.
+    Aces,
+
+    /// The Hejl/Burgess-Dawson filmic approximation (includes its own
+    /// gamma, inverted here so the output stays linear).
+    Filmic,
+}
+
+impl Operator {
+    fn from_name(name: &str) -> Option<Operator> {
+        match name {
+            "reinhard" => Some(Operator::Reinhard),
+            "aces" => Some(Operator::Aces),
+            "filmic" => Some(Operator::Filmic),
+            _ => None,
+        }
+    }
+
+    fn map(&self, x: f32) -> f32 {
+        match self {
+            Operator::Reinhard => x / (1.0 + x),
+            Operator::Aces => {
+                let y = x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14);
+                y.clamp(0.0, 1.0)
+            }
+            Operator::Filmic => {
+                let x = (x - 0.004).max(0.0);
+                let y = (x * (6.2 * x + 0.5)) / (x * (6.2 * x + 1.7) + 0.06);
+                // The curve approximates an sRGB encode; undo it so the
+                // output pipeline re-encodes once.
+                y.powf(2.2)
+            }
+        }
+    }
+}
+
+/// The tonemap filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let operator = match params.get("operator") {
+            Some(v) => v
+                .as_string()
+                .and_then(Operator::from_name)
+                .ok_or(FilterError::InvalidParameter("operator"))?,
+            None => Operator::Reinhard,
+        };
+        let exposure = match params.get("exposure") {
+            Some(v) => v
+                .as_float()
+                .ok_or(FilterError::InvalidParameter("exposure"))? as f32,
+            None => 1.0,
+        };
+        if exposure <= 0.0 {
+            return Err(FilterError::InvalidParameter("exposure"));
+        }
+        Ok(Func {
+            previous: frame.previous.clone(),
+            operator,
+            exposure,
+            format: frame.format,
+        })
+    }
+}
+
+/// The tonemap filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    operator: Operator,
+    exposure: f32,
+    format: Format,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        let [r, g, b, a] = self.previous.get(x, y).normalize();
+        let rgba = [
+            self.operator.map(r * self.exposure),
+            self.operator.map(g * self.exposure),
+            self.operator.map(b * self.exposure),
+            a,
+        ];
+        Texel::from_normalized_dithered(self.format, rgba, x, y)
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The transform filter: flips or rotates the previous pass in 90 degree
+//! steps.
+//!
+//! The render target size is fixed by the pipeline, so 90 and 270 degree
+//! rotations of a non-square target also stretch the content to the target's
+//! aspect ratio.
+//!
+//! # Parameters
+//!
+//! * `op`: the transform, one of "flip_x", "flip_y", "rotate90",
+//!   "rotate180" or "rotate270".
+
+use std::sync::Arc;
+
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::ParameterSchema;
+use crate::filter::ParameterType;
+use crate::params::ParameterMap;
+use crate::texture::OutputTexture;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// Describes this filter to front-ends.
+pub const INFO: FilterInfo = FilterInfo {
+    name: "transform",
+    description: "Flips or rotates the previous pass in 90 degree steps.",
+    params: &[
+        ParameterSchema {
+            name: "op",
+            ty: ParameterType::String,
+            default: None,
+            required: true,
+        },
+    ],
+};
+
+/// The supported transforms.
+enum Op {
+    FlipX,
+    FlipY,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Op {
+    fn from_name(name: &str) -> Option<Op> {
+        match name {
+            "flip_x" => Some(Op::FlipX),
+            "flip_y" => Some(Op::FlipY),
+            "rotate90" => Some(Op::Rotate90),
+            "rotate180" => Some(Op::Rotate180),
+            "rotate270" => Some(Op::Rotate270),
+            _ => None,
+        }
+    }
+}
+
+/// The transform filter.
+pub struct Filter;
+
+impl crate::filter::New for Filter {
+    fn new() -> Filter {
+        Filter
+    }
+}
+
+impl crate::filter::Filter for Filter {
+    type Function = Func;
+
+    fn new_function(
+        &self,
+        frame: &FrameBuffer,
+        params: &ParameterMap,
+    ) -> Result<Func, FilterError> {
+        let op = params
+            .get("op")
+            .ok_or(FilterError::MissingParameter("op"))?
+            .as_string()
+            .and_then(Op::from_name)
+            .ok_or(FilterError::InvalidParameter("op"))?;
+        Ok(Func {
+            previous: frame.previous.clone(),
+            op,
+            width: frame.width,
+            height: frame.height,
+        })
+    }
+}
+
+/// The transform filter function.
+pub struct Func {
+    previous: Arc<OutputTexture>,
+    op: Op,
+    width: u32,
+    height: u32,
+}
+
+impl crate::filter::Function for Func {
+    fn apply(&self, x: u32, y: u32) -> Texel {
+        // Each output texel pulls from the source texel the inverse transform
+        // maps it to; rotations swap axes in normalized space so non-square
+        // targets stay in bounds.
+        let last_x = self.width - 1;
+        let last_y = self.height - 1;
+        let (sx, sy) = match self.op {
+            Op::FlipX => (last_x - x, y),
+            Op::FlipY => (x, last_y - y),
+            Op::Rotate180 => (last_x - x, last_y - y),
+            Op::Rotate90 => self.remap(y, last_x - x),
+            Op::Rotate270 => self.remap(last_y - y, x),
+        };
+        self.previous.get(sx, sy)
+    }
+}
+
+impl Func {
+    /// Rescales axis-swapped coordinates back into the target's bounds.
+    fn remap(&self, x: u32, y: u32) -> (u32, u32) {
+        if self.width == self.height {
+            return (x, y);
+        }
+        let sx = (x as u64 * (self.width - 1) as u64 / (self.height - 1).max(1) as u64) as u32;
+        let sy = (y as u64 * (self.height - 1) as u64 / (self.width - 1).max(1) as u64) as u32;
+        (sx, sy)
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Importer reading compiled BPX textures back, so one compilation can
+//! feed another without a round trip through an intermediate image.
+//!
+//! Only raw payloads load; block compressed payloads would need a full
+//! decoder and intermediates are written raw anyway.
+
+use std::io::Read;
+
+use crate::import::ImportError;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+
+/// Returns the texel format stored under the given container header id.
+fn format_of(id: u8) -> Option<Format> {
+    match id {
+        0 => Some(Format::L8),
+        1 => Some(Format::F32),
+        2 => Some(Format::RGBA8),
+        3 => Some(Format::RGBAF32),
+        4 => Some(Format::R16),
+        5 => Some(Format::RGBA16),
+        6 => Some(Format::RGBA16F),
+        7 => Some(Format::RG8),
+        8 => Some(Format::RG16),
+        9 => Some(Format::RGBA8Srgb),
+        10 => Some(Format::RGB565),
+        11 => Some(Format::RGBA4444),
+        12 => Some(Format::RGB5A1),
+        13 => Some(Format::P8),
+        _ => None,
+    }
+}
+
+/// Loads a raw encoded BPX texture from an arbitrary reader.
+///
+/// Fails with [Unsupported](ImportError::Unsupported) when the payload is
+/// block compressed; recompile the source with
+/// [Raw](crate::encode::Encoding::Raw) encoding to chain it into another
+/// pipeline.
+pub fn load_bpx<R: Read>(mut reader: R) -> Result<OutputTexture, ImportError> {
+    let mut header = [0u8; 24];
+    reader.read_exact(&mut header)?;
+    if header[..4] != *b"BPXT" {
+        return Err(ImportError::Malformed("not a BPX texture"));
+    }
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&header[4..8]);
+    let version = u32::from_le_bytes(buf);
+    if version != 1 {
+        return Err(ImportError::Unsupported("BPX container version"));
+    }
+    buf.copy_from_slice(&header[8..12]);
+    let width = u32::from_le_bytes(buf);
+    buf.copy_from_slice(&header[12..16]);
+    let height = u32::from_le_bytes(buf);
+    let format =
+        format_of(header[16]).ok_or(ImportError::Malformed("unknown texel format"))?;
+    if header[18] != 0 {
+        return Err(ImportError::Unsupported(
+            "block compressed payload, only raw payloads load back",
+        ));
+    }
+    if header[19] > 1 {
+        return Err(ImportError::Unsupported(
+            "layered texture, only single layer textures load back",
+        ));
+    }
+    if header[20] != 0 {
+        return Err(ImportError::Unsupported(
+            "cubemap texture, only plain 2D textures load back",
+        ));
+    }
+    let mut palette = Box::new([[0u8; 4]; 256]);
+    if format == Format::P8 {
+        for entry in palette.iter_mut() {
+            reader.read_exact(entry)?;
+        }
+    }
+    let mut size = [0u8; 8];
+    reader.read_exact(&mut size)?;
+    let size = u64::from_le_bytes(size);
+    let expected = width as u64 * height as u64 * format.texel_size() as u64;
+    if size != expected {
+        return Err(ImportError::Malformed("payload size does not match the header"));
+    }
+    let mut data = vec![0u8; size as usize];
+    reader.read_exact(&mut data)?;
+    Ok(OutputTexture::from_parts(width, height, format, data, palette))
+}
@@ -0,0 +1,248 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+//! The DDS importer, decoding BC1-5 payloads back to RGBA8.
+
+use std::io::Read;
+
+use image::RgbaImage;
+
+use crate::import::ImportError;
+use crate::texture::ImageTexture;
+
+/// The decodable payload layouts of a DDS file.
+enum Layout {
+    Rgba8,
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+}
+
+/// Unpacks a RGB565 word into RGB bytes.
+fn unpack_565(word: u16) -> [u8; 3] {
+    [
+        (((word >> 11) & 31) * 255 / 31) as u8,
+        (((word >> 5) & 63) * 255 / 63) as u8,
+        ((word & 31) * 255 / 31) as u8,
+    ]
+}
+
+/// Decodes a 8 bytes BC1 color block into RGBA texels.
+///
+/// `opaque` forces the 4 color mode used inside BC3 blocks.
+pub(super) fn decode_bc1_block(data: &[u8], opaque: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([data[0], data[1]]);
+    let c1 = u16::from_le_bytes([data[2], data[3]]);
+    let e0 = unpack_565(c0);
+    let e1 = unpack_565(c1);
+    let mut palette = [[0u8; 4]; 4];
+    palette[0] = [e0[0], e0[1], e0[2], 255];
+    palette[1] = [e1[0], e1[1], e1[2], 255];
+    if c0 > c1 || opaque {
+        for channel in 0..3 {
+            palette[2][channel] =
+                ((2 * e0[channel] as u32 + e1[channel] as u32) / 3) as u8;
+            palette[3][channel] =
+                ((e0[channel] as u32 + 2 * e1[channel] as u32) / 3) as u8;
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for channel in 0..3 {
+            palette[2][channel] =
+                ((e0[channel] as u32 + e1[channel] as u32) / 2) as u8;
+        }
+        palette[2][3] = 255;
+        // Palette entry 3 stays transparent black.
+    }
+    let indices = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let mut out = [[0u8; 4]; 16];
+    for (i, texel) in out.iter_mut().enumerate() {
+        *texel = palette[(indices >> (i * 2) & 3) as usize];
+    }
+    out
+}
+
+/// Decodes a 8 bytes BC4 alpha block into single channel texels.
+pub(super) fn decode_bc4_block(data: &[u8]) -> [u8; 16] {
+    let a0 = data[0];
+    let a1 = data[1];
+    let mut palette = [0u8; 8];
+    palette[0] = a0;
+    palette[1] = a1;
+    if a0 > a1 {
+        for (i, value) in palette.iter_mut().enumerate().skip(2) {
+            *value = (((8 - i) as u32 * a0 as u32 + (i - 1) as u32 * a1 as u32) / 7) as u8;
+        }
+    } else {
+        for (i, value) in palette.iter_mut().enumerate().take(6).skip(2) {
+            *value = (((6 - i) as u32 * a0 as u32 + (i - 1) as u32 * a1 as u32) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+    let mut indices = 0u64;
+    for (i, byte) in data[2..8].iter().enumerate() {
+        indices |= (*byte as u64) << (i * 8);
+    }
+    let mut out = [0u8; 16];
+    for (i, texel) in out.iter_mut().enumerate() {
+        *texel = palette[(indices >> (i * 3) & 7) as usize];
+    }
+    out
+}
+
+/// Copies a decoded 4x4 block into the image, clipping at its edges.
+pub(super) fn blit_block(image: &mut RgbaImage, bx: u32, by: u32, block: &[[u8; 4]; 16]) {
+    for dy in 0..4 {
+        for dx in 0..4 {
+            let x = bx * 4 + dx;
+            let y = by * 4 + dy;
+            if x < image.width() && y < image.height() {
+                image.put_pixel(x, y, image::Rgba(block[(dy * 4 + dx) as usize]));
+            }
+        }
+    }
+}
+
+/// Loads a DDS file from a reader, decoding its base level to RGBA8.
+pub fn load_dds<R: Read>(mut reader: R) -> Result<ImageTexture, ImportError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"DDS " {
+        return Err(ImportError::Malformed("missing DDS magic"));
+    }
+    let mut header = [0u8; 124];
+    reader.read_exact(&mut header)?;
+    let height = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+    let width = u32::from_le_bytes([header[12], header[13], header[14], header[15]]);
+    if width == 0 || height == 0 {
+        return Err(ImportError::Malformed("empty texture"));
+    }
+    let pf_flags = u32::from_le_bytes([header[76], header[77], header[78], header[79]]);
+    let four_cc = &header[80..84];
+    let layout = if pf_flags & 0x4 != 0 {
+        match four_cc {
+            b"DXT1" => Layout::Bc1,
+            b"DXT2" | b"DXT3" => Layout::Bc2,
+            b"DXT4" | b"DXT5" => Layout::Bc3,
+            b"DX10" => {
+                let mut dx10 = [0u8; 20];
+                reader.read_exact(&mut dx10)?;
+                let dxgi = u32::from_le_bytes([dx10[0], dx10[1], dx10[2], dx10[3]]);
+                match dxgi {
+                    28 | 29 => Layout::Rgba8,
+                    71 | 72 => Layout::Bc1,
+                    74 | 75 => Layout::Bc2,
+                    77 | 78 => Layout::Bc3,
+                    80 => Layout::Bc4,
+                    83 => Layout::Bc5,
+                    _ => return Err(ImportError::Unsupported("DXGI format")),
+                }
+            }
+            _ => return Err(ImportError::Unsupported("FourCC format")),
+        }
+    } else if pf_flags & 0x40 != 0 {
+        let bit_count = u32::from_le_bytes([header[84], header[85], header[86], header[87]]);
+        if bit_count != 32 {
+            return Err(ImportError::Unsupported("uncompressed bit depth"));
+        }
+        Layout::Rgba8
+    } else {
+        return Err(ImportError::Unsupported("pixel format"));
+    };
+    let mut image = RgbaImage::new(width, height);
+    if let Layout::Rgba8 = layout {
+        let mut row = vec![0u8; width as usize * 4];
+        for y in 0..height {
+            reader.read_exact(&mut row)?;
+            for x in 0..width {
+                let offset = x as usize * 4;
+                image.put_pixel(
+                    x,
+                    y,
+                    image::Rgba([row[offset], row[offset + 1], row[offset + 2], row[offset + 3]]),
+                );
+            }
+        }
+        return Ok(ImageTexture::new(image.into()));
+    }
+    let block_size = match layout {
+        Layout::Bc1 | Layout::Bc4 => 8,
+        _ => 16,
+    };
+    let mut data = vec![0u8; block_size];
+    for by in 0..height.div_ceil(4) {
+        for bx in 0..width.div_ceil(4) {
+            reader.read_exact(&mut data)?;
+            let block = match layout {
+                Layout::Rgba8 => unreachable!(),
+                Layout::Bc1 => decode_bc1_block(&data, false),
+                Layout::Bc2 => {
+                    let mut block = decode_bc1_block(&data[8..], true);
+                    for (i, texel) in block.iter_mut().enumerate() {
+                        // 16 explicit 4 bits alpha values, low nibble first.
+                        texel[3] = (data[i / 2] >> (i % 2 * 4) & 0xF) * 17;
+                    }
+                    block
+                }
+                Layout::Bc3 => {
+                    let mut block = decode_bc1_block(&data[8..], true);
+                    let alpha = decode_bc4_block(&data[..8]);
+                    for (texel, alpha) in block.iter_mut().zip(alpha) {
+                        texel[3] = alpha;
+                    }
+                    block
+                }
+                Layout::Bc4 => {
+                    let mut block = [[0u8; 4]; 16];
+                    let red = decode_bc4_block(&data);
+                    for (texel, red) in block.iter_mut().zip(red) {
+                        *texel = [red, red, red, 255];
+                    }
+                    block
+                }
+                Layout::Bc5 => {
+                    let mut block = [[0, 0, 0, 255u8]; 16];
+                    let red = decode_bc4_block(&data[..8]);
+                    let green = decode_bc4_block(&data[8..]);
+                    for ((texel, red), green) in block.iter_mut().zip(red).zip(green) {
+                        texel[0] = red;
+                        texel[1] = green;
+                    }
+                    block
+                }
+            };
+            blit_block(&mut image, bx, by, &block);
+        }
+    }
+    Ok(ImageTexture::new(image.into()))
+}
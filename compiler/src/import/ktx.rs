@@ -0,0 +1,256 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+//! The KTX and KTX2 importer, decoding the base level of engine ready
+//! textures back to an image texture.
+
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+use image::RgbaImage;
+
+use crate::import::dds;
+use crate::import::ImportError;
+use crate::texture::ImageTexture;
+
+/// The 12 bytes KTX2 file identifier.
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// The 12 bytes legacy KTX 1.1 file identifier.
+const KTX1_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, ImportError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, ImportError> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Decodes a raw texel payload into RGBA8, clamping float channels.
+///
+/// `channels` is the channel count and `float` selects 32 bits float
+/// channels over 8 bits ones; single channel data is replicated to grey.
+fn decode_raw(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    channels: usize,
+    float: bool,
+) -> Result<RgbaImage, ImportError> {
+    let texel_size = channels * if float { 4 } else { 1 };
+    if data.len() < width as usize * height as usize * texel_size {
+        return Err(ImportError::Malformed("truncated texel payload"));
+    }
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        let offset = (y as usize * width as usize + x as usize) * texel_size;
+        let mut texel = [0u8, 0, 0, 255];
+        for channel in 0..channels {
+            texel[channel] = if float {
+                let offset = offset + channel * 4;
+                let value = f32::from_le_bytes([
+                    data[offset],
+                    data[offset + 1],
+                    data[offset + 2],
+                    data[offset + 3],
+                ]);
+                (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+            } else {
+                data[offset + channel]
+            };
+        }
+        if channels == 1 {
+            texel[1] = texel[0];
+            texel[2] = texel[0];
+        }
+        image::Rgba(texel)
+    }))
+}
+
+/// Decodes a BC encoded payload into RGBA8 using the DDS block decoders.
+fn decode_blocks(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    vk_format: u32,
+) -> Result<RgbaImage, ImportError> {
+    let block_size = match vk_format {
+        131..=134 | 139 => 8,
+        _ => 16,
+    };
+    let blocks_x = width.div_ceil(4) as usize;
+    let blocks_y = height.div_ceil(4) as usize;
+    if data.len() < blocks_x * blocks_y * block_size {
+        return Err(ImportError::Malformed("truncated block payload"));
+    }
+    let mut image = RgbaImage::new(width, height);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let data = &data[(by * blocks_x + bx) * block_size..][..block_size];
+            let block = match vk_format {
+                131..=134 => dds::decode_bc1_block(data, false),
+                137 | 138 => {
+                    let mut block = dds::decode_bc1_block(&data[8..], true);
+                    let alpha = dds::decode_bc4_block(&data[..8]);
+                    for (texel, alpha) in block.iter_mut().zip(alpha) {
+                        texel[3] = alpha;
+                    }
+                    block
+                }
+                139 => {
+                    let mut block = [[0u8; 4]; 16];
+                    let red = dds::decode_bc4_block(data);
+                    for (texel, red) in block.iter_mut().zip(red) {
+                        *texel = [red, red, red, 255];
+                    }
+                    block
+                }
+                141 => {
+                    let mut block = [[0, 0, 0, 255u8]; 16];
+                    let red = dds::decode_bc4_block(&data[..8]);
+                    let green = dds::decode_bc4_block(&data[8..]);
+                    for ((texel, red), green) in block.iter_mut().zip(red).zip(green) {
+                        texel[0] = red;
+                        texel[1] = green;
+                    }
+                    block
+                }
+                _ => unreachable!(),
+            };
+            dds::blit_block(&mut image, bx as u32, by as u32, &block);
+        }
+    }
+    Ok(image)
+}
+
+/// Loads the base level of a KTX2 file.
+fn load_ktx2<R: Read + Seek>(mut reader: R) -> Result<ImageTexture, ImportError> {
+    let vk_format = read_u32(&mut reader)?;
+    let _type_size = read_u32(&mut reader)?;
+    let width = read_u32(&mut reader)?;
+    let height = read_u32(&mut reader)?;
+    let _depth = read_u32(&mut reader)?;
+    let _layers = read_u32(&mut reader)?;
+    let _faces = read_u32(&mut reader)?;
+    let levels = read_u32(&mut reader)?;
+    let scheme = read_u32(&mut reader)?;
+    if width == 0 || height == 0 || levels == 0 {
+        return Err(ImportError::Malformed("empty texture"));
+    }
+    // Skip the descriptor/key-value/supercompression index and read the
+    // base entry of the level index.
+    reader.seek(SeekFrom::Current(32))?;
+    let offset = read_u64(&mut reader)?;
+    let length = read_u64(&mut reader)?;
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut data = vec![0u8; length as usize];
+    reader.read_exact(&mut data)?;
+    let data = match scheme {
+        0 => data,
+        3 => miniz_oxide::inflate::decompress_to_vec_zlib(&data)
+            .map_err(|_| ImportError::Malformed("ZLIB supercompressed payload"))?,
+        _ => return Err(ImportError::Unsupported("supercompression scheme")),
+    };
+    let image = match vk_format {
+        9 => decode_raw(&data, width, height, 1, false)?,
+        37 | 43 => decode_raw(&data, width, height, 4, false)?,
+        100 => decode_raw(&data, width, height, 1, true)?,
+        109 => decode_raw(&data, width, height, 4, true)?,
+        131..=134 | 137 | 138 | 139 | 141 => decode_blocks(&data, width, height, vk_format)?,
+        _ => return Err(ImportError::Unsupported("VkFormat")),
+    };
+    Ok(ImageTexture::new(image.into()))
+}
+
+/// Loads the base level of a legacy KTX 1.1 file.
+fn load_ktx1<R: Read>(mut reader: R) -> Result<ImageTexture, ImportError> {
+    if read_u32(&mut reader)? != 0x0403_0201 {
+        return Err(ImportError::Unsupported("big endian KTX"));
+    }
+    let _gl_type = read_u32(&mut reader)?;
+    let _gl_type_size = read_u32(&mut reader)?;
+    let _gl_format = read_u32(&mut reader)?;
+    let gl_internal_format = read_u32(&mut reader)?;
+    let _gl_base_internal_format = read_u32(&mut reader)?;
+    let width = read_u32(&mut reader)?;
+    let height = read_u32(&mut reader)?;
+    let _depth = read_u32(&mut reader)?;
+    let _array_elements = read_u32(&mut reader)?;
+    let _faces = read_u32(&mut reader)?;
+    let _mip_levels = read_u32(&mut reader)?;
+    let kvd_bytes = read_u32(&mut reader)?;
+    if width == 0 || height == 0 {
+        return Err(ImportError::Malformed("empty texture"));
+    }
+    std::io::copy(
+        &mut reader.by_ref().take(kvd_bytes as u64),
+        &mut std::io::sink(),
+    )?;
+    let image_size = read_u32(&mut reader)?;
+    let mut data = vec![0u8; image_size as usize];
+    reader.read_exact(&mut data)?;
+    let image = match gl_internal_format {
+        0x8229 => {
+            // GL_R8; rows are padded to 4 bytes.
+            let pitch = (width as usize).next_multiple_of(4);
+            if data.len() < pitch * height as usize {
+                return Err(ImportError::Malformed("truncated texel payload"));
+            }
+            RgbaImage::from_fn(width, height, |x, y| {
+                let value = data[y as usize * pitch + x as usize];
+                image::Rgba([value, value, value, 255])
+            })
+        }
+        0x8058 => decode_raw(&data, width, height, 4, false)?, // GL_RGBA8
+        _ => return Err(ImportError::Unsupported("GL internal format")),
+    };
+    Ok(ImageTexture::new(image.into()))
+}
+
+/// Loads a KTX or KTX2 file from a reader, decoding its base level.
+pub fn load_ktx<R: Read + Seek>(mut reader: R) -> Result<ImageTexture, ImportError> {
+    let mut identifier = [0u8; 12];
+    reader.read_exact(&mut identifier)?;
+    if identifier == KTX2_IDENTIFIER {
+        load_ktx2(reader)
+    } else if identifier == KTX1_IDENTIFIER {
+        load_ktx1(reader)
+    } else {
+        Err(ImportError::Malformed("missing KTX identifier"))
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+//! Source texture importers beyond what the image crate decodes.
+
+mod bpx;
+mod dds;
+mod ktx;
+mod tiff;
+
+pub use bpx::load_bpx;
+pub use dds::load_dds;
+pub use ktx::load_ktx;
+pub use tiff::load_tiff;
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::texture::ImageTexture;
+use crate::texture::Texture;
+
+/// Errors raised while importing a source texture.
+#[derive(Debug)]
+pub enum ImportError {
+    /// An io error occured while reading the file.
+    Io(std::io::Error),
+
+    /// The file could not be decoded by the image crate.
+    Image(image::ImageError),
+
+    /// The file is not a valid instance of its container format.
+    Malformed(&'static str),
+
+    /// The file uses a feature of its container the importer cannot decode.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "io error: {}", e),
+            ImportError::Image(e) => write!(f, "image error: {}", e),
+            ImportError::Malformed(what) => write!(f, "malformed file: {}", what),
+            ImportError::Unsupported(what) => write!(f, "unsupported file: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+    fn from(e: std::io::Error) -> ImportError {
+        ImportError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for ImportError {
+    fn from(e: image::ImageError) -> ImportError {
+        ImportError::Image(e)
+    }
+}
+
+/// Loads a source texture from a path, dispatching on its extension.
+///
+/// Containers the image crate cannot decode (DDS, KTX) go through the
+/// importers of this module; everything else is decoded by the image
+/// crate directly. Float sources such as OpenEXR stay float-backed so
+/// RGBAF32 pipelines keep their precision.
+pub fn load_texture(path: &Path) -> Result<ImageTexture, ImportError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("dds") => load_dds(BufReader::new(File::open(path)?)),
+        Some("ktx") | Some("ktx2") => load_ktx(BufReader::new(File::open(path)?)),
+        Some("tif") | Some("tiff") => load_tiff(BufReader::new(File::open(path)?)),
+        _ => Ok(ImageTexture::new(image::open(path)?)),
+    }
+}
+
+/// Loads a texture parameter from a path.
+///
+/// Compiled BPX textures load through [load_bpx] so one compilation can
+/// feed another; everything else goes through [load_texture].
+pub fn load_parameter(path: &Path) -> Result<Arc<dyn Texture>, ImportError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("bpx") => Ok(Arc::new(load_bpx(BufReader::new(File::open(path)?))?)),
+        _ => Ok(Arc::new(load_texture(path)?)),
+    }
+}
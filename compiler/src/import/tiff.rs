@@ -0,0 +1,179 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+
+//! The TIFF importer, adding the float sample support the image crate lacks.
+//!
+//! Regular TIFF files are decoded by the image crate; this module only
+//! parses uncompressed single channel 32 bits float TIFFs (as exported by
+//! terrain tools) and maps them onto the float texture path.
+
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+use image::DynamicImage;
+use image::ImageFormat;
+use image::Rgba32FImage;
+
+use crate::import::ImportError;
+use crate::texture::ImageTexture;
+
+/// A single field of a TIFF image file directory.
+struct Entry {
+    kind: u16,
+    count: u32,
+    raw: [u8; 4],
+}
+
+fn read_u16(data: &[u8], le: bool) -> u16 {
+    if le {
+        u16::from_le_bytes([data[0], data[1]])
+    } else {
+        u16::from_be_bytes([data[0], data[1]])
+    }
+}
+
+fn read_u32(data: &[u8], le: bool) -> u32 {
+    if le {
+        u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+    } else {
+        u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+    }
+}
+
+impl Entry {
+    /// Returns the first value of this field, which for counts of one is
+    /// stored inline in the offset word.
+    fn value(&self, le: bool) -> u32 {
+        match self.kind {
+            3 => read_u16(&self.raw, le) as u32,
+            _ => read_u32(&self.raw, le),
+        }
+    }
+
+    /// Reads all values of this field, following the offset word when the
+    /// array does not fit inline.
+    fn values<R: Read + Seek>(&self, reader: &mut R, le: bool) -> Result<Vec<u32>, ImportError> {
+        let size = match self.kind {
+            3 => 2,
+            4 => 4,
+            _ => return Err(ImportError::Unsupported("TIFF field type")),
+        };
+        if self.count as usize * size <= 4 {
+            return Ok((0..self.count as usize)
+                .map(|i| match self.kind {
+                    3 => read_u16(&self.raw[i * 2..], le) as u32,
+                    _ => read_u32(&self.raw, le),
+                })
+                .collect());
+        }
+        reader.seek(SeekFrom::Start(read_u32(&self.raw, le) as u64))?;
+        let mut data = vec![0u8; self.count as usize * size];
+        reader.read_exact(&mut data)?;
+        Ok(data
+            .chunks(size)
+            .map(|chunk| match self.kind {
+                3 => read_u16(chunk, le) as u32,
+                _ => read_u32(chunk, le),
+            })
+            .collect())
+    }
+}
+
+/// Loads a TIFF file from a reader.
+///
+/// Uncompressed single channel float images decode to RGBAF32; everything
+/// else falls back to the image crate.
+pub fn load_tiff<R: Read + Seek>(mut reader: R) -> Result<ImageTexture, ImportError> {
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    let le = match &header[0..2] {
+        b"II" => true,
+        b"MM" => false,
+        _ => return Err(ImportError::Malformed("missing TIFF byte order mark")),
+    };
+    if read_u16(&header[2..4], le) != 42 {
+        return Err(ImportError::Malformed("missing TIFF magic"));
+    }
+    reader.seek(SeekFrom::Start(read_u32(&header[4..8], le) as u64))?;
+    let mut buf = [0u8; 2];
+    reader.read_exact(&mut buf)?;
+    let count = read_u16(&buf, le);
+    let mut fields = std::collections::HashMap::new();
+    for _ in 0..count {
+        let mut entry = [0u8; 12];
+        reader.read_exact(&mut entry)?;
+        let tag = read_u16(&entry[0..2], le);
+        fields.insert(
+            tag,
+            Entry {
+                kind: read_u16(&entry[2..4], le),
+                count: read_u32(&entry[4..8], le),
+                raw: [entry[8], entry[9], entry[10], entry[11]],
+            },
+        );
+    }
+    let value = |tag: u16, default: u32| fields.get(&tag).map(|e| e.value(le)).unwrap_or(default);
+    let compression = value(259, 1);
+    let samples = value(277, 1);
+    let bits = value(258, 1);
+    let sample_format = value(339, 1);
+    if sample_format != 3 || bits != 32 || samples != 1 || compression != 1 {
+        // Not the float layout this importer exists for; the image crate
+        // handles the usual integer TIFF flavours.
+        reader.seek(SeekFrom::Start(0))?;
+        return ImageTexture::from_reader(reader, ImageFormat::Tiff).map_err(Into::into);
+    }
+    let width = value(256, 0);
+    let height = value(257, 0);
+    if width == 0 || height == 0 {
+        return Err(ImportError::Malformed("empty texture"));
+    }
+    let rows_per_strip = value(278, height);
+    let offsets = match fields.get(&273) {
+        Some(entry) => entry.values(&mut reader, le)?,
+        None => return Err(ImportError::Malformed("missing strip offsets")),
+    };
+    let mut image = Rgba32FImage::new(width, height);
+    for (strip, offset) in offsets.iter().enumerate() {
+        let first_row = strip as u32 * rows_per_strip;
+        let rows = rows_per_strip.min(height - first_row);
+        reader.seek(SeekFrom::Start(*offset as u64))?;
+        let mut data = vec![0u8; width as usize * rows as usize * 4];
+        reader.read_exact(&mut data)?;
+        for (i, chunk) in data.chunks(4).enumerate() {
+            let l = f32::from_bits(read_u32(chunk, le));
+            let x = i as u32 % width;
+            let y = first_row + i as u32 / width;
+            image.put_pixel(x, y, image::Rgba([l, l, l, 1.0]));
+        }
+    }
+    Ok(ImageTexture::new(DynamicImage::ImageRgba32F(image)))
+}
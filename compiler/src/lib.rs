@@ -0,0 +1,1191 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The BlockProject 3D texture compiler core.
+
+pub mod desc;
+pub mod encode;
+pub mod filter;
+pub mod import;
+pub mod mipmap;
+pub mod output;
+pub mod params;
+pub mod pipeline;
+#[cfg(feature = "async")]
+pub mod progress;
+pub mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod texture;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::encode::EncodeError;
+use crate::encode::Encoding;
+use crate::encode::Quality;
+use crate::filter::DynamicFilter;
+use crate::filter::Filter;
+use crate::filter::FilterError;
+use crate::filter::FilterInfo;
+use crate::filter::FrameBuffer;
+use crate::filter::Viewport;
+use crate::mipmap::MipFilter;
+use crate::output::Container;
+use crate::params::Parameter;
+use crate::params::ParameterMap;
+use crate::pipeline::CancelToken;
+use crate::pipeline::Diagnostic;
+use crate::pipeline::ExecutorKind;
+use crate::pipeline::Pass;
+use crate::pipeline::PassReport;
+use crate::pipeline::Pipeline;
+use crate::pipeline::PipelineDelegate;
+use crate::pipeline::PipelineError;
+use crate::pipeline::Tiling;
+use crate::template::Template;
+use crate::template::TemplateError;
+use crate::texture::ArrayTexture;
+use crate::texture::Cubemap;
+use crate::texture::Format;
+use crate::texture::ImageTexture;
+use crate::texture::OutputTexture;
+use crate::texture::Texture;
+use crate::texture::TextureError;
+
+/// The configuration of a texture compilation.
+pub struct Config {
+    /// Width in texels of the output texture.
+    pub width: u32,
+
+    /// Height in texels of the output texture.
+    pub height: u32,
+
+    /// Format of the output texture.
+    pub format: Format,
+
+    /// If set, keeps the exact output size instead of rounding it up to
+    /// the next power of two, for assets authored at exact sizes such as
+    /// UI atlases.
+    pub npot: bool,
+
+    /// Path of the output texture file.
+    pub output: PathBuf,
+
+    /// Container format the output texture is written in.
+    pub container: Container,
+
+    /// Encoding of the texel payload stored in the output container.
+    pub encoding: Encoding,
+
+    /// If set, supercompresses the payload of containers that support it.
+    pub supercompress: bool,
+
+    /// Effort spent searching block compression endpoints.
+    pub quality: Quality,
+
+    /// Number of layers of the output texture array, 1 for a plain 2D
+    /// texture.
+    ///
+    /// Each layer renders the whole filter chain once, with the zero based
+    /// layer index injected into the shared parameters as an int parameter
+    /// named `layer`, and the container stores the layers as a texture
+    /// array. Terrain splat sets and material arrays build this way.
+    pub layers: u32,
+
+    /// If set, assembles the rendered layers into a cubemap instead of a
+    /// texture array.
+    ///
+    /// Requires exactly six square layers, one face per layer in the
+    /// +X -X +Y -Y +Z -Z container order; a
+    /// [equirect_to_cube](filter::equirect_to_cube) pass rendering face px,
+    /// nx, py, ny, pz, nz by layer index is the usual producer. The
+    /// container stores
+    /// the faces through its cubemap layout, and with
+    /// [mipmaps](Config::mipmaps) every face carries its own chain.
+    pub cubemap: bool,
+
+    /// If set, generates a full mip chain down to 1x1 and stores it in the
+    /// output container, one chain per layer.
+    pub mipmaps: bool,
+
+    /// The downsampling filter generating mip levels; box is the common
+    /// default but visibly blurs normal and detail maps, which kaiser and
+    /// lanczos keep sharp.
+    pub mip_filter: MipFilter,
+
+    /// If set, rescales the alpha of every mip level so the coverage of an
+    /// alpha test at this reference value stays constant across the chain,
+    /// keeping the silhouette of alpha tested foliage and fences from
+    /// thinning out in the distance; see
+    /// [preserve_coverage](mipmap::preserve_coverage). Only meaningful with
+    /// [mipmaps](Config::mipmaps).
+    pub alpha_coverage: Option<f64>,
+
+    /// Names of the filters re-run on every generated mip level below the
+    /// base, for adjustments that must follow downsampling instead of being
+    /// downsampled themselves, such as a Toksvig roughness correction or a
+    /// contrast boost at small mips.
+    ///
+    /// Each level renders the passes with the downsampled level as the
+    /// previous texture and its zero based level index as the int parameter
+    /// `mip`. Only meaningful with [mipmaps](Config::mipmaps).
+    pub mip_passes: Vec<String>,
+
+    /// Names of the filters to run in order.
+    ///
+    /// A name can carry a `:buffer` suffix: the output of that pass is then
+    /// published under the buffer name, and later filters can reference it
+    /// by passing the name as the value of a texture parameter.
+    pub filters: Vec<String>,
+
+    /// Parameters shared by all filters.
+    pub params: ParameterMap,
+
+    /// Per pass parameter overrides, zipped with [filters](Config::filters);
+    /// passes past the end of this list run on the shared map alone.
+    pub pass_params: Vec<ParameterMap>,
+
+    /// Number of threads to render with.
+    pub n_threads: usize,
+
+    /// The execution backend rendering the passes.
+    pub executor: ExecutorKind,
+
+    /// If set, additionally saves the output as a debug PNG next to the output file.
+    pub debug: bool,
+
+    /// If set, forces fixed seeds in stochastic filters and byte-identical
+    /// output containers so content-addressed stores get stable hashes.
+    pub deterministic: bool,
+
+    /// The seed every stochastic filter derives its randomness from, None
+    /// to let each pass draw a fresh one from the operating system.
+    ///
+    /// Unlike [deterministic](Config::deterministic) this only pins the
+    /// randomness, leaving container timestamps and encoder heuristics
+    /// untouched.
+    pub seed: Option<u64>,
+
+    /// If set, a texel rejected by the render target fails the pass instead
+    /// of warning and leaving a black texel.
+    pub strict: bool,
+
+    /// If set, renders each pass in fixed size tiles instead of over the
+    /// whole target at once, bounding peak memory on very large textures.
+    pub tiling: Option<Tiling>,
+
+    /// If set, skips the compilation and reuses the existing output when an
+    /// identical configuration already produced it.
+    ///
+    /// Reuse is tracked through a small `.cache` sidecar written next to
+    /// the output file, keyed on the content of every input including the
+    /// texel payload of source images.
+    pub cache: bool,
+
+    /// Upper bound in bytes on the memory the compilation may use for
+    /// render target buffers and per pass working memory, None for no
+    /// bound.
+    ///
+    /// When a whole pass would not fit, tiled rendering is enabled
+    /// automatically; when the render target buffers alone exceed the
+    /// budget, the compilation fails with [Error::MemoryBudget] instead of
+    /// being OOM-killed.
+    pub max_memory: Option<u64>,
+
+    /// If set, saves the pipeline state to this path after every completed
+    /// pass and resumes from it on the next run, so long bakes survive
+    /// crashes or machine restarts.
+    ///
+    /// The file is deleted once the compilation succeeds.
+    pub checkpoint: Option<PathBuf>,
+
+    /// Token used to cancel the compilation from another thread.
+    pub cancel: CancelToken,
+}
+
+/// Errors raised by the compiler.
+#[derive(Debug)]
+pub enum Error {
+    /// No filter exists with the given name.
+    UnknownFilter(String),
+
+    /// A filter could not be constructed.
+    Filter(FilterError),
+
+    /// A filter produced texels rejected by the render target in strict mode
+    /// (filter name, rejection).
+    Mismatch(String, TextureError),
+
+    /// The output could not be encoded.
+    Encode(EncodeError),
+
+    /// An io error occured while saving the output.
+    Io(std::io::Error),
+
+    /// The debug image could not be saved.
+    Image(image::ImageError),
+
+    /// A source texture could not be imported.
+    Import(import::ImportError),
+
+    /// A template could not be loaded.
+    Template(TemplateError),
+
+    /// A cubemap compilation was configured with a layer count other than
+    /// six (actual layer count).
+    CubemapLayers(u32),
+
+    /// The rendered layers could not be assembled into a cubemap.
+    Cubemap(TextureError),
+
+    /// The render target buffers alone exceed the configured memory budget
+    /// (required bytes, budget bytes).
+    MemoryBudget(u64, u64),
+
+    /// The compilation was cancelled through its cancellation token.
+    Cancelled,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownFilter(name) => write!(f, "unknown filter '{}'", name),
+            Error::Filter(e) => write!(f, "filter error: {}", e),
+            Error::Mismatch(name, e) => {
+                write!(f, "filter '{}' produced a rejected texel: {}", name, e)
+            }
+            Error::Encode(e) => write!(f, "encode error: {}", e),
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Image(e) => write!(f, "image error: {}", e),
+            Error::Import(e) => write!(f, "import error: {}", e),
+            Error::Template(e) => write!(f, "template error: {}", e),
+            Error::CubemapLayers(layers) => write!(
+                f,
+                "a cubemap needs exactly 6 layers but the configuration renders {}",
+                layers
+            ),
+            Error::Cubemap(e) => write!(f, "cubemap error: {}", e),
+            Error::MemoryBudget(required, budget) => write!(
+                f,
+                "the render target buffers need {} bytes but the memory budget is {}",
+                required, budget
+            ),
+            Error::Cancelled => f.write_str("the compilation was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<FilterError> for Error {
+    fn from(e: FilterError) -> Error {
+        Error::Filter(e)
+    }
+}
+
+impl From<PipelineError> for Error {
+    fn from(e: PipelineError) -> Error {
+        match e {
+            PipelineError::Filter(e) => Error::Filter(e),
+            PipelineError::Mismatch(name, e) => Error::Mismatch(name, e),
+            PipelineError::Cancelled => Error::Cancelled,
+        }
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(e: EncodeError) -> Error {
+        Error::Encode(e)
+    }
+}
+
+impl From<TemplateError> for Error {
+    fn from(e: TemplateError) -> Error {
+        Error::Template(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// The result of a successful compilation.
+pub struct CompileReport {
+    /// Paths of all files written by the compiler.
+    pub outputs: Vec<PathBuf>,
+
+    /// Final width in texels of the output texture, after any rounding.
+    pub width: u32,
+
+    /// Final height in texels of the output texture, after any rounding.
+    pub height: u32,
+
+    /// Format of the output texture.
+    pub format: Format,
+
+    /// Timing report of each rendered pass.
+    pub passes: Vec<PassReport>,
+
+    /// Non fatal issues encountered during the compilation, with the pass,
+    /// filter and texel position they refer to when known.
+    pub warnings: Vec<Diagnostic>,
+
+    /// The rendered output texture, so it can feed the parameters of a
+    /// follow up compilation without reloading the written file. None when
+    /// the compilation was skipped by the incremental cache or produced a
+    /// texture array, reported through [array](CompileReport::array).
+    pub texture: Option<Arc<OutputTexture>>,
+
+    /// The rendered texture array of a layered compilation. None for plain
+    /// 2D outputs and cached reuse.
+    pub array: Option<Arc<ArrayTexture>>,
+
+    /// FNV-1a hash of the output texel payload.
+    pub content_hash: u64,
+
+    /// True when the existing output was reused by the incremental cache
+    /// instead of being recompiled.
+    pub cached: bool,
+}
+
+impl CompileReport {
+    /// Total wall time spent rendering passes.
+    pub fn duration(&self) -> Duration {
+        self.passes.iter().map(|pass| pass.duration).sum()
+    }
+}
+
+/// Streaming FNV-1a hasher used for content hashes and cache fingerprints.
+pub(crate) struct Fnv1a {
+    hash: u64,
+}
+
+impl Fnv1a {
+    pub(crate) fn new() -> Fnv1a {
+        Fnv1a {
+            hash: 0xcbf29ce484222325,
+        }
+    }
+
+    pub(crate) fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Hashes everything that affects the output of a compilation: the target
+/// geometry, the filter chain, the parameters (source images by content)
+/// and the container options.
+fn config_fingerprint(config: &Config) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(&config.width.to_le_bytes());
+    hasher.write(&config.height.to_le_bytes());
+    hasher.write(&config.layers.to_le_bytes());
+    hasher.write(config.format.name().as_bytes());
+    hasher.write(&[config.cubemap as u8, config.mipmaps as u8]);
+    hasher.write(config.mip_filter.name().as_bytes());
+    hasher.write(&[config.alpha_coverage.is_some() as u8]);
+    if let Some(reference) = config.alpha_coverage {
+        hasher.write(&reference.to_le_bytes());
+    }
+    for name in &config.mip_passes {
+        hasher.write(name.as_bytes());
+        hasher.write(&[0]);
+    }
+    hasher.write(config.container.name().as_bytes());
+    hasher.write(config.encoding.name().as_bytes());
+    hasher.write(config.quality.name().as_bytes());
+    hasher.write(&[
+        config.supercompress as u8,
+        config.deterministic as u8,
+        config.npot as u8,
+    ]);
+    hasher.write(&[config.seed.is_some() as u8]);
+    if let Some(seed) = config.seed {
+        hasher.write(&seed.to_le_bytes());
+    }
+    hasher.write(&[config.max_memory.is_some() as u8]);
+    if let Some(budget) = config.max_memory {
+        hasher.write(&budget.to_le_bytes());
+    }
+    if let Some(tiling) = config.tiling {
+        hasher.write(&tiling.size.to_le_bytes());
+        hasher.write(&tiling.apron.to_le_bytes());
+    }
+    for name in &config.filters {
+        hasher.write(name.as_bytes());
+        hasher.write(&[0]);
+    }
+    hash_params(&mut hasher, &config.params);
+    for params in &config.pass_params {
+        hasher.write(&[0]);
+        hash_params(&mut hasher, params);
+    }
+    hasher.finish()
+}
+
+/// Hashes a parameter map into a configuration fingerprint.
+fn hash_params(hasher: &mut Fnv1a, params: &ParameterMap) {
+    // The map iterates in unspecified order; sort so two identical
+    // configurations fingerprint identically.
+    let mut params: Vec<_> = params.iter().collect();
+    params.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, value) in params {
+        hasher.write(name.as_bytes());
+        hasher.write(&[0]);
+        // A tag byte per type keeps e.g. Int(1) and Bool(true) apart.
+        match value {
+            Parameter::Float(v) => {
+                hasher.write(&[1]);
+                hasher.write(&v.to_le_bytes());
+            }
+            Parameter::Int(v) => {
+                hasher.write(&[2]);
+                hasher.write(&v.to_le_bytes());
+            }
+            Parameter::Bool(v) => hasher.write(&[3, *v as u8]),
+            Parameter::Vector2(v) => {
+                hasher.write(&[4]);
+                for component in v {
+                    hasher.write(&component.to_le_bytes());
+                }
+            }
+            Parameter::Vector3(v) => {
+                hasher.write(&[5]);
+                for component in v {
+                    hasher.write(&component.to_le_bytes());
+                }
+            }
+            Parameter::Vector4(v) => {
+                hasher.write(&[6]);
+                for component in v {
+                    hasher.write(&component.to_le_bytes());
+                }
+            }
+            Parameter::String(v) => {
+                hasher.write(&[7]);
+                hasher.write(v.as_bytes());
+            }
+            Parameter::Color(v) => {
+                hasher.write(&[9]);
+                for component in v {
+                    hasher.write(&component.to_le_bytes());
+                }
+            }
+            Parameter::Texture(v) => {
+                // Hashed through the trait so any texture implementation
+                // fingerprints by content, not by identity.
+                hasher.write(&[8]);
+                hasher.write(&v.width().to_le_bytes());
+                hasher.write(&v.height().to_le_bytes());
+                for y in 0..v.height() {
+                    for x in 0..v.width() {
+                        for channel in v.get(x, y).normalize() {
+                            hasher.write(&channel.to_le_bytes());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the path of the cache sidecar tracking the given output.
+fn cache_path(output: &Path) -> PathBuf {
+    let mut ext = output.extension().unwrap_or_default().to_os_string();
+    ext.push(".cache");
+    output.with_extension(ext)
+}
+
+/// Returns the report of a previous identical compilation if its outputs
+/// are still on disk, None if anything changed.
+fn try_reuse(config: &Config, fingerprint: u64) -> Option<CompileReport> {
+    if !config.output.is_file() {
+        return None;
+    }
+    let entry = std::fs::read_to_string(cache_path(&config.output)).ok()?;
+    let mut fields = entry.split_whitespace();
+    if u64::from_str_radix(fields.next()?, 16).ok()? != fingerprint {
+        return None;
+    }
+    let content_hash = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let width = fields.next()?.parse().ok()?;
+    let height = fields.next()?.parse().ok()?;
+    let mut outputs = vec![config.output.clone()];
+    if config.debug {
+        let path = config.output.with_extension("png");
+        if !path.is_file() {
+            return None;
+        }
+        outputs.push(path);
+    }
+    Some(CompileReport {
+        outputs,
+        width,
+        height,
+        format: config.format,
+        passes: Vec::new(),
+        warnings: Vec::new(),
+        texture: None,
+        array: None,
+        content_hash,
+        cached: true,
+    })
+}
+
+fn run_config<D: PipelineDelegate>(
+    config: &Config,
+    n_threads: usize,
+    delegate: &D,
+) -> Result<CompileReport, Error> {
+    let fingerprint = match config.cache {
+        true => {
+            let fingerprint = config_fingerprint(config);
+            if let Some(report) = try_reuse(config, fingerprint) {
+                return Ok(report);
+            }
+            Some(fingerprint)
+        }
+        false => None,
+    };
+    let build_passes = || -> Result<Vec<Pass>, Error> {
+        config
+            .filters
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let (name, publish) = match name.split_once(':') {
+                    Some((name, publish)) => (name, Some(publish.into())),
+                    None => (name.as_str(), None),
+                };
+                let params = config.pass_params.get(index).cloned().unwrap_or_default();
+                DynamicFilter::from_name(name)
+                    .map(|filter| Pass {
+                        filter,
+                        publish,
+                        params,
+                    })
+                    .ok_or_else(|| Error::UnknownFilter(name.into()))
+            })
+            .collect::<Result<_, _>>()
+    };
+    let passes = build_passes()?;
+    let mut warnings = Vec::new();
+    let mut tiling = config.tiling;
+    if let Some(budget) = config.max_memory {
+        let width = config.width.next_power_of_two() as u64;
+        let height = config.height.next_power_of_two() as u64;
+        let target = width * height * config.format.texel_size() as u64;
+        let published = passes.iter().filter(|pass| pass.publish.is_some()).count() as u64;
+        // The two swap chain buffers plus one buffer kept alive per
+        // published pass are resident for the whole run; tiling cannot
+        // shrink them, so a budget below that is a hard error.
+        let resident = target * (2 + published);
+        if resident > budget {
+            return Err(Error::MemoryBudget(resident, budget));
+        }
+        // Rendering a pass costs a handful of Texels of working memory per
+        // texel in flight (spans plus kernel intermediates); fall back to
+        // tiles sized for the remaining budget when a whole pass does not
+        // fit.
+        const WORKING_PER_TEXEL: u64 = 32;
+        if tiling.is_none() && resident + width * height * WORKING_PER_TEXEL > budget {
+            let mut size: u32 = 16;
+            while (size as u64 * 2) * (size as u64 * 2) * WORKING_PER_TEXEL <= budget - resident
+                && (size as u64) < width.max(height)
+            {
+                size *= 2;
+            }
+            tiling = Some(Tiling { size, apron: 8 });
+            warnings.push(Diagnostic::global(format!(
+                "enabled tiled rendering ({} texel tiles) to honor the {} bytes memory budget",
+                size, budget
+            )));
+        }
+    }
+    let layers = config.layers.max(1);
+    let mut prebuilt = Some(passes);
+    let mut pass_reports = Vec::new();
+    let mut rendered: Vec<Arc<OutputTexture>> = Vec::with_capacity(layers as usize);
+    for layer in 0..layers {
+        let passes = match prebuilt.take() {
+            Some(passes) => passes,
+            None => build_passes()?,
+        };
+        let mut pipeline = Pipeline::with_executor(
+            config.width,
+            config.height,
+            config.format,
+            passes,
+            config.executor.into_executor(n_threads),
+            config.npot,
+        );
+        pipeline.set_deterministic(config.deterministic);
+        pipeline.set_seed(config.seed);
+        pipeline.set_strict(config.strict);
+        pipeline.set_tiling(tiling);
+        // Each layer checkpoints separately so resuming a layered bake
+        // never replays the state of another layer.
+        pipeline.set_checkpoint(match layers {
+            1 => config.checkpoint.clone(),
+            _ => config.checkpoint.as_ref().map(|path| {
+                let mut ext = path.extension().unwrap_or_default().to_os_string();
+                ext.push(format!(".{}", layer));
+                path.with_extension(ext)
+            }),
+        });
+        let layered;
+        let params = match layers {
+            1 => &config.params,
+            _ => {
+                let mut map = config.params.clone();
+                map.insert("layer".into(), Parameter::Int(layer as i64));
+                layered = map;
+                &layered
+            }
+        };
+        pass_reports.extend(pipeline.run(params, delegate, &mut warnings, &config.cancel)?);
+        rendered.push(pipeline.into_texture());
+    }
+    let output = rendered.last().unwrap().clone();
+    let build_mip_passes = || -> Result<Vec<Pass>, Error> {
+        config
+            .mip_passes
+            .iter()
+            .map(|name| {
+                DynamicFilter::from_name(name)
+                    .map(Pass::from)
+                    .ok_or_else(|| Error::UnknownFilter(name.clone()))
+            })
+            .collect()
+    };
+    // With mipmaps enabled every layer expands into its full chain; the
+    // writers store whatever depth they are handed.
+    let mut chains: Vec<Vec<Arc<OutputTexture>>> = Vec::with_capacity(rendered.len());
+    for (index, layer) in rendered.iter().enumerate() {
+        let chain = match config.mipmaps {
+            true => {
+                let mut chain = mipmap::generate(layer.clone(), config.mip_filter);
+                if !config.mip_passes.is_empty() {
+                    for (level, texture) in chain.iter_mut().enumerate().skip(1) {
+                        // Each level renders its own exactly sized pipeline
+                        // seeded with the downsampled level; the levels are
+                        // small enough that tiling never pays off.
+                        let mut pipeline = Pipeline::with_executor(
+                            texture.width(),
+                            texture.height(),
+                            texture.format(),
+                            build_mip_passes()?,
+                            config.executor.into_executor(n_threads),
+                            true,
+                        );
+                        pipeline.set_deterministic(config.deterministic);
+                        pipeline.set_seed(config.seed);
+                        pipeline.set_strict(config.strict);
+                        pipeline.set_input(texture.clone());
+                        let mut params = config.params.clone();
+                        if layers > 1 {
+                            params.insert("layer".into(), Parameter::Int(index as i64));
+                        }
+                        params.insert("mip".into(), Parameter::Int(level as i64));
+                        pass_reports.extend(pipeline.run(
+                            &params,
+                            delegate,
+                            &mut warnings,
+                            &config.cancel,
+                        )?);
+                        *texture = pipeline.into_texture();
+                    }
+                }
+                if let Some(reference) = config.alpha_coverage {
+                    mipmap::preserve_coverage(&mut chain, reference as f32);
+                }
+                chain
+            }
+            false => vec![layer.clone()],
+        };
+        chains.push(chain);
+    }
+    let array = match rendered.len() {
+        1 => None,
+        // Every layer comes from an identically sized pipeline, so
+        // building the array and pushing its levels cannot fail.
+        _ => {
+            let mut array = ArrayTexture::new(rendered.clone()).unwrap();
+            for level in 1..chains[0].len() {
+                array
+                    .push_mip(chains.iter().map(|chain| chain[level].clone()).collect())
+                    .unwrap();
+            }
+            Some(Arc::new(array))
+        }
+    };
+    let cubemap = match config.cubemap {
+        false => None,
+        true => {
+            if rendered.len() != 6 {
+                return Err(Error::CubemapLayers(layers));
+            }
+            // The layers come from identically sized pipelines, so only a
+            // non square face can be rejected here.
+            let faces = std::array::from_fn(|face| rendered[face].clone());
+            let mut cubemap = Cubemap::new(faces).map_err(Error::Cubemap)?;
+            for level in 1..chains[0].len() {
+                let mut faces = chains.iter().map(|chain| chain[level].clone());
+                // Every face chain halves in lockstep, so pushing the
+                // levels cannot fail.
+                cubemap
+                    .push_mip(std::array::from_fn(|_| faces.next().unwrap()))
+                    .unwrap();
+            }
+            Some(cubemap)
+        }
+    };
+    let mut outputs = Vec::new();
+    // The payload streams from the encoder into the container writer
+    // instead of being assembled in memory first.
+    encode::check(output.format(), config.encoding)?;
+    match &cubemap {
+        Some(cubemap) => match config.container {
+            Container::Bpx => output::write_bpx_cubemap(
+                &config.output,
+                cubemap,
+                config.encoding,
+                config.quality,
+            )?,
+            Container::Ktx2 => output::write_ktx2_cubemap(
+                &config.output,
+                cubemap,
+                config.encoding,
+                config.quality,
+                config.supercompress,
+            )?,
+            Container::Dds => output::write_dds_cubemap(
+                &config.output,
+                cubemap,
+                config.encoding,
+                config.quality,
+            )?,
+        },
+        None => match (&array, config.container) {
+            (None, Container::Bpx) => output::write_bpx(
+                &config.output,
+                &chains[0],
+                config.encoding,
+                config.quality,
+            )?,
+            (Some(array), Container::Bpx) => output::write_bpx_array(
+                &config.output,
+                array,
+                config.encoding,
+                config.quality,
+            )?,
+            (None, Container::Ktx2) => output::write_ktx2(
+                &config.output,
+                &chains[0],
+                config.encoding,
+                config.quality,
+                config.supercompress,
+            )?,
+            (Some(array), Container::Ktx2) => output::write_ktx2_array(
+                &config.output,
+                array,
+                config.encoding,
+                config.quality,
+                config.supercompress,
+            )?,
+            (None, Container::Dds) => output::write_dds(
+                &config.output,
+                &chains[0],
+                config.encoding,
+                config.quality,
+            )?,
+            (Some(array), Container::Dds) => output::write_dds_array(
+                &config.output,
+                array,
+                config.encoding,
+                config.quality,
+            )?,
+        },
+    }
+    outputs.push(config.output.clone());
+    if config.debug {
+        match &array {
+            None => {
+                let path = config.output.with_extension("png");
+                output.to_rgba_lossy().save(&path).map_err(Error::Image)?;
+                outputs.push(path);
+            }
+            Some(array) => {
+                for index in 0..array.len() {
+                    let path = config.output.with_extension(format!("{}.png", index));
+                    let layer = array.layer(index).unwrap();
+                    layer.to_rgba_lossy().save(&path).map_err(Error::Image)?;
+                    outputs.push(path);
+                }
+            }
+        }
+    }
+    let mut hasher = Fnv1a::new();
+    for chain in &chains {
+        for level in chain {
+            hasher.write(level.data());
+        }
+    }
+    let content_hash = hasher.finish();
+    if let Some(fingerprint) = fingerprint {
+        // A stale or unwritable sidecar only costs a recompile next run.
+        let _ = std::fs::write(
+            cache_path(&config.output),
+            format!(
+                "{:016x} {:016x} {} {}\n",
+                fingerprint,
+                content_hash,
+                output.width(),
+                output.height()
+            ),
+        );
+    }
+    Ok(CompileReport {
+        outputs,
+        width: output.width(),
+        height: output.height(),
+        format: output.format(),
+        passes: pass_reports,
+        warnings,
+        texture: match array.is_some() {
+            true => None,
+            false => Some(output),
+        },
+        array,
+        content_hash,
+        cached: false,
+    })
+}
+
+// A configured compile must stay movable onto a background thread; this
+// breaks whenever an inner type loses Send or Sync.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Compiler>();
+    assert_send_sync::<Session>();
+    assert_send_sync::<Pipeline>();
+    assert_send_sync::<filter::DynamicFilter>();
+    assert_send_sync::<filter::DynamicFunction>();
+};
+
+/// The texture compiler.
+pub struct Compiler {
+    config: Config,
+}
+
+impl Compiler {
+    /// Creates a new compiler from a configuration.
+    pub fn new(config: Config) -> Compiler {
+        Compiler { config }
+    }
+
+    /// Creates a compiler from a TOML template and caller parameters.
+    ///
+    /// The template provides the size, format and filter chain; `params`
+    /// supplies the parameters it declares. The output is written next to
+    /// the template as a BPX file and renders on four threads; adjust
+    /// either through [config_mut](Compiler::config_mut) before running.
+    pub fn from_template(path: &Path, params: ParameterMap) -> Result<Compiler, Error> {
+        let template = Template::load(path)?;
+        // Strip the ".template.toml" (or plain ".toml") suffix before
+        // appending the output extension.
+        let mut output = path.to_path_buf();
+        output.set_extension("");
+        if output.extension().map(|v| v == "template").unwrap_or(false) {
+            output.set_extension("");
+        }
+        output.set_extension("bpx");
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let config = template.into_config(dir, output, params, 4)?;
+        Ok(Compiler { config })
+    }
+
+    /// Returns the configuration of this compiler for adjustments between
+    /// construction and [run](Compiler::run).
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    /// Registers a user defined filter and appends it as the next pass of
+    /// this compiler.
+    ///
+    /// This is the extension point for applications embedding the crate:
+    /// any type implementing [Filter] renders per texel like the built-in
+    /// filters, without living in the crate's filter directory. The
+    /// instance is registered under the name of its info (see
+    /// [register_instance](filter::register_instance)), so subsequent
+    /// passes and templates can also refer to it by name, and receives
+    /// parameters from [params](Config::params) like every other pass.
+    pub fn add_custom_filter<F>(&mut self, info: &'static FilterInfo, filter: F)
+    where
+        F: Filter + Send + Sync + 'static,
+        F::Function: 'static,
+    {
+        filter::register_instance(info, filter);
+        self.config.filters.push(info.name.into());
+    }
+
+    /// Checks the configuration of this compiler without rendering.
+    ///
+    /// The target size and payload encoding are resolved and every filter
+    /// function is constructed against an empty frame buffer, so unknown
+    /// filters and missing, mistyped or unsupported parameters surface in
+    /// milliseconds instead of partway through a long render. Returns one
+    /// diagnostic per problem found; an empty list means the pipeline
+    /// would start rendering.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let config = &self.config;
+        let mut diagnostics = Vec::new();
+        if config.width == 0 || config.height == 0 {
+            diagnostics.push(Diagnostic::global(format!(
+                "invalid render target size {}x{}",
+                config.width, config.height
+            )));
+        }
+        if let Err(e) = encode::check(config.format, config.encoding) {
+            diagnostics.push(Diagnostic::global(e.to_string()));
+        }
+        let previous = Arc::new(match config.npot {
+            true => OutputTexture::new_exact(config.width.max(1), config.height.max(1), config.format),
+            false => OutputTexture::new(config.width.max(1), config.height.max(1), config.format),
+        });
+        let width = previous.width();
+        let height = previous.height();
+        if config.cubemap {
+            if config.layers != 6 {
+                diagnostics.push(Diagnostic::global(format!(
+                    "a cubemap needs exactly 6 layers but the configuration renders {}",
+                    config.layers
+                )));
+            }
+            if width != height {
+                diagnostics.push(Diagnostic::global(format!(
+                    "cubemap faces must be square but the output resolves to {}x{}",
+                    width, height
+                )));
+            }
+        }
+        let mut buffers = HashMap::new();
+        for (index, name) in config.filters.iter().enumerate() {
+            let (name, publish) = match name.split_once(':') {
+                Some((name, publish)) => (name, Some(publish)),
+                None => (name.as_str(), None),
+            };
+            let filter = match DynamicFilter::from_name(name) {
+                Some(filter) => filter,
+                None => {
+                    diagnostics.push(Diagnostic {
+                        pass: Some(index),
+                        filter: Some(name.into()),
+                        position: None,
+                        message: format!("unknown filter '{}'", name),
+                    });
+                    continue;
+                }
+            };
+            // Functions precompute intermediates over the viewport; keep it
+            // to a single texel so validation stays cheap.
+            let frame = FrameBuffer {
+                width,
+                height,
+                format: config.format,
+                viewport: Viewport {
+                    x: 0,
+                    y: 0,
+                    width: 1,
+                    height: 1,
+                },
+                previous: previous.clone(),
+                history: vec![previous.clone()],
+                buffers: buffers.clone(),
+                deterministic: config.deterministic,
+                seed: config.seed,
+            };
+            let merged;
+            let params = match config.pass_params.get(index) {
+                Some(overrides) if !overrides.is_empty() => {
+                    merged = config.params.merged(overrides);
+                    &merged
+                }
+                _ => &config.params,
+            };
+            if let Err(e) = filter.new_function(&frame, params) {
+                diagnostics.push(Diagnostic {
+                    pass: Some(index),
+                    filter: Some(name.into()),
+                    position: None,
+                    message: e.to_string(),
+                });
+            }
+            if let Some(publish) = publish {
+                buffers.insert(publish.into(), previous.clone());
+            }
+        }
+        // Mip passes run on the shared parameter map alone, with the level
+        // index added; validate them against level one.
+        let mut mip_params = config.params.clone();
+        mip_params.insert("mip".into(), Parameter::Int(1));
+        for name in &config.mip_passes {
+            let filter = match DynamicFilter::from_name(name) {
+                Some(filter) => filter,
+                None => {
+                    diagnostics.push(Diagnostic {
+                        pass: None,
+                        filter: Some(name.clone()),
+                        position: None,
+                        message: format!("unknown filter '{}'", name),
+                    });
+                    continue;
+                }
+            };
+            let frame = FrameBuffer {
+                width,
+                height,
+                format: config.format,
+                viewport: Viewport {
+                    x: 0,
+                    y: 0,
+                    width: 1,
+                    height: 1,
+                },
+                previous: previous.clone(),
+                history: vec![previous.clone()],
+                buffers: HashMap::new(),
+                deterministic: config.deterministic,
+                seed: config.seed,
+            };
+            if let Err(e) = filter.new_function(&frame, &mip_params) {
+                diagnostics.push(Diagnostic {
+                    pass: None,
+                    filter: Some(name.clone()),
+                    position: None,
+                    message: e.to_string(),
+                });
+            }
+        }
+        diagnostics
+    }
+
+    /// Compiles the texture, reporting progress to the given delegate.
+    ///
+    /// A compiler can run any number of times; every run renders the same
+    /// configuration from scratch.
+    pub fn run<D: PipelineDelegate>(&self, delegate: &D) -> Result<CompileReport, Error> {
+        run_config(&self.config, self.config.n_threads, delegate)
+    }
+
+    /// Compiles the texture on a background thread without blocking an
+    /// async runtime (feature "async").
+    ///
+    /// Progress arrives on the returned stream as
+    /// [ProgressEvent](progress::ProgressEvent)s and the job future
+    /// resolves to the report once the compilation ends. The render keeps
+    /// going if either is dropped; stop it through the configuration's
+    /// cancellation token.
+    #[cfg(feature = "async")]
+    pub fn run_async(
+        self,
+    ) -> (
+        futures::channel::mpsc::UnboundedReceiver<progress::ProgressEvent>,
+        progress::CompileJob,
+    ) {
+        let (delegate, events) = progress::StreamDelegate::new();
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(self.run(&delegate));
+        });
+        (events, progress::CompileJob { receiver })
+    }
+}
+
+/// A compilation session reusing resources across multiple jobs.
+///
+/// Batch scenarios should decode shared source images once through
+/// [load_texture](Session::load_texture) and run every job on the same
+/// session instead of reconstructing a full configuration per texture.
+pub struct Session {
+    n_threads: usize,
+    textures: HashMap<PathBuf, Arc<ImageTexture>>,
+}
+
+impl Session {
+    /// Creates a new session rendering with the given number of threads.
+    pub fn new(n_threads: usize) -> Session {
+        Session {
+            n_threads,
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of threads jobs of this session render with.
+    pub fn n_threads(&self) -> usize {
+        self.n_threads
+    }
+
+    /// Loads a texture, reusing the decoded image if it was already loaded
+    /// by a previous job of this session.
+    pub fn load_texture(&mut self, path: &Path) -> Result<Arc<ImageTexture>, Error> {
+        if let Some(texture) = self.textures.get(path) {
+            return Ok(texture.clone());
+        }
+        let texture = Arc::new(import::load_texture(path).map_err(Error::Import)?);
+        self.textures.insert(path.into(), texture.clone());
+        Ok(texture)
+    }
+
+    /// Compiles a single job of this session.
+    ///
+    /// The number of threads of the configuration is overridden by the
+    /// session wide thread count.
+    pub fn run<D: PipelineDelegate>(
+        &self,
+        config: &Config,
+        delegate: &D,
+    ) -> Result<CompileReport, Error> {
+        run_config(config, self.n_threads, delegate)
+    }
+}
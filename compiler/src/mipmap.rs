@@ -0,0 +1,251 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+//! Mip chain generation.
+//!
+//! Every level is downsampled from the base level with the kernel
+//! stretched over the full footprint (see
+//! [sample_scaled](crate::texture::Texture::sample_scaled)) instead of
+//! halving the previous level, so repeated filtering never compounds.
+//!
+//! Downsampling averages in the working color space of the texture's
+//! format: [RGBA8Srgb](crate::texture::Format::RGBA8Srgb) texels are
+//! linearized before averaging and re-encoded afterwards, which avoids
+//! the classic darkened mips of averaging gamma encoded bytes, while the
+//! linear formats average their stored values directly.
+
+use std::sync::Arc;
+
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::SampleKernel;
+use crate::texture::Texel;
+use crate::texture::Texture;
+use crate::texture::WrapMode;
+
+/// The downsampling filter generating mip levels.
+///
+/// Box is the fastest and the common default, but visibly blurs normal
+/// and detail maps; the windowed sinc filters keep those sharp at the
+/// cost of slight ringing around hard edges.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MipFilter {
+    /// Plain average of the footprint.
+    #[default]
+    Box,
+
+    /// Triangle (tent) weighting of the footprint.
+    Triangle,
+
+    /// Kaiser windowed sinc over three times the footprint.
+    Kaiser,
+
+    /// Lanczos windowed sinc over three times the footprint.
+    Lanczos,
+}
+
+impl MipFilter {
+    /// Returns the name of this filter as used by the command line and
+    /// documents.
+    pub fn name(self) -> &'static str {
+        match self {
+            MipFilter::Box => "box",
+            MipFilter::Triangle => "triangle",
+            MipFilter::Kaiser => "kaiser",
+            MipFilter::Lanczos => "lanczos",
+        }
+    }
+
+    /// Parses a filter from its command line name.
+    pub fn from_name(name: &str) -> Option<MipFilter> {
+        match name {
+            "box" => Some(MipFilter::Box),
+            "triangle" => Some(MipFilter::Triangle),
+            "kaiser" => Some(MipFilter::Kaiser),
+            "lanczos" => Some(MipFilter::Lanczos),
+            _ => None,
+        }
+    }
+
+    /// Returns the kernel this filter downsamples with.
+    pub fn kernel(self) -> SampleKernel {
+        match self {
+            MipFilter::Box => SampleKernel::Box,
+            MipFilter::Triangle => SampleKernel::Triangle,
+            MipFilter::Kaiser => SampleKernel::Kaiser,
+            MipFilter::Lanczos => SampleKernel::Lanczos3,
+        }
+    }
+}
+
+impl std::fmt::Display for MipFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl serde::Serialize for MipFilter {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for MipFilter {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<MipFilter, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        MipFilter::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format_args!("unknown mip filter '{}'", name)))
+    }
+}
+
+/// Generates the full mip chain of a texture, base level first, halving
+/// (rounded down, never below one texel) until a 1x1 level.
+///
+/// A 1x1 base is its own complete chain. sRGB formats are averaged in
+/// linear light (see the [module](crate::mipmap) documentation):
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use texturec_compiler::mipmap::{self, MipFilter};
+/// # use texturec_compiler::texture::{Format, OutputTexture, Texel, Texture};
+/// let mut base = OutputTexture::new_exact(2, 2, Format::RGBA8Srgb);
+/// for (x, y, value) in [(0, 0, 0), (1, 0, 255), (0, 1, 255), (1, 1, 0)] {
+///     base.set(x, y, Texel::RGBA8Srgb([value, value, value, 255])).unwrap();
+/// }
+/// let chain = mipmap::generate(Arc::new(base), MipFilter::Box);
+/// // Encoded 0 and 255 decode to 0.0 and 1.0; their linear mean 0.5
+/// // re-encodes near 188 instead of the darker encoded mean 127.
+/// assert_eq!(chain[1].get(0, 0), Texel::RGBA8Srgb([187, 187, 187, 255]));
+/// ```
+pub fn generate(base: Arc<OutputTexture>, filter: MipFilter) -> Vec<Arc<OutputTexture>> {
+    let kernel = filter.kernel();
+    let mut chain = vec![base];
+    loop {
+        let base = &chain[0];
+        let previous = chain.last().unwrap();
+        if previous.width() == 1 && previous.height() == 1 {
+            return chain;
+        }
+        let width = (previous.width() / 2).max(1);
+        let height = (previous.height() / 2).max(1);
+        let scale = (base.width() as f64 / width as f64)
+            .max(base.height() as f64 / height as f64);
+        let mut level = OutputTexture::new_exact(width, height, base.format());
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f64 + 0.5) / width as f64;
+                let v = (y as f64 + 0.5) / height as f64;
+                let texel = base.sample_scaled(u, v, kernel, scale, WrapMode::Clamp);
+                // The level shares the base format, so the texel cannot be
+                // rejected.
+                level.set(x, y, texel).unwrap();
+            }
+        }
+        chain.push(Arc::new(level));
+    }
+}
+
+/// Returns true when a format stores an alpha channel.
+fn has_alpha(format: Format) -> bool {
+    match format {
+        Format::RGBA8
+        | Format::RGBA8Srgb
+        | Format::RGBA16
+        | Format::RGBA16F
+        | Format::RGBAF32
+        | Format::RGBA4444
+        | Format::RGB5A1
+        | Format::P8 => true,
+        Format::L8 | Format::F32 | Format::R16 | Format::RG8 | Format::RG16 | Format::RGB565 => {
+            false
+        }
+    }
+}
+
+/// Fraction of texels whose alpha passes the reference of an alpha test.
+fn coverage(texture: &OutputTexture, reference: f32) -> f64 {
+    let mut passed = 0u64;
+    for y in 0..texture.height() {
+        for x in 0..texture.width() {
+            if texture.get(x, y).normalize()[3] > reference {
+                passed += 1;
+            }
+        }
+    }
+    passed as f64 / (texture.width() as u64 * texture.height() as u64) as f64
+}
+
+/// Returns a copy of a level with its alpha multiplied by `scale` and
+/// clamped, quantized through the level's own format.
+fn rescale(level: &OutputTexture, scale: f32) -> OutputTexture {
+    let mut rescaled = OutputTexture::new_exact(level.width(), level.height(), level.format());
+    for y in 0..level.height() {
+        for x in 0..level.width() {
+            let mut rgba = level.get(x, y).normalize();
+            rgba[3] = (rgba[3] * scale).min(1.0);
+            // The rescaled level shares the source format, so the texel
+            // cannot be rejected.
+            rescaled
+                .set(x, y, Texel::from_normalized(level.format(), rgba))
+                .unwrap();
+        }
+    }
+    rescaled
+}
+
+/// Rescales the alpha of every level below the base so the coverage of an
+/// alpha test at `reference` stays constant across the chain.
+///
+/// Downsampling pulls the alpha of edge texels towards the alpha test
+/// threshold, so alpha tested foliage and fences visibly thin out in the
+/// distance; matching each level's coverage to the base level's restores
+/// their silhouette. Formats without an alpha channel are left untouched.
+pub fn preserve_coverage(chain: &mut [Arc<OutputTexture>], reference: f32) {
+    if !has_alpha(chain[0].format()) {
+        return;
+    }
+    let target = coverage(&chain[0], reference);
+    for level in chain.iter_mut().skip(1) {
+        // Coverage grows monotonically with the scale; a short bisection on
+        // the rescaled level itself, so quantization through the texel
+        // format is accounted for, lands within a fraction of an alpha
+        // step. The upper bound is kept as the smallest scale known to
+        // reach the target.
+        let mut low = 0.0f32;
+        let mut high = 4.0f32;
+        for _ in 0..10 {
+            let mid = (low + high) / 2.0;
+            match coverage(&rescale(level, mid), reference) < target {
+                true => low = mid,
+                false => high = mid,
+            }
+        }
+        *level = Arc::new(rescale(level, high));
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+//! The BPX texture container writer.
+//!
+//! The layout is a fixed little endian header followed by the texel
+//! payload of each mip level:
+//!
+//! | Offset | Size | Field                          |
+//! |--------|------|--------------------------------|
+//! | 0      | 4    | Magic ("BPXT")                 |
+//! | 4      | 4    | Container version (1)          |
+//! | 8      | 4    | Width in texels                |
+//! | 12     | 4    | Height in texels               |
+//! | 16     | 1    | Texel format id                |
+//! | 17     | 1    | Mip level count                |
+//! | 18     | 1    | Payload encoding id            |
+//! | 19     | 1    | Layer count (0 for plain 2D)   |
+//! | 20     | 1    | Cubemap flag (1 for cubemaps)  |
+//! | 21     | 3    | Reserved (zero)                |
+//!
+//! Palette indexed (p8) files store their 256 entry RGBA8 palette (1024
+//! bytes) between the header and the first mip level; layered files store
+//! one palette per layer, before the payloads of that layer.
+//!
+//! Each mip level is a 8 bytes payload size followed by the payload.
+//! Layered files repeat the palette and mip levels once per layer;
+//! cubemaps store their six faces as layers in +X -X +Y -Y +Z -Z order.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::encode::Encoding;
+use crate::encode::Quality;
+use crate::texture::ArrayTexture;
+use crate::texture::CubeFace;
+use crate::texture::Cubemap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texture;
+
+/// Magic bytes identifying a BPX texture file.
+const MAGIC: [u8; 4] = *b"BPXT";
+
+/// Version of the container layout written by this module.
+const VERSION: u32 = 1;
+
+/// Returns the id of a texel format as stored in the container header.
+fn format_id(format: Format) -> u8 {
+    match format {
+        Format::L8 => 0,
+        Format::F32 => 1,
+        Format::RGBA8 => 2,
+        Format::RGBAF32 => 3,
+        Format::R16 => 4,
+        Format::RGBA16 => 5,
+        Format::RGBA16F => 6,
+        Format::RG8 => 7,
+        Format::RG16 => 8,
+        Format::RGBA8Srgb => 9,
+        Format::RGB565 => 10,
+        Format::RGBA4444 => 11,
+        Format::RGB5A1 => 12,
+        Format::P8 => 13,
+    }
+}
+
+/// Returns the id of a payload encoding as stored in the container header.
+fn encoding_id(encoding: Encoding) -> u8 {
+    match encoding {
+        Encoding::Raw => 0,
+        Encoding::Bc1 => 1,
+        Encoding::Bc3 => 2,
+        Encoding::Bc4 => 3,
+        Encoding::Bc5 => 4,
+        Encoding::Bc6h => 5,
+        Encoding::Bc7 => 6,
+        Encoding::Astc4x4 => 7,
+        Encoding::Astc5x5 => 8,
+        Encoding::Astc6x6 => 9,
+        Encoding::Astc8x8 => 10,
+        Encoding::Etc2Rgb => 11,
+        Encoding::Etc2Rgba => 12,
+        Encoding::EacR11 => 13,
+        Encoding::EacRg11 => 14,
+    }
+}
+
+/// Writes an encoded texture as a BPX texture file at the given path.
+///
+/// `mips` is the mip chain of the texture, base level first; a single
+/// element writes a texture without mips. The payload is encoded and
+/// written block by block instead of being assembled in memory first; its
+/// size is known ahead through [encoded_size](crate::encode::encoded_size).
+pub fn write_bpx(
+    path: &Path,
+    mips: &[Arc<OutputTexture>],
+    encoding: Encoding,
+    quality: Quality,
+) -> std::io::Result<()> {
+    write_layers(path, &[mips], encoding, quality, false)
+}
+
+/// Writes an encoded texture array as a layered BPX texture file at the
+/// given path, like [write_bpx].
+pub fn write_bpx_array(
+    path: &Path,
+    array: &ArrayTexture,
+    encoding: Encoding,
+    quality: Quality,
+) -> std::io::Result<()> {
+    let layers: Vec<&[Arc<OutputTexture>]> = (0..array.len())
+        .map(|index| array.mip_chain(index).unwrap())
+        .collect();
+    write_layers(path, &layers, encoding, quality, false)
+}
+
+/// Writes an encoded cubemap as a BPX texture file at the given path, its
+/// six faces stored as layers with the cubemap flag set.
+pub fn write_bpx_cubemap(
+    path: &Path,
+    cubemap: &Cubemap,
+    encoding: Encoding,
+    quality: Quality,
+) -> std::io::Result<()> {
+    let faces: Vec<&[Arc<OutputTexture>]> = CubeFace::ALL
+        .iter()
+        .map(|face| cubemap.mip_chain(*face))
+        .collect();
+    write_layers(path, &faces, encoding, quality, true)
+}
+
+fn write_layers(
+    path: &Path,
+    layers: &[&[Arc<OutputTexture>]],
+    encoding: Encoding,
+    quality: Quality,
+    cube: bool,
+) -> std::io::Result<()> {
+    if layers.len() > 255 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "BPX holds at most 255 layers",
+        ));
+    }
+    let first = &layers[0][0];
+    // Plain 2D files keep a zero layer count so they stay byte identical
+    // with files written before layers existed.
+    let layer_count = match layers.len() {
+        1 => 0,
+        n => n as u8,
+    };
+    let mips = layers[0].len() as u8;
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&first.width().to_le_bytes())?;
+    writer.write_all(&first.height().to_le_bytes())?;
+    writer.write_all(&[format_id(first.format()), mips, encoding_id(encoding), layer_count])?;
+    writer.write_all(&[cube as u8, 0, 0, 0])?;
+    for chain in layers {
+        if first.format() == Format::P8 {
+            for entry in chain[0].palette() {
+                writer.write_all(entry)?;
+            }
+        }
+        for texture in *chain {
+            writer.write_all(&crate::encode::encoded_size(texture, encoding).to_le_bytes())?;
+            crate::encode::encode_into(texture, encoding, quality, &mut writer)?;
+        }
+    }
+    writer.flush()
+}
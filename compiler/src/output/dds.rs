@@ -0,0 +1,202 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+//! The DDS container writer.
+//!
+//! Writes a DX10 style DDS file (legacy header plus DXGI extension) so
+//! Windows centric pipelines can inspect texturec output with existing
+//! viewers. Uncompressed and BC encoded payloads are supported; ASTC and
+//! ETC2 payloads have no portable DDS representation and are rejected.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::encode::Encoding;
+use crate::encode::Quality;
+use crate::texture::ArrayTexture;
+use crate::texture::CubeFace;
+use crate::texture::Cubemap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texture;
+
+/// Returns the DXGI format of an encoded payload, if DDS can hold it.
+fn dxgi_format(format: Format, encoding: Encoding) -> Option<u32> {
+    match encoding {
+        Encoding::Raw => Some(match format {
+            Format::L8 => 61,     // DXGI_FORMAT_R8_UNORM
+            Format::R16 => 56,    // DXGI_FORMAT_R16_UNORM
+            Format::RG8 => 49,    // DXGI_FORMAT_R8G8_UNORM
+            Format::RG16 => 35,   // DXGI_FORMAT_R16G16_UNORM
+            Format::F32 => 41,    // DXGI_FORMAT_R32_FLOAT
+            Format::RGBA8 => 28,     // DXGI_FORMAT_R8G8B8A8_UNORM
+            Format::RGBA8Srgb => 29, // DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+            Format::RGBA16 => 11,  // DXGI_FORMAT_R16G16B16A16_UNORM
+            Format::RGBA16F => 10, // DXGI_FORMAT_R16G16B16A16_FLOAT
+            Format::RGBAF32 => 2, // DXGI_FORMAT_R32G32B32A32_FLOAT
+            // The DXGI packed 16 bits layouts do not match the channel
+            // order this compiler renders.
+            Format::RGB565 | Format::RGBA4444 | Format::RGB5A1 => return None,
+            // The DX10 header path has no palette indexed formats.
+            Format::P8 => return None,
+        }),
+        Encoding::Bc1 => Some(71),  // DXGI_FORMAT_BC1_UNORM
+        Encoding::Bc3 => Some(77),  // DXGI_FORMAT_BC3_UNORM
+        Encoding::Bc4 => Some(80),  // DXGI_FORMAT_BC4_UNORM
+        Encoding::Bc5 => Some(83),  // DXGI_FORMAT_BC5_UNORM
+        Encoding::Bc6h => Some(95), // DXGI_FORMAT_BC6H_UF16
+        Encoding::Bc7 => Some(98),  // DXGI_FORMAT_BC7_UNORM
+        _ => None,
+    }
+}
+
+/// Writes an encoded texture as a DDS file at the given path.
+///
+/// `mips` is the mip chain of the texture, base level first; a single
+/// element writes a texture without mips. The payload is encoded and
+/// written block by block instead of being assembled in memory first.
+pub fn write_dds(
+    path: &Path,
+    mips: &[Arc<OutputTexture>],
+    encoding: Encoding,
+    quality: Quality,
+) -> std::io::Result<()> {
+    write_layers(path, &[mips], encoding, quality, false)
+}
+
+/// Writes an encoded texture array as a DDS file at the given path, using
+/// the arraySize field of the DX10 extension.
+pub fn write_dds_array(
+    path: &Path,
+    array: &ArrayTexture,
+    encoding: Encoding,
+    quality: Quality,
+) -> std::io::Result<()> {
+    let layers: Vec<&[Arc<OutputTexture>]> = (0..array.len())
+        .map(|index| array.mip_chain(index).unwrap())
+        .collect();
+    write_layers(path, &layers, encoding, quality, false)
+}
+
+/// Writes an encoded cubemap as a DDS file at the given path, using the
+/// cubemap caps and the TEXTURECUBE resource flag of the DX10 extension.
+pub fn write_dds_cubemap(
+    path: &Path,
+    cubemap: &Cubemap,
+    encoding: Encoding,
+    quality: Quality,
+) -> std::io::Result<()> {
+    let faces: Vec<&[Arc<OutputTexture>]> = CubeFace::ALL
+        .iter()
+        .map(|face| cubemap.mip_chain(*face))
+        .collect();
+    write_layers(path, &faces, encoding, quality, true)
+}
+
+fn write_layers(
+    path: &Path,
+    layers: &[&[Arc<OutputTexture>]],
+    encoding: Encoding,
+    quality: Quality,
+    cube: bool,
+) -> std::io::Result<()> {
+    let texture = &layers[0][0];
+    let mips = layers[0].len() as u32;
+    let dxgi = dxgi_format(texture.format(), encoding).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("DDS cannot hold a {} payload", encoding),
+        )
+    })?;
+    // DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT |
+    // DDSD_MIPMAPCOUNT, plus pitch or linear size depending on encoding.
+    let mut flags = 0x2_1007u32;
+    let pitch_or_linear = if encoding == Encoding::Raw {
+        flags |= 0x8; // DDSD_PITCH
+        texture.width() * texture.format().texel_size() as u32
+    } else {
+        flags |= 0x8_0000; // DDSD_LINEARSIZE
+        crate::encode::encoded_size(texture, encoding) as u32
+    };
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(b"DDS ")?;
+    writer.write_all(&124u32.to_le_bytes())?; // dwSize
+    writer.write_all(&flags.to_le_bytes())?;
+    writer.write_all(&texture.height().to_le_bytes())?;
+    writer.write_all(&texture.width().to_le_bytes())?;
+    writer.write_all(&pitch_or_linear.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?; // dwDepth
+    writer.write_all(&mips.to_le_bytes())?; // dwMipMapCount
+    writer.write_all(&[0u8; 44])?; // dwReserved1
+    writer.write_all(&32u32.to_le_bytes())?; // ddspf.dwSize
+    writer.write_all(&0x4u32.to_le_bytes())?; // DDPF_FOURCC
+    writer.write_all(b"DX10")?;
+    writer.write_all(&[0u8; 20])?; // masks
+    let mut caps = match cube {
+        // DDSCAPS_COMPLEX | DDSCAPS_TEXTURE
+        true => 0x1008u32,
+        false => 0x1000,
+    };
+    if mips > 1 {
+        // DDSCAPS_COMPLEX | DDSCAPS_MIPMAP
+        caps |= 0x40_0008;
+    }
+    writer.write_all(&caps.to_le_bytes())?;
+    let caps2 = match cube {
+        // DDSCAPS2_CUBEMAP plus all six face bits.
+        true => 0xFE00u32,
+        false => 0,
+    };
+    writer.write_all(&caps2.to_le_bytes())?;
+    writer.write_all(&[0u8; 12])?; // dwCaps3..4, dwReserved2
+    writer.write_all(&dxgi.to_le_bytes())?;
+    writer.write_all(&3u32.to_le_bytes())?; // D3D10_RESOURCE_DIMENSION_TEXTURE2D
+    let misc = match cube {
+        // D3D10_RESOURCE_MISC_TEXTURECUBE
+        true => 0x4u32,
+        false => 0,
+    };
+    writer.write_all(&misc.to_le_bytes())?;
+    // For cubemaps arraySize counts cubes, not faces.
+    let array_size = match cube {
+        true => 1u32,
+        false => layers.len() as u32,
+    };
+    writer.write_all(&array_size.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?; // miscFlags2
+    for chain in layers {
+        for texture in *chain {
+            crate::encode::encode_into(texture, encoding, quality, &mut writer)?;
+        }
+    }
+    writer.flush()
+}
@@ -0,0 +1,272 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+//! The KTX2 container writer.
+//!
+//! Writes a standard KTX2 file (identifier, header, level index, a basic
+//! data format descriptor and the level payloads) so texturec output can
+//! be validated and viewed by standard Khronos tooling. Supercompression
+//! uses the ZLIB scheme when requested.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::encode::Encoding;
+use crate::encode::Quality;
+use crate::texture::ArrayTexture;
+use crate::texture::CubeFace;
+use crate::texture::Cubemap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texture;
+
+/// The 12 bytes KTX2 file identifier.
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// The ZLIB supercompression scheme id.
+const SUPERCOMPRESSION_ZLIB: u32 = 3;
+
+/// Returns the VkFormat of an encoded payload, if Vulkan has one for it.
+fn vk_format(format: Format, encoding: Encoding) -> Option<u32> {
+    Some(match encoding {
+        Encoding::Raw => match format {
+            Format::L8 => 9,        // VK_FORMAT_R8_UNORM
+            Format::R16 => 70,      // VK_FORMAT_R16_UNORM
+            Format::RG8 => 16,      // VK_FORMAT_R8G8_UNORM
+            Format::RG16 => 77,     // VK_FORMAT_R16G16_UNORM
+            Format::F32 => 100,     // VK_FORMAT_R32_SFLOAT
+            Format::RGBA8 => 37,     // VK_FORMAT_R8G8B8A8_UNORM
+            Format::RGBA8Srgb => 43, // VK_FORMAT_R8G8B8A8_SRGB
+            Format::RGBA16 => 91,   // VK_FORMAT_R16G16B16A16_UNORM
+            Format::RGBA16F => 97,  // VK_FORMAT_R16G16B16A16_SFLOAT
+            Format::RGBAF32 => 109, // VK_FORMAT_R32G32B32A32_SFLOAT
+            Format::RGB565 => 4,    // VK_FORMAT_R5G6B5_UNORM_PACK16
+            Format::RGBA4444 => 2,  // VK_FORMAT_R4G4B4A4_UNORM_PACK16
+            Format::RGB5A1 => 6,    // VK_FORMAT_R5G5B5A1_UNORM_PACK16
+            // Vulkan dropped palette indexed formats.
+            Format::P8 => return None,
+        },
+        Encoding::Bc1 => 131,      // VK_FORMAT_BC1_RGB_UNORM_BLOCK
+        Encoding::Bc3 => 137,      // VK_FORMAT_BC3_UNORM_BLOCK
+        Encoding::Bc4 => 139,      // VK_FORMAT_BC4_UNORM_BLOCK
+        Encoding::Bc5 => 141,      // VK_FORMAT_BC5_UNORM_BLOCK
+        Encoding::Bc6h => 143,     // VK_FORMAT_BC6H_UFLOAT_BLOCK
+        Encoding::Bc7 => 145,      // VK_FORMAT_BC7_UNORM_BLOCK
+        Encoding::Astc4x4 => 157,  // VK_FORMAT_ASTC_4x4_UNORM_BLOCK
+        Encoding::Astc5x5 => 161,  // VK_FORMAT_ASTC_5x5_UNORM_BLOCK
+        Encoding::Astc6x6 => 165,  // VK_FORMAT_ASTC_6x6_UNORM_BLOCK
+        Encoding::Astc8x8 => 171,  // VK_FORMAT_ASTC_8x8_UNORM_BLOCK
+        Encoding::Etc2Rgb => 147,  // VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK
+        Encoding::Etc2Rgba => 151, // VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK
+        Encoding::EacR11 => 153,   // VK_FORMAT_EAC_R11_UNORM_BLOCK
+        Encoding::EacRg11 => 155,  // VK_FORMAT_EAC_R11G11_UNORM_BLOCK
+    })
+}
+
+/// Returns the size in bytes of a single component of an unencoded texel.
+fn type_size(format: Format, encoding: Encoding) -> u32 {
+    if encoding != Encoding::Raw {
+        return 1;
+    }
+    match format {
+        Format::L8 | Format::RG8 | Format::RGBA8 | Format::RGBA8Srgb | Format::P8 => 1,
+        Format::R16 | Format::RG16 | Format::RGBA16 | Format::RGBA16F => 2,
+        Format::RGB565 | Format::RGBA4444 | Format::RGB5A1 => 2,
+        Format::F32 | Format::RGBAF32 => 4,
+    }
+}
+
+/// Writes an encoded texture as a KTX2 file at the given path.
+///
+/// `mips` is the mip chain of the texture, base level first; a single
+/// element writes a texture without mips. Plain payloads are encoded and
+/// written block by block instead of being assembled in memory first.
+/// Supercompression needs the compressed length in the level index before
+/// the data, so it still buffers each level's payload.
+pub fn write_ktx2(
+    path: &Path,
+    mips: &[Arc<OutputTexture>],
+    encoding: Encoding,
+    quality: Quality,
+    supercompress: bool,
+) -> std::io::Result<()> {
+    write_layers(path, &[mips], encoding, quality, supercompress, false)
+}
+
+/// Writes an encoded texture array as a KTX2 file at the given path, using
+/// the layerCount header field with the layers concatenated per level.
+pub fn write_ktx2_array(
+    path: &Path,
+    array: &ArrayTexture,
+    encoding: Encoding,
+    quality: Quality,
+    supercompress: bool,
+) -> std::io::Result<()> {
+    let layers: Vec<&[Arc<OutputTexture>]> = (0..array.len())
+        .map(|index| array.mip_chain(index).unwrap())
+        .collect();
+    write_layers(path, &layers, encoding, quality, supercompress, false)
+}
+
+/// Writes an encoded cubemap as a KTX2 file at the given path, using the
+/// faceCount header field with the faces concatenated per level.
+pub fn write_ktx2_cubemap(
+    path: &Path,
+    cubemap: &Cubemap,
+    encoding: Encoding,
+    quality: Quality,
+    supercompress: bool,
+) -> std::io::Result<()> {
+    let faces: Vec<&[Arc<OutputTexture>]> = CubeFace::ALL
+        .iter()
+        .map(|face| cubemap.mip_chain(*face))
+        .collect();
+    write_layers(path, &faces, encoding, quality, supercompress, true)
+}
+
+fn write_layers(
+    path: &Path,
+    layers: &[&[Arc<OutputTexture>]],
+    encoding: Encoding,
+    quality: Quality,
+    supercompress: bool,
+    cube: bool,
+) -> std::io::Result<()> {
+    let texture = &layers[0][0];
+    let levels = layers[0].len();
+    let vk_format = vk_format(texture.format(), encoding).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("KTX2 cannot hold a {} payload", texture.format()),
+        )
+    })?;
+    // One level holds every layer and face, concatenated.
+    let uncompressed: Vec<u64> = (0..levels)
+        .map(|level| {
+            crate::encode::encoded_size(&layers[0][level], encoding) * layers.len() as u64
+        })
+        .collect();
+    // Non BasisLZ schemes supercompress each mip level separately.
+    let compressed = if supercompress {
+        let mut blobs = Vec::with_capacity(levels);
+        for level in 0..levels {
+            let mut payload = Vec::with_capacity(uncompressed[level] as usize);
+            for chain in layers {
+                crate::encode::encode_into(&chain[level], encoding, quality, &mut payload)?;
+            }
+            blobs.push(miniz_oxide::deflate::compress_to_vec_zlib(&payload, 6));
+        }
+        Some(blobs)
+    } else {
+        None
+    };
+    let scheme = if compressed.is_some() {
+        SUPERCOMPRESSION_ZLIB
+    } else {
+        0
+    };
+    let stored: Vec<u64> = (0..levels)
+        .map(|level| match &compressed {
+            Some(blobs) => blobs[level].len() as u64,
+            None => uncompressed[level],
+        })
+        .collect();
+    // Identifier (12) + header (36) + index (32) + one level index entry
+    // (24) per level, then the basic data format descriptor and the 16
+    // bytes aligned levels.
+    let dfd_offset = (80 + 24 * levels) as u32;
+    let dfd_length = 28u32;
+    // The specification stores levels smallest first; assign offsets
+    // accordingly while the index stays in base first order.
+    let mut offsets = vec![0u64; levels];
+    let mut cursor = ((dfd_offset + dfd_length) as u64).next_multiple_of(16);
+    for level in (0..levels).rev() {
+        offsets[level] = cursor;
+        cursor = (cursor + stored[level]).next_multiple_of(16);
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&IDENTIFIER)?;
+    writer.write_all(&vk_format.to_le_bytes())?;
+    writer.write_all(&type_size(texture.format(), encoding).to_le_bytes())?;
+    writer.write_all(&texture.width().to_le_bytes())?;
+    writer.write_all(&texture.height().to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?; // pixelDepth
+    // Zero means "not an array" per the specification, so single layer
+    // files do not become one element arrays. Cubemap faces count through
+    // faceCount, not layerCount.
+    let layer_count = match (cube, layers.len()) {
+        (true, _) | (false, 1) => 0u32,
+        (false, n) => n as u32,
+    };
+    writer.write_all(&layer_count.to_le_bytes())?;
+    let face_count = match cube {
+        true => 6u32,
+        false => 1,
+    };
+    writer.write_all(&face_count.to_le_bytes())?;
+    writer.write_all(&(levels as u32).to_le_bytes())?; // levelCount
+    writer.write_all(&scheme.to_le_bytes())?;
+    writer.write_all(&dfd_offset.to_le_bytes())?;
+    writer.write_all(&dfd_length.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?; // kvdByteOffset
+    writer.write_all(&0u32.to_le_bytes())?; // kvdByteLength
+    writer.write_all(&0u64.to_le_bytes())?; // sgdByteOffset
+    writer.write_all(&0u64.to_le_bytes())?; // sgdByteLength
+    for level in 0..levels {
+        writer.write_all(&offsets[level].to_le_bytes())?;
+        writer.write_all(&stored[level].to_le_bytes())?;
+        writer.write_all(&uncompressed[level].to_le_bytes())?;
+    }
+    // Basic data format descriptor with no sample information.
+    writer.write_all(&dfd_length.to_le_bytes())?; // dfdTotalSize
+    writer.write_all(&0u32.to_le_bytes())?; // vendorId / descriptorType
+    writer.write_all(&(2u32 << 16 | 24).to_le_bytes())?; // version / blockSize
+    writer.write_all(&[0u8; 16])?;
+    let mut position = (dfd_offset + dfd_length) as u64;
+    for level in (0..levels).rev() {
+        for _ in position..offsets[level] {
+            writer.write_all(&[0u8])?;
+        }
+        match &compressed {
+            Some(blobs) => writer.write_all(&blobs[level])?,
+            None => {
+                for chain in layers {
+                    crate::encode::encode_into(&chain[level], encoding, quality, &mut writer)?;
+                }
+            }
+        }
+        position = offsets[level] + stored[level];
+    }
+    writer.flush()
+}
@@ -0,0 +1,109 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Output container writers.
+
+mod bpx;
+mod dds;
+mod ktx2;
+
+pub use bpx::write_bpx;
+pub use bpx::write_bpx_array;
+pub use bpx::write_bpx_cubemap;
+pub use dds::write_dds;
+pub use dds::write_dds_array;
+pub use dds::write_dds_cubemap;
+pub use ktx2::write_ktx2;
+pub use ktx2::write_ktx2_array;
+pub use ktx2::write_ktx2_cubemap;
+
+use std::path::Path;
+
+/// The container format the output texture is written in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Container {
+    /// The BPX texture container.
+    Bpx,
+
+    /// The Khronos KTX2 container.
+    Ktx2,
+
+    /// The DirectDraw Surface container.
+    Dds,
+}
+
+impl Container {
+    /// Returns the name of this container as used by the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Container::Bpx => "bpx",
+            Container::Ktx2 => "ktx2",
+            Container::Dds => "dds",
+        }
+    }
+
+    /// Parses a container from its command line name.
+    pub fn from_name(name: &str) -> Option<Container> {
+        match name {
+            "bpx" => Some(Container::Bpx),
+            "ktx2" => Some(Container::Ktx2),
+            "dds" => Some(Container::Dds),
+            _ => None,
+        }
+    }
+
+    /// Picks the container matching the extension of an output path,
+    /// defaulting to BPX.
+    pub fn from_path(path: &Path) -> Container {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ktx2") => Container::Ktx2,
+            Some("dds") => Container::Dds,
+            _ => Container::Bpx,
+        }
+    }
+}
+
+impl std::fmt::Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl serde::Serialize for Container {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Container {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Container, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Container::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format_args!("unknown container format '{}'", name)))
+    }
+}
@@ -0,0 +1,522 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Filter parameter storage and parsing.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::import::ImportError;
+use crate::texture::ImageTexture;
+use crate::texture::Texture;
+
+/// A single filter parameter.
+#[derive(Clone)]
+pub enum Parameter {
+    /// A floating point parameter.
+    Float(f64),
+
+    /// An integer parameter.
+    Int(i64),
+
+    /// A boolean parameter.
+    Bool(bool),
+
+    /// A 2 components vector parameter.
+    Vector2([f64; 2]),
+
+    /// A 3 components vector parameter.
+    Vector3([f64; 3]),
+
+    /// A 4 components vector parameter.
+    Vector4([f64; 4]),
+
+    /// A normalized RGBA color parameter.
+    Color([f64; 4]),
+
+    /// A string parameter.
+    String(String),
+
+    /// A texture parameter.
+    ///
+    /// Any [Texture] fits, so the output of a previous compilation can
+    /// feed the next one without a round trip through an image file.
+    Texture(Arc<dyn Texture>),
+}
+
+impl Parameter {
+    /// Returns this parameter as a float, None if it is not a number.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Parameter::Float(v) => Some(*v),
+            Parameter::Int(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+
+    /// Returns this parameter as an integer, None if it is not an integer.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Parameter::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns this parameter as a boolean, None if it is not a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Parameter::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns this parameter as a 2 components vector, None if it is not one.
+    pub fn as_vector2(&self) -> Option<[f64; 2]> {
+        match self {
+            Parameter::Vector2(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns this parameter as a 3 components vector, None if it is not one.
+    pub fn as_vector3(&self) -> Option<[f64; 3]> {
+        match self {
+            Parameter::Vector3(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns this parameter as a 4 components vector, None if it is not
+    /// one. Colors convert, so filters reading RGBA vectors accept them.
+    pub fn as_vector4(&self) -> Option<[f64; 4]> {
+        match self {
+            Parameter::Vector4(v) => Some(*v),
+            Parameter::Color(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns this parameter as a normalized RGBA color, None if it is
+    /// neither a color nor a 4 components vector.
+    pub fn as_color(&self) -> Option<[f64; 4]> {
+        match self {
+            Parameter::Color(v) => Some(*v),
+            Parameter::Vector4(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns this parameter as a string, None if it is not a string.
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            Parameter::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns this parameter as a texture, None if it is not a texture.
+    pub fn as_texture(&self) -> Option<&Arc<dyn Texture>> {
+        match self {
+            Parameter::Texture(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Errors raised when parsing parameters.
+#[derive(Debug)]
+pub enum ParameterError {
+    /// A parameter value is not valid unicode (parameter name).
+    InvalidUnicode(String),
+
+    /// A texture parameter could not be decoded (parameter name, importer error).
+    InvalidTexture(String, ImportError),
+
+    /// An explicitly typed value does not parse as its type (parameter
+    /// name, type name).
+    InvalidValue(String, &'static str),
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParameterError::InvalidUnicode(name) => {
+                write!(f, "invalid unicode in value of parameter '{}'", name)
+            }
+            ParameterError::InvalidTexture(name, e) => {
+                write!(f, "unable to load texture parameter '{}': {}", name, e)
+            }
+            ParameterError::InvalidValue(name, ty) => {
+                write!(f, "value of parameter '{}' is not a valid {}", name, ty)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParameterError {}
+
+fn parse_vector(value: &str) -> Option<Parameter> {
+    let components: Option<Vec<f64>> = value.split(',').map(|v| v.trim().parse().ok()).collect();
+    let components = components?;
+    match components.len() {
+        2 => Some(Parameter::Vector2([components[0], components[1]])),
+        3 => Some(Parameter::Vector3([components[0], components[1], components[2]])),
+        4 => Some(Parameter::Vector4([
+            components[0],
+            components[1],
+            components[2],
+            components[3],
+        ])),
+        _ => None,
+    }
+}
+
+/// Returns the RGBA value of a named color, None for unknown names.
+fn named_color(name: &str) -> Option<[f64; 4]> {
+    match name {
+        "black" => Some([0.0, 0.0, 0.0, 1.0]),
+        "white" => Some([1.0, 1.0, 1.0, 1.0]),
+        "red" => Some([1.0, 0.0, 0.0, 1.0]),
+        "green" => Some([0.0, 1.0, 0.0, 1.0]),
+        "blue" => Some([0.0, 0.0, 1.0, 1.0]),
+        "yellow" => Some([1.0, 1.0, 0.0, 1.0]),
+        "cyan" => Some([0.0, 1.0, 1.0, 1.0]),
+        "magenta" => Some([1.0, 0.0, 1.0, 1.0]),
+        "grey" | "gray" => Some([0.5, 0.5, 0.5, 1.0]),
+        "orange" => Some([1.0, 0.5, 0.0, 1.0]),
+        "transparent" => Some([0.0, 0.0, 0.0, 0.0]),
+        _ => None,
+    }
+}
+
+/// Parses a `#RRGGBB`/`#RRGGBBAA` hex color or a named color into
+/// normalized RGBA, opaque when no alpha digits are given.
+pub(crate) fn parse_color(value: &str) -> Option<[f64; 4]> {
+    let hex = match value.strip_prefix('#') {
+        Some(v) => v,
+        None => return named_color(value),
+    };
+    if hex.len() != 6 && hex.len() != 8 {
+        return None;
+    }
+    let mut channels = [0.0, 0.0, 0.0, 1.0];
+    for (channel, chunk) in channels.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let chunk = std::str::from_utf8(chunk).ok()?;
+        *channel = u8::from_str_radix(chunk, 16).ok()? as f64 / 255.0;
+    }
+    Some(channels)
+}
+
+/// Parses an explicitly typed `prefix:value` parameter, None when the
+/// prefix is not a known type name so values like Windows paths fall back
+/// to sniffing.
+fn parse_prefixed(name: &str, prefix: &str, value: &str) -> Option<Result<Parameter, ParameterError>> {
+    let invalid = |ty| ParameterError::InvalidValue(name.into(), ty);
+    let parsed = match prefix {
+        "int" => value.parse().map(Parameter::Int).map_err(|_| invalid("integer")),
+        "float" => value.parse().map(Parameter::Float).map_err(|_| invalid("float")),
+        "bool" => value.parse().map(Parameter::Bool).map_err(|_| invalid("boolean")),
+        "str" => Ok(Parameter::String(value.into())),
+        "vec2" => match parse_vector(value) {
+            Some(Parameter::Vector2(v)) => Ok(Parameter::Vector2(v)),
+            _ => Err(invalid("2 components vector")),
+        },
+        "vec3" => match parse_vector(value) {
+            Some(Parameter::Vector3(v)) => Ok(Parameter::Vector3(v)),
+            _ => Err(invalid("3 components vector")),
+        },
+        "vec4" => match parse_vector(value) {
+            Some(Parameter::Vector4(v)) => Ok(Parameter::Vector4(v)),
+            _ => Err(invalid("4 components vector")),
+        },
+        "color" => parse_color(value).map(Parameter::Color).ok_or_else(|| invalid("color")),
+        "tex" => crate::import::load_parameter(Path::new(value))
+            .map(Parameter::Texture)
+            .map_err(|e| ParameterError::InvalidTexture(name.into(), e)),
+        _ => return None,
+    };
+    Some(parsed)
+}
+
+fn parse_value(name: &str, value: &OsStr) -> Result<Parameter, ParameterError> {
+    if let Some((prefix, rest)) = value.to_str().and_then(|v| v.split_once(':')) {
+        if let Some(parsed) = parse_prefixed(name, prefix, rest) {
+            return parsed;
+        }
+    }
+    let path = Path::new(value);
+    // The importable extensions mirror the dispatch of
+    // [load_parameter](crate::import::load_parameter): the containers the
+    // importers decode themselves, which the image crate does not know,
+    // plus everything the image crate recognizes.
+    let importable = matches!(
+        path.extension().and_then(|v| v.to_str()),
+        Some("bpx") | Some("dds") | Some("ktx") | Some("ktx2") | Some("tif") | Some("tiff")
+    );
+    if (importable || image::ImageFormat::from_path(path).is_ok()) && path.is_file() {
+        let texture = crate::import::load_parameter(path)
+            .map_err(|e| ParameterError::InvalidTexture(name.into(), e))?;
+        return Ok(Parameter::Texture(texture));
+    }
+    let value = value
+        .to_str()
+        .ok_or_else(|| ParameterError::InvalidUnicode(name.into()))?;
+    if let Ok(v) = value.parse() {
+        return Ok(Parameter::Int(v));
+    }
+    if let Ok(v) = value.parse() {
+        return Ok(Parameter::Float(v));
+    }
+    if let Ok(v) = value.parse() {
+        return Ok(Parameter::Bool(v));
+    }
+    if let Some(v) = parse_vector(value) {
+        return Ok(v);
+    }
+    if value.starts_with('#') {
+        // A leading '#' can only mean a hex color, so a malformed one is
+        // reported instead of silently becoming a string.
+        let color = parse_color(value).ok_or_else(|| ParameterError::InvalidValue(name.into(), "color"))?;
+        return Ok(Parameter::Color(color));
+    }
+    Ok(Parameter::String(value.into()))
+}
+
+/// A map of named filter parameters.
+#[derive(Clone, Default)]
+pub struct ParameterMap {
+    params: HashMap<String, Parameter>,
+}
+
+impl ParameterMap {
+    /// Creates an empty parameter map.
+    pub fn new() -> ParameterMap {
+        ParameterMap {
+            params: HashMap::new(),
+        }
+    }
+
+    /// Returns a builder assembling a map from typed values.
+    ///
+    /// Unlike [parse](ParameterMap::parse) no type sniffing happens, so a
+    /// string that happens to look like a number or a path stays a string.
+    pub fn builder() -> ParameterMapBuilder {
+        ParameterMapBuilder::default()
+    }
+
+    /// Parses a parameter map from (name, value) pairs.
+    ///
+    /// The type of each parameter is guessed from its value: paths to
+    /// existing files with an extension the importers decode (a known image
+    /// format, DDS, KTX/KTX2 or compiled BPX) load as textures, then
+    /// numbers, booleans and comma separated vectors are attempted, and
+    /// anything else is kept as a string.
+    ///
+    /// Sniffing can be overridden with an explicit type prefix: `int:5`,
+    /// `float:1`, `bool:true`, `str:1024`, `vec2:`/`vec3:`/`vec4:` ahead of
+    /// a comma separated vector, `color:` ahead of a hex or named color, or
+    /// `tex:` ahead of a path that must load as a texture instead of
+    /// silently staying a string. `#RRGGBB`/`#RRGGBBAA` values parse as
+    /// colors without a prefix.
+    pub fn parse<'a, I: IntoIterator<Item = (&'a str, &'a OsStr)>>(
+        pairs: I,
+    ) -> Result<ParameterMap, ParameterError> {
+        let mut params = HashMap::new();
+        for (name, value) in pairs {
+            params.insert(name.into(), parse_value(name, value)?);
+        }
+        Ok(ParameterMap { params })
+    }
+
+    /// Inserts a parameter, replacing any previous parameter with the same name.
+    pub fn insert(&mut self, name: String, param: Parameter) {
+        self.params.insert(name, param);
+    }
+
+    /// Inserts a texture parameter decoded from an arbitrary reader.
+    ///
+    /// Unlike [parse](ParameterMap::parse) this does not require the content
+    /// to live at a real path, so archives and virtual file systems can feed
+    /// source images and LUTs directly.
+    pub fn insert_texture<R: Read + Seek>(
+        &mut self,
+        name: String,
+        reader: R,
+        format: image::ImageFormat,
+    ) -> Result<(), ParameterError> {
+        let texture = ImageTexture::from_reader(reader, format)
+            .map_err(|e| ParameterError::InvalidTexture(name.clone(), e.into()))?;
+        self.params.insert(name, Parameter::Texture(Arc::new(texture)));
+        Ok(())
+    }
+
+    /// Inserts a texture parameter from an already decoded image, for hosts
+    /// that generate inputs procedurally instead of loading them from disk.
+    pub fn insert_image(&mut self, name: String, image: image::DynamicImage) {
+        let texture = ImageTexture::new(image);
+        self.params.insert(name, Parameter::Texture(Arc::new(texture)));
+    }
+
+    /// Inserts a texture parameter from a raw RGBA8 pixel buffer laid out
+    /// row by row, top to bottom.
+    ///
+    /// Fails when the buffer does not hold exactly `width * height * 4`
+    /// bytes.
+    pub fn insert_rgba(
+        &mut self,
+        name: String,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    ) -> Result<(), ParameterError> {
+        let image = image::RgbaImage::from_raw(width, height, data).ok_or_else(|| {
+            ParameterError::InvalidTexture(
+                name.clone(),
+                ImportError::Malformed("buffer size does not match the image dimensions"),
+            )
+        })?;
+        self.insert_image(name, image::DynamicImage::ImageRgba8(image));
+        Ok(())
+    }
+
+    /// Returns the parameter with the given name, None if it does not exist.
+    pub fn get(&self, name: &str) -> Option<&Parameter> {
+        self.params.get(name)
+    }
+
+    /// Returns true when this map holds no parameter.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// Returns a copy of this map with every parameter of `overrides`
+    /// replacing the same named parameter, if any.
+    pub fn merged(&self, overrides: &ParameterMap) -> ParameterMap {
+        let mut params = self.params.clone();
+        for (name, param) in &overrides.params {
+            params.insert(name.clone(), param.clone());
+        }
+        ParameterMap { params }
+    }
+
+    /// Iterates the parameters of this map in unspecified order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Parameter)> {
+        self.params.iter()
+    }
+}
+
+/// Builds a [ParameterMap] from typed values.
+///
+/// ```
+/// # use texturec_compiler::params::ParameterMap;
+/// let params = ParameterMap::builder()
+///     .float("sigma", 2.0)
+///     .string("mode", "perlin")
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct ParameterMapBuilder {
+    params: HashMap<String, Parameter>,
+}
+
+impl ParameterMapBuilder {
+    /// Adds a floating point parameter.
+    pub fn float(mut self, name: impl Into<String>, value: f64) -> ParameterMapBuilder {
+        self.params.insert(name.into(), Parameter::Float(value));
+        self
+    }
+
+    /// Adds an integer parameter.
+    pub fn int(mut self, name: impl Into<String>, value: i64) -> ParameterMapBuilder {
+        self.params.insert(name.into(), Parameter::Int(value));
+        self
+    }
+
+    /// Adds a boolean parameter.
+    pub fn bool(mut self, name: impl Into<String>, value: bool) -> ParameterMapBuilder {
+        self.params.insert(name.into(), Parameter::Bool(value));
+        self
+    }
+
+    /// Adds a 2 components vector parameter.
+    pub fn vector2(mut self, name: impl Into<String>, value: [f64; 2]) -> ParameterMapBuilder {
+        self.params.insert(name.into(), Parameter::Vector2(value));
+        self
+    }
+
+    /// Adds a 3 components vector parameter.
+    pub fn vector3(mut self, name: impl Into<String>, value: [f64; 3]) -> ParameterMapBuilder {
+        self.params.insert(name.into(), Parameter::Vector3(value));
+        self
+    }
+
+    /// Adds a 4 components vector parameter.
+    pub fn vector4(mut self, name: impl Into<String>, value: [f64; 4]) -> ParameterMapBuilder {
+        self.params.insert(name.into(), Parameter::Vector4(value));
+        self
+    }
+
+    /// Adds a string parameter.
+    pub fn string(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> ParameterMapBuilder {
+        self.params
+            .insert(name.into(), Parameter::String(value.into()));
+        self
+    }
+
+    /// Adds a texture parameter.
+    pub fn texture(
+        mut self,
+        name: impl Into<String>,
+        texture: Arc<dyn Texture>,
+    ) -> ParameterMapBuilder {
+        self.params.insert(name.into(), Parameter::Texture(texture));
+        self
+    }
+
+    /// Sets a normalized RGBA color parameter.
+    pub fn color(mut self, name: impl Into<String>, value: [f64; 4]) -> ParameterMapBuilder {
+        self.params.insert(name.into(), Parameter::Color(value));
+        self
+    }
+
+    /// Finishes the builder into a parameter map.
+    pub fn build(self) -> ParameterMap {
+        ParameterMap {
+            params: self.params,
+        }
+    }
+}
@@ -0,0 +1,940 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The render pipeline running filter passes over a pool of render
+//! target buffers.
+//!
+//! Passes form a graph rather than a plain chain: each pass can publish
+//! its output under a name, and later passes reference published buffers
+//! through their texture parameters, wiring masks, composites and packed
+//! channels without intermediate files.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use bp3d_threads::ScopedThreadManager;
+use bp3d_threads::ThreadPool;
+#[cfg(feature = "texel-tracing")]
+use tracing::instrument;
+use tracing::warn;
+
+use crate::filter::DynamicFilter;
+use crate::filter::DynamicFunction;
+use crate::filter::Filter;
+use crate::filter::FilterError;
+use crate::filter::FrameBuffer;
+use crate::filter::Function;
+use crate::filter::Viewport;
+use crate::params::ParameterMap;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::SwapChain;
+use crate::texture::Texel;
+use crate::texture::Texture;
+use crate::texture::TextureError;
+
+/// Statistics counters of the pass a pipeline is rendering, shared with
+/// its spans.
+///
+/// Every pipeline owns its counters, so pipelines running concurrently
+/// never clobber each other's progress or reports.
+#[derive(Default)]
+struct PassCounters {
+    /// Number of texels processed in the current pass.
+    processed: AtomicU64,
+
+    /// Nanoseconds render threads spent computing spans of the current
+    /// pass, summed across threads.
+    busy: AtomicU64,
+}
+
+/// A cloneable token used to cancel a running compilation from another thread.
+///
+/// Cancellation is checked at chunk boundaries, independently of the
+/// delegate mechanism; a cancelled run fails with a cancellation error.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Creates a new token with no pending cancellation.
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Requests cancellation of the run holding this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Errors raised while running a pipeline.
+#[derive(Debug)]
+pub enum PipelineError {
+    /// A filter could not be constructed.
+    Filter(FilterError),
+
+    /// A filter produced texels rejected by the render target in strict mode
+    /// (filter name, rejection).
+    Mismatch(String, TextureError),
+
+    /// The run was cancelled through its cancellation token.
+    Cancelled,
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineError::Filter(e) => write!(f, "filter error: {}", e),
+            PipelineError::Mismatch(name, e) => {
+                write!(f, "filter '{}' produced a rejected texel: {}", name, e)
+            }
+            PipelineError::Cancelled => f.write_str("the run was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl From<FilterError> for PipelineError {
+    fn from(e: FilterError) -> PipelineError {
+        PipelineError::Filter(e)
+    }
+}
+
+/// Progress statistics of a running pass, computed by the pipeline so
+/// front-ends do not have to duplicate the bookkeeping.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PassStats {
+    /// Number of texels processed so far.
+    pub processed: u64,
+
+    /// Total texel count of the pass.
+    pub total: u64,
+
+    /// Wall time elapsed since the pass started rendering.
+    pub elapsed: Duration,
+}
+
+impl PassStats {
+    /// Average number of texels processed per second since the pass
+    /// started, zero until any time has elapsed.
+    pub fn texels_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds <= 0.0 {
+            return 0.0;
+        }
+        self.processed as f64 / seconds
+    }
+
+    /// Estimated wall time left before the pass finishes, None until the
+    /// first texels land.
+    pub fn remaining(&self) -> Option<Duration> {
+        let rate = self.texels_per_second();
+        if rate <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            (self.total - self.processed) as f64 / rate,
+        ))
+    }
+}
+
+/// A structured warning collected while running a pipeline.
+///
+/// Diagnostics carry the pass, filter and texel position they refer to, so
+/// callers can fail builds or report issues programmatically instead of
+/// parsing log output; the [Display](fmt::Display) form is what the
+/// command line prints.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// Index of the pass that raised the diagnostic, None for issues not
+    /// tied to a pass.
+    pub pass: Option<usize>,
+
+    /// Name of the filter rendered by that pass.
+    pub filter: Option<String>,
+
+    /// Texel coordinates the diagnostic refers to, None for pass wide
+    /// issues.
+    pub position: Option<(u32, u32)>,
+
+    /// Human readable description of the issue.
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic not tied to any pass.
+    pub fn global(message: String) -> Diagnostic {
+        Diagnostic {
+            pass: None,
+            filter: None,
+            position: None,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let (Some(pass), Some(filter)) = (self.pass, &self.filter) {
+            write!(f, "pass {} ({}): ", pass + 1, filter)?;
+        }
+        if let Some((x, y)) = self.position {
+            write!(f, "at ({}, {}): ", x, y)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Delegate receiving progress notifications for a single pass.
+pub trait PassDelegate: Send + Sync {
+    /// Called for each processed texel with the progress statistics of the
+    /// pass.
+    fn on_progress(&self, stats: PassStats);
+
+    /// Called when the pass has fully rendered.
+    fn on_end(&self);
+}
+
+/// Delegate receiving pipeline wide notifications.
+pub trait PipelineDelegate: Send + Sync {
+    /// The type of per pass delegate spawned by this delegate.
+    type Pass: PassDelegate;
+
+    /// Called when a pass starts; returns the delegate tracking that pass.
+    fn on_start_pass(&self, index: usize, count: usize, name: &str) -> Self::Pass;
+}
+
+/// Timing and statistics report of a single rendered pass.
+pub struct PassReport {
+    /// Name of the filter rendered by the pass.
+    pub name: String,
+
+    /// Wall time spent rendering the pass.
+    pub duration: Duration,
+
+    /// Combined time render threads spent computing texel spans, summed
+    /// across threads.
+    pub busy: Duration,
+
+    /// Number of texels processed by the pass.
+    pub texels: u64,
+
+    /// Number of texels rejected by the render target during the pass.
+    ///
+    /// Always zero in strict mode, where the first rejection fails the pass.
+    pub mismatches: u64,
+}
+
+impl PassReport {
+    /// Average number of threads busy rendering during the pass.
+    ///
+    /// Values close to 1.0 mean the pass ran at serial speed; values close
+    /// to the thread count mean the executor kept every thread fed.
+    pub fn parallelism(&self) -> f64 {
+        let wall = self.duration.as_secs_f64();
+        if wall <= 0.0 {
+            return 0.0;
+        }
+        self.busy.as_secs_f64() / wall
+    }
+
+    /// Average number of texels rendered per second of wall time.
+    pub fn throughput(&self) -> f64 {
+        let wall = self.duration.as_secs_f64();
+        if wall <= 0.0 {
+            return 0.0;
+        }
+        self.texels as f64 / wall
+    }
+}
+
+/// Executes the texel spans of a pass on behalf of the pipeline.
+///
+/// Embedding applications can implement this to run texture compiles on
+/// their own job system instead of the built-in bp3d-threads pool.
+///
+/// Executors must be Send + Sync so a configured pipeline can be moved
+/// onto a background thread.
+pub trait Executor: Send + Sync {
+    /// Runs `task` for contiguous horizontal spans covering every texel of
+    /// a `width` by `height` pass and feeds each rendered span to `consume`
+    /// on the calling thread.
+    fn dispatch(
+        &self,
+        width: u32,
+        height: u32,
+        task: &(dyn Fn(u32, Range<u32>) -> Vec<Texel> + Sync),
+        consume: &mut dyn FnMut(u32, Range<u32>, Vec<Texel>),
+    );
+}
+
+/// The default executor creating a bp3d-threads pool per pass.
+pub struct ThreadPoolExecutor {
+    n_threads: usize,
+}
+
+impl ThreadPoolExecutor {
+    /// Creates a new executor rendering with the given number of threads.
+    pub fn new(n_threads: usize) -> ThreadPoolExecutor {
+        ThreadPoolExecutor { n_threads }
+    }
+}
+
+impl Executor for ThreadPoolExecutor {
+    fn dispatch(
+        &self,
+        width: u32,
+        height: u32,
+        task: &(dyn Fn(u32, Range<u32>) -> Vec<Texel> + Sync),
+        consume: &mut dyn FnMut(u32, Range<u32>, Vec<Texel>),
+    ) {
+        crossbeam::thread::scope(|scope| {
+            let manager = ScopedThreadManager::new(scope);
+            let mut pool: ThreadPool<ScopedThreadManager, (u32, Vec<Texel>)> =
+                ThreadPool::new(self.n_threads);
+            // One span per row: queueing a closure per texel costs more
+            // than rendering it on large targets.
+            for y in 0..height {
+                pool.send(&manager, move |_| (y, task(y, 0..width)));
+            }
+            for res in pool.reduce() {
+                let (y, texels) = res.expect("A render thread has panicked");
+                consume(y, 0..width, texels);
+            }
+        })
+        .expect("The render scope has panicked");
+    }
+}
+
+/// An executor rendering on a rayon work-stealing pool.
+///
+/// Work-stealing keeps many-core machines saturated when row costs are
+/// uneven, which the fixed queues of the default pool do not. Only
+/// available with the `rayon` feature.
+#[cfg(feature = "rayon")]
+pub struct RayonExecutor {
+    pool: rayon::ThreadPool,
+}
+
+#[cfg(feature = "rayon")]
+impl RayonExecutor {
+    /// Creates a new executor rendering with the given number of threads;
+    /// 0 uses one thread per core.
+    pub fn new(n_threads: usize) -> RayonExecutor {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n_threads)
+            .build()
+            .expect("Unable to build the rayon pool");
+        RayonExecutor { pool }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Executor for RayonExecutor {
+    fn dispatch(
+        &self,
+        width: u32,
+        height: u32,
+        task: &(dyn Fn(u32, Range<u32>) -> Vec<Texel> + Sync),
+        consume: &mut dyn FnMut(u32, Range<u32>, Vec<Texel>),
+    ) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.pool.in_place_scope(|scope| {
+            for y in 0..height {
+                let sender = sender.clone();
+                scope.spawn(move |_| {
+                    let _ = sender.send((y, task(y, 0..width)));
+                });
+            }
+            // Rows stream back as they complete; the iteration ends once
+            // every spawned task has dropped its sender.
+            drop(sender);
+            for (y, texels) in receiver {
+                consume(y, 0..width, texels);
+            }
+        });
+    }
+}
+
+/// The execution backend rendering the passes of a pipeline.
+#[derive(Copy, Clone, Default)]
+pub enum ExecutorKind {
+    /// The built-in bp3d-threads pool.
+    #[default]
+    ThreadPool,
+
+    /// The rayon work-stealing pool; only available with the `rayon`
+    /// feature.
+    #[cfg(feature = "rayon")]
+    Rayon,
+}
+
+impl ExecutorKind {
+    /// Returns the backend matching the given name, None if no such
+    /// backend exists or it is compiled out.
+    pub fn from_name(name: &str) -> Option<ExecutorKind> {
+        match name {
+            "threadpool" => Some(ExecutorKind::ThreadPool),
+            #[cfg(feature = "rayon")]
+            "rayon" => Some(ExecutorKind::Rayon),
+            _ => None,
+        }
+    }
+
+    /// Creates an executor of this backend rendering with the given number
+    /// of threads.
+    pub fn into_executor(self, n_threads: usize) -> Box<dyn Executor> {
+        match self {
+            ExecutorKind::ThreadPool => Box::new(ThreadPoolExecutor::new(n_threads)),
+            #[cfg(feature = "rayon")]
+            ExecutorKind::Rayon => Box::new(RayonExecutor::new(n_threads)),
+        }
+    }
+}
+
+/// A contiguous span of texel computations.
+struct Span<'a> {
+    function: &'a DynamicFunction,
+    counters: &'a PassCounters,
+    y: u32,
+    xs: Range<u32>,
+}
+
+impl<'a> Span<'a> {
+    #[cfg_attr(
+        feature = "texel-tracing",
+        instrument(name = "span", skip(self, format), fields(y = self.y, start = self.xs.start))
+    )]
+    fn run(self, format: Format) -> Vec<Texel> {
+        let start = Instant::now();
+        let mut out = vec![Texel::from_normalized(format, [0.0; 4]); self.xs.len()];
+        self.function.apply_span(self.y, self.xs, &mut out);
+        self.counters.processed.fetch_add(out.len() as u64, Ordering::Relaxed);
+        self.counters
+            .busy
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        out
+    }
+}
+
+/// The tiling configuration of a pipeline.
+///
+/// Tiled pipelines render each pass in square tiles instead of over the
+/// whole target at once, bounding the task queue and the intermediates of
+/// kernel filters when compiling very large textures.
+#[derive(Copy, Clone)]
+pub struct Tiling {
+    /// Width and height in texels of a tile.
+    pub size: u32,
+
+    /// Extra texels of context around each tile exposed to filters through
+    /// the frame buffer viewport.
+    ///
+    /// Kernel filters clamp reads of their intermediates to the viewport,
+    /// so an apron smaller than the widest kernel radius of the pipeline
+    /// shows up as seams along tile edges.
+    pub apron: u32,
+}
+
+/// Magic bytes opening a checkpoint file.
+const CHECKPOINT_MAGIC: &[u8; 4] = b"TXCK";
+
+fn write_buffer(file: &mut impl Write, buffer: &OutputTexture) -> std::io::Result<()> {
+    file.write_all(&(buffer.data().len() as u64).to_le_bytes())?;
+    file.write_all(buffer.data())?;
+    for entry in buffer.palette() {
+        file.write_all(entry)?;
+    }
+    Ok(())
+}
+
+fn read_buffer(
+    file: &mut impl Read,
+    width: u32,
+    height: u32,
+    format: Format,
+) -> Option<OutputTexture> {
+    let mut len = [0; 8];
+    file.read_exact(&mut len).ok()?;
+    let len = u64::from_le_bytes(len) as usize;
+    if len != width as usize * height as usize * format.texel_size() {
+        return None;
+    }
+    let mut data = vec![0; len];
+    file.read_exact(&mut data).ok()?;
+    let mut palette = Box::new([[0; 4]; 256]);
+    for entry in palette.iter_mut() {
+        file.read_exact(entry).ok()?;
+    }
+    Some(OutputTexture::from_parts(width, height, format, data, palette))
+}
+
+/// A single pass of a pipeline.
+pub struct Pass {
+    /// The filter rendered by this pass.
+    pub filter: DynamicFilter,
+
+    /// If set, the rendered buffer is kept alive under this name and can be
+    /// referenced by texture parameters of later passes.
+    pub publish: Option<String>,
+
+    /// Parameters overriding same named parameters of the shared map for
+    /// this pass only.
+    pub params: ParameterMap,
+}
+
+impl From<DynamicFilter> for Pass {
+    fn from(filter: DynamicFilter) -> Pass {
+        Pass {
+            filter,
+            publish: None,
+            params: ParameterMap::new(),
+        }
+    }
+}
+
+/// The render pipeline.
+pub struct Pipeline {
+    passes: Vec<Pass>,
+    chain: SwapChain,
+    buffers: HashMap<String, Arc<OutputTexture>>,
+    width: u32,
+    height: u32,
+    format: Format,
+    executor: Box<dyn Executor>,
+    tiling: Option<Tiling>,
+    checkpoint: Option<PathBuf>,
+    counters: PassCounters,
+    deterministic: bool,
+    seed: Option<u64>,
+    strict: bool,
+}
+
+impl Pipeline {
+    /// Creates a new pipeline rendering the given passes in order.
+    ///
+    /// The render target size is rounded up to the next power of two.
+    pub fn new(
+        width: u32,
+        height: u32,
+        format: Format,
+        passes: Vec<Pass>,
+        n_threads: usize,
+    ) -> Pipeline {
+        Pipeline::with_executor(
+            width,
+            height,
+            format,
+            passes,
+            Box::new(ThreadPoolExecutor::new(n_threads)),
+            false,
+        )
+    }
+
+    /// Creates a new pipeline rendering its passes on the given executor.
+    ///
+    /// The render target size is rounded up to the next power of two
+    /// unless `npot` keeps it exact.
+    pub fn with_executor(
+        width: u32,
+        height: u32,
+        format: Format,
+        passes: Vec<Pass>,
+        executor: Box<dyn Executor>,
+        npot: bool,
+    ) -> Pipeline {
+        let chain = SwapChain::new(width, height, format, npot);
+        let width = chain.previous().width();
+        let height = chain.previous().height();
+        Pipeline {
+            passes,
+            chain,
+            buffers: HashMap::new(),
+            width,
+            height,
+            format,
+            executor,
+            tiling: None,
+            checkpoint: None,
+            counters: PassCounters::default(),
+            deterministic: false,
+            seed: None,
+            strict: false,
+        }
+    }
+
+    /// Retains `depth` swap chain buffers so filters can read that many
+    /// recent passes through [history](crate::filter::FrameBuffer::history).
+    ///
+    /// Replaces the chain, so call this before rendering; depths below 2
+    /// are raised to 2.
+    pub fn set_chain_depth(&mut self, depth: usize) {
+        // The pipeline size is already final, so the chain must not round.
+        self.chain = SwapChain::with_depth(self.width, self.height, self.format, true, depth);
+    }
+
+    /// Renders passes tile by tile instead of over the whole target at
+    /// once; see [Tiling] for the trade-offs.
+    pub fn set_tiling(&mut self, tiling: Option<Tiling>) {
+        self.tiling = tiling;
+    }
+
+    /// Seeds the buffer the first pass reads as its previous pass, so a
+    /// pipeline can re-process an already rendered texture such as a
+    /// generated mip level.
+    ///
+    /// The texture must match the pipeline size and format.
+    pub fn set_input(&mut self, input: Arc<OutputTexture>) {
+        self.chain.present_shared(input);
+    }
+
+    /// Saves the render target, the published buffers and the pass index to
+    /// this path after every completed pass, and resumes from that state at
+    /// the start of [run](Pipeline::run), so long bakes survive crashes or
+    /// machine restarts.
+    ///
+    /// A checkpoint left by a run with a different size or format is ignored
+    /// with a warning; the file is deleted once every pass has rendered.
+    pub fn set_checkpoint(&mut self, checkpoint: Option<PathBuf>) {
+        self.checkpoint = checkpoint;
+    }
+
+    fn save_checkpoint(&self, path: &Path, completed: usize) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(CHECKPOINT_MAGIC)?;
+        file.write_all(&self.width.to_le_bytes())?;
+        file.write_all(&self.height.to_le_bytes())?;
+        let name = self.format.name();
+        file.write_all(&[name.len() as u8])?;
+        file.write_all(name.as_bytes())?;
+        file.write_all(&(completed as u32).to_le_bytes())?;
+        write_buffer(&mut file, self.chain.previous())?;
+        file.write_all(&(self.buffers.len() as u32).to_le_bytes())?;
+        for (name, buffer) in &self.buffers {
+            file.write_all(&(name.len() as u32).to_le_bytes())?;
+            file.write_all(name.as_bytes())?;
+            write_buffer(&mut file, buffer)?;
+        }
+        file.flush()
+    }
+
+    fn load_checkpoint(&mut self, path: &Path) -> Option<usize> {
+        let mut file = BufReader::new(File::open(path).ok()?);
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic).ok()?;
+        if &magic != CHECKPOINT_MAGIC {
+            return None;
+        }
+        let mut width = [0; 4];
+        file.read_exact(&mut width).ok()?;
+        let width = u32::from_le_bytes(width);
+        let mut height = [0; 4];
+        file.read_exact(&mut height).ok()?;
+        let height = u32::from_le_bytes(height);
+        let mut name_len = [0; 1];
+        file.read_exact(&mut name_len).ok()?;
+        let mut name = vec![0; name_len[0] as usize];
+        file.read_exact(&mut name).ok()?;
+        if width != self.width || height != self.height || name != self.format.name().as_bytes() {
+            return None;
+        }
+        let mut completed = [0; 4];
+        file.read_exact(&mut completed).ok()?;
+        let completed = u32::from_le_bytes(completed) as usize;
+        let target = read_buffer(&mut file, width, height, self.format)?;
+        let mut count = [0; 4];
+        file.read_exact(&mut count).ok()?;
+        let mut buffers = HashMap::new();
+        for _ in 0..u32::from_le_bytes(count) {
+            let mut len = [0; 4];
+            file.read_exact(&mut len).ok()?;
+            let mut name = vec![0; u32::from_le_bytes(len) as usize];
+            file.read_exact(&mut name).ok()?;
+            let name = String::from_utf8(name).ok()?;
+            let buffer = read_buffer(&mut file, width, height, self.format)?;
+            buffers.insert(name, Arc::new(buffer));
+        }
+        self.chain.present(target);
+        self.buffers = buffers;
+        Some(completed)
+    }
+
+    /// Forces fixed seeds in stochastic filters so two runs with the same
+    /// inputs produce bit-identical output.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Derives the randomness of every stochastic filter from the given
+    /// seed instead of the operating system, so two runs with the same seed
+    /// and inputs produce bit-identical output.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    /// Fails a pass on the first texel rejected by the render target
+    /// instead of warning and leaving a black texel.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    fn next_pass<D: PassDelegate>(
+        &mut self,
+        index: usize,
+        pass: &Pass,
+        params: &ParameterMap,
+        delegate: &D,
+        warnings: &mut Vec<Diagnostic>,
+        cancel: &CancelToken,
+    ) -> Result<(u64, u64, Duration), PipelineError> {
+        let merged;
+        let params = match pass.params.is_empty() {
+            true => params,
+            false => {
+                merged = params.merged(&pass.params);
+                &merged
+            }
+        };
+        let mut target = self.chain.acquire();
+        let total = self.width as u64 * self.height as u64;
+        let start = Instant::now();
+        let mut mismatches = 0u64;
+        let mut rejection: Option<TextureError> = None;
+        self.counters.processed.store(0, Ordering::Relaxed);
+        self.counters.busy.store(0, Ordering::Relaxed);
+        // A single tile covering the whole target when tiling is off.
+        let tile = match self.tiling {
+            Some(tiling) => tiling.size.max(1),
+            None => self.width.max(self.height),
+        };
+        let apron = match self.tiling {
+            Some(tiling) => tiling.apron,
+            None => 0,
+        };
+        let mut ty = 0;
+        'tiles: while ty < self.height {
+            let tile_h = tile.min(self.height - ty);
+            let mut tx = 0;
+            while tx < self.width {
+                let tile_w = tile.min(self.width - tx);
+                let left = tx.saturating_sub(apron);
+                let top = ty.saturating_sub(apron);
+                let frame = FrameBuffer {
+                    width: self.width,
+                    height: self.height,
+                    format: self.format,
+                    viewport: Viewport {
+                        x: left,
+                        y: top,
+                        width: (tx + tile_w + apron).min(self.width) - left,
+                        height: (ty + tile_h + apron).min(self.height) - top,
+                    },
+                    previous: self.chain.previous().clone(),
+                    history: self.chain.history(),
+                    buffers: self.buffers.clone(),
+                    deterministic: self.deterministic,
+                    seed: self.seed,
+                };
+                let function = pass.filter.new_function(&frame, params)?;
+                self.executor.dispatch(
+                    tile_w,
+                    tile_h,
+                    &|y, xs| {
+                        let y = ty + y;
+                        let xs = (tx + xs.start)..(tx + xs.end);
+                        if cancel.is_cancelled() {
+                            // Drain the remaining spans as fast as possible.
+                            let black = Texel::from_normalized(self.format, [0.0; 4]);
+                            return vec![black; xs.len()];
+                        }
+                        Span {
+                            function: &function,
+                            counters: &self.counters,
+                            y,
+                            xs,
+                        }
+                        .run(self.format)
+                    },
+                    &mut |y, xs, texels| {
+                        let y = ty + y;
+                        for (x, texel) in ((tx + xs.start)..(tx + xs.end)).zip(texels) {
+                            if let Err(e) = target.set(x, y, texel) {
+                                mismatches += 1;
+                                if self.strict {
+                                    if rejection.is_none() {
+                                        rejection = Some(e);
+                                    }
+                                } else {
+                                    warn!("Ignoring texel at ({}, {}): {}", x, y, e);
+                                    warnings.push(Diagnostic {
+                                        pass: Some(index),
+                                        filter: Some(pass.filter.name().into()),
+                                        position: Some((x, y)),
+                                        message: format!("ignored a rejected texel: {}", e),
+                                    });
+                                }
+                            }
+                            delegate.on_progress(PassStats {
+                                processed: self.counters.processed.load(Ordering::Relaxed),
+                                total,
+                                elapsed: start.elapsed(),
+                            });
+                        }
+                    },
+                );
+                if cancel.is_cancelled() {
+                    break 'tiles;
+                }
+                tx += tile_w;
+            }
+            ty += tile_h;
+        }
+        delegate.on_end();
+        self.chain.present(target);
+        if let Some(name) = &pass.publish {
+            self.buffers
+                .insert(name.clone(), self.chain.previous().clone());
+        }
+        if let Some(e) = rejection {
+            return Err(PipelineError::Mismatch(pass.filter.name().into(), e));
+        }
+        if cancel.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+        Ok((
+            mismatches,
+            self.counters.processed.load(Ordering::Relaxed),
+            Duration::from_nanos(self.counters.busy.load(Ordering::Relaxed)),
+        ))
+    }
+
+    /// Runs every pass of this pipeline in order.
+    ///
+    /// Returns the timing report of each pass; non fatal issues are appended
+    /// to the given diagnostic list with their pass, filter and position.
+    /// When a checkpoint is configured and a matching checkpoint file
+    /// exists, the passes it covers are skipped and do not appear in the
+    /// report.
+    pub fn run<D: PipelineDelegate>(
+        &mut self,
+        params: &ParameterMap,
+        delegate: &D,
+        warnings: &mut Vec<Diagnostic>,
+        cancel: &CancelToken,
+    ) -> Result<Vec<PassReport>, PipelineError> {
+        // The passes are moved out so next_pass can borrow the pipeline
+        // mutably, and restored on every exit path so a failed or cancelled
+        // run can be retried on the same pipeline.
+        let passes = std::mem::take(&mut self.passes);
+        let result = self.run_passes(&passes, params, delegate, warnings, cancel);
+        self.passes = passes;
+        result
+    }
+
+    fn run_passes<D: PipelineDelegate>(
+        &mut self,
+        passes: &[Pass],
+        params: &ParameterMap,
+        delegate: &D,
+        warnings: &mut Vec<Diagnostic>,
+        cancel: &CancelToken,
+    ) -> Result<Vec<PassReport>, PipelineError> {
+        let count = passes.len();
+        let mut completed = 0;
+        if let Some(path) = self.checkpoint.clone() {
+            if path.is_file() {
+                match self.load_checkpoint(&path) {
+                    Some(v) => completed = v.min(count),
+                    None => warnings.push(Diagnostic::global(format!(
+                        "ignored an incompatible checkpoint at '{}'",
+                        path.display()
+                    ))),
+                }
+            }
+        }
+        let mut reports = Vec::with_capacity(count - completed);
+        for (index, pass) in passes.iter().enumerate().skip(completed) {
+            if cancel.is_cancelled() {
+                return Err(PipelineError::Cancelled);
+            }
+            let delegate = delegate.on_start_pass(index, count, pass.filter.name());
+            let start = Instant::now();
+            let (mismatches, texels, busy) =
+                self.next_pass(index, pass, params, &delegate, warnings, cancel)?;
+            reports.push(PassReport {
+                name: pass.filter.name().into(),
+                duration: start.elapsed(),
+                busy,
+                texels,
+                mismatches,
+            });
+            if let Some(path) = &self.checkpoint {
+                if index + 1 < count {
+                    if let Err(e) = self.save_checkpoint(path, index + 1) {
+                        warnings.push(Diagnostic::global(format!(
+                            "unable to save a checkpoint at '{}': {}",
+                            path.display(),
+                            e
+                        )));
+                    }
+                }
+            }
+        }
+        if let Some(path) = &self.checkpoint {
+            let _ = std::fs::remove_file(path);
+        }
+        Ok(reports)
+    }
+
+    /// Consumes this pipeline and returns the final render target.
+    pub fn into_texture(self) -> Arc<OutputTexture> {
+        self.chain.into_previous()
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Compile progress as an async stream, for hosts built on async runtimes.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::channel::mpsc::unbounded;
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::channel::mpsc::UnboundedSender;
+use futures::channel::oneshot;
+
+use crate::pipeline::PassDelegate;
+use crate::pipeline::PassStats;
+use crate::pipeline::PipelineDelegate;
+use crate::CompileReport;
+use crate::Error;
+
+/// A progress event emitted by a running compilation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// A pass started rendering (index, pass count, filter name).
+    PassStarted {
+        /// Index of the pass.
+        index: usize,
+
+        /// Total number of passes.
+        count: usize,
+
+        /// Name of the filter rendered by the pass.
+        name: String,
+    },
+
+    /// Texels were processed; the statistics carry elapsed time, the
+    /// estimated remainder and the throughput of the pass.
+    Progress(PassStats),
+
+    /// The current pass finished rendering.
+    PassEnded,
+}
+
+/// A pipeline delegate forwarding progress into an async stream.
+///
+/// Per texel progress is coalesced to roughly one event per percent so the
+/// stream stays small even for very large textures.
+pub struct StreamDelegate {
+    sender: UnboundedSender<ProgressEvent>,
+}
+
+impl StreamDelegate {
+    /// Creates a new delegate and the stream yielding its events.
+    ///
+    /// The stream ends when the compilation finishes and the delegate is
+    /// dropped.
+    pub fn new() -> (StreamDelegate, UnboundedReceiver<ProgressEvent>) {
+        let (sender, receiver) = unbounded();
+        (StreamDelegate { sender }, receiver)
+    }
+}
+
+impl PipelineDelegate for StreamDelegate {
+    type Pass = StreamPassDelegate;
+
+    fn on_start_pass(&self, index: usize, count: usize, name: &str) -> StreamPassDelegate {
+        let _ = self.sender.unbounded_send(ProgressEvent::PassStarted {
+            index,
+            count,
+            name: name.into(),
+        });
+        StreamPassDelegate {
+            sender: self.sender.clone(),
+            last: AtomicU64::new(0),
+        }
+    }
+}
+
+/// The per pass delegate spawned by [StreamDelegate].
+pub struct StreamPassDelegate {
+    sender: UnboundedSender<ProgressEvent>,
+    last: AtomicU64,
+}
+
+impl PassDelegate for StreamPassDelegate {
+    fn on_progress(&self, stats: PassStats) {
+        let step = (stats.total / 100).max(1);
+        let last = self.last.load(Ordering::Relaxed);
+        let done = stats.processed == stats.total && last < stats.total;
+        if done || stats.processed.saturating_sub(last) >= step {
+            // fetch_max keeps the watermark monotonic when render threads
+            // report out of order.
+            self.last.fetch_max(stats.processed, Ordering::Relaxed);
+            let _ = self.sender.unbounded_send(ProgressEvent::Progress(stats));
+        }
+    }
+
+    fn on_end(&self) {
+        let _ = self.sender.unbounded_send(ProgressEvent::PassEnded);
+    }
+}
+
+/// A compilation running on a background thread, resolving to its report.
+///
+/// Returned by [run_async](crate::Compiler::run_async). Dropping the job
+/// does not stop the render; cancel through the configuration's
+/// [CancelToken](crate::pipeline::CancelToken) instead.
+pub struct CompileJob {
+    pub(crate) receiver: oneshot::Receiver<Result<CompileReport, Error>>,
+}
+
+impl Future for CompileJob {
+    type Output = Result<CompileReport, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<CompileReport, Error>> {
+        match Pin::new(&mut self.receiver).poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            // The background thread died without reporting, which only a
+            // filter panic causes; surface it as a cancellation rather
+            // than poisoning the runtime.
+            Poll::Ready(Err(oneshot::Canceled)) => Poll::Ready(Err(Error::Cancelled)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Reusable TOML texture templates (".template.toml" documents).
+//!
+//! A template captures a full filter chain once and is instantiated per
+//! asset with a handful of caller parameters, instead of hand-assembling
+//! the chain on the command line for every texture:
+//!
+//! ```toml
+//! width = 512
+//! height = 512
+//! format = "rgba8"
+//!
+//! [params.roughness]
+//! type = "float"
+//! default = 1.0
+//!
+//! [params.base]
+//! type = "texture"
+//!
+//! [[filters]]
+//! name = "resample"
+//! [filters.params]
+//! method = { string = "bicubic" }
+//!
+//! [[filters]]
+//! name = "gaussian"
+//! [filters.params]
+//! sigma = { ref = "roughness" }
+//! ```
+//!
+//! Declared parameters without a default must be supplied by the caller;
+//! all of them are visible to every filter under their declared name, and a
+//! `{ ref = "name" }` filter parameter routes one to a filter parameter of
+//! a different name. Texture paths in the document resolve relative to the
+//! template file.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::desc::ParameterDesc;
+use crate::encode::Encoding;
+use crate::encode::Quality;
+use crate::filter::ParameterType;
+use crate::mipmap::MipFilter;
+use crate::output::Container;
+use crate::params::Parameter;
+use crate::params::ParameterError;
+use crate::params::ParameterMap;
+use crate::pipeline::CancelToken;
+use crate::pipeline::ExecutorKind;
+use crate::texture::Format;
+use crate::Config;
+
+/// Errors raised when loading or instantiating a template.
+#[derive(Debug)]
+pub enum TemplateError {
+    /// The template file could not be read.
+    Io(std::io::Error),
+
+    /// The template document could not be parsed.
+    Parse(toml::de::Error),
+
+    /// A declared parameter with no default was not supplied (parameter
+    /// name).
+    MissingParameter(String),
+
+    /// A supplied parameter does not match its declared type (parameter
+    /// name, declared type).
+    InvalidParameter(String, ParameterType),
+
+    /// A filter parameter references an undeclared parameter (filter name,
+    /// referenced name).
+    UnknownRef(String, String),
+
+    /// A texture parameter of the template could not be loaded.
+    Parameter(ParameterError),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Io(e) => write!(f, "io error: {}", e),
+            TemplateError::Parse(e) => write!(f, "parse error: {}", e),
+            TemplateError::MissingParameter(name) => {
+                write!(f, "missing template parameter '{}'", name)
+            }
+            TemplateError::InvalidParameter(name, ty) => {
+                write!(f, "template parameter '{}' is not a {}", name, ty)
+            }
+            TemplateError::UnknownRef(filter, name) => write!(
+                f,
+                "filter '{}' references undeclared parameter '{}'",
+                filter, name
+            ),
+            TemplateError::Parameter(e) => write!(f, "parameter error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<ParameterError> for TemplateError {
+    fn from(e: ParameterError) -> TemplateError {
+        TemplateError::Parameter(e)
+    }
+}
+
+/// A parameter declared by a template.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TemplateParam {
+    /// Type the caller supplied value must have.
+    #[serde(rename = "type")]
+    pub ty: ParameterType,
+
+    /// Value used when the caller supplies none, as a plain TOML value of
+    /// the declared type (textures as their path); a declaration without a
+    /// default must be supplied.
+    #[serde(default)]
+    pub default: Option<toml::Value>,
+}
+
+/// The value of a filter parameter in a template.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FilterParam {
+    /// The value of a declared parameter, routed under the filter's own
+    /// parameter name.
+    Ref {
+        /// Name of the declared parameter to take the value from.
+        #[serde(rename = "ref")]
+        name: String,
+    },
+
+    /// A literal value.
+    Value(ParameterDesc),
+}
+
+/// One filter of a template chain.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TemplateFilter {
+    /// Name of the filter to run.
+    pub name: String,
+
+    /// If set, the pass output is published under this name for texture
+    /// parameters of later filters.
+    #[serde(default)]
+    pub publish: Option<String>,
+
+    /// Parameters of this filter alone, overriding same named shared
+    /// parameters.
+    #[serde(default)]
+    pub params: HashMap<String, FilterParam>,
+}
+
+/// A reusable texture template.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    /// Default width in texels of the output texture.
+    pub width: u32,
+
+    /// Default height in texels of the output texture.
+    pub height: u32,
+
+    /// Format of the output texture.
+    pub format: Format,
+
+    /// If set, keeps the exact output size instead of rounding it up to
+    /// the next power of two.
+    #[serde(default)]
+    pub npot: bool,
+
+    /// Number of layers of the output texture array, 1 for a plain 2D
+    /// texture; see [layers](crate::Config::layers).
+    #[serde(default = "default_layers")]
+    pub layers: u32,
+
+    /// If set, assembles the six rendered layers into a cubemap; see
+    /// [cubemap](crate::Config::cubemap).
+    #[serde(default)]
+    pub cubemap: bool,
+
+    /// If set, generates and stores a full mip chain; see
+    /// [mipmaps](crate::Config::mipmaps).
+    #[serde(default)]
+    pub mipmaps: bool,
+
+    /// The downsampling filter generating mip levels (box by default); see
+    /// [mip_filter](crate::Config::mip_filter).
+    #[serde(default)]
+    pub mip_filter: MipFilter,
+
+    /// If set, keeps alpha test coverage at this reference value constant
+    /// across the mip chain; see
+    /// [alpha_coverage](crate::Config::alpha_coverage).
+    #[serde(default)]
+    pub alpha_coverage: Option<f64>,
+
+    /// Names of the filters re-run on every generated mip level below the
+    /// base; see [mip_passes](crate::Config::mip_passes).
+    #[serde(default)]
+    pub mip_passes: Vec<String>,
+
+    /// Parameters the template declares for its callers.
+    #[serde(default)]
+    pub params: HashMap<String, TemplateParam>,
+
+    /// The filters to run in order.
+    pub filters: Vec<TemplateFilter>,
+}
+
+fn default_layers() -> u32 {
+    1
+}
+
+/// Returns the type of a parameter value.
+fn type_of(param: &Parameter) -> ParameterType {
+    match param {
+        Parameter::Float(_) => ParameterType::Float,
+        Parameter::Int(_) => ParameterType::Int,
+        Parameter::Bool(_) => ParameterType::Bool,
+        Parameter::Vector2(_) => ParameterType::Vector2,
+        Parameter::Vector3(_) => ParameterType::Vector3,
+        Parameter::Vector4(_) => ParameterType::Vector4,
+        Parameter::Color(_) => ParameterType::Color,
+        Parameter::String(_) => ParameterType::String,
+        Parameter::Texture(_) => ParameterType::Texture,
+    }
+}
+
+/// Returns true when a value satisfies a declared type.
+///
+/// Integers satisfy float declarations and colors and 4 components
+/// vectors satisfy each other, like the [Parameter] accessors accept
+/// them.
+fn type_matches(param: &Parameter, ty: ParameterType) -> bool {
+    let actual = type_of(param);
+    actual == ty
+        || (actual == ParameterType::Int && ty == ParameterType::Float)
+        || (actual == ParameterType::Color && ty == ParameterType::Vector4)
+        || (actual == ParameterType::Vector4 && ty == ParameterType::Color)
+}
+
+/// Resolves a texture path of the document against the template directory.
+fn resolve(desc: ParameterDesc, dir: &Path) -> ParameterDesc {
+    match desc {
+        ParameterDesc::Texture(path) if path.is_relative() => {
+            ParameterDesc::Texture(dir.join(path))
+        }
+        desc => desc,
+    }
+}
+
+fn float_of(value: &toml::Value) -> Option<f64> {
+    match value {
+        toml::Value::Float(v) => Some(*v),
+        toml::Value::Integer(v) => Some(*v as f64),
+        _ => None,
+    }
+}
+
+fn vector_of<const N: usize>(value: &toml::Value) -> Option<[f64; N]> {
+    let array = value.as_array()?;
+    if array.len() != N {
+        return None;
+    }
+    let mut components = [0.0; N];
+    for (component, value) in components.iter_mut().zip(array) {
+        *component = float_of(value)?;
+    }
+    Some(components)
+}
+
+/// Turns the plain TOML default of a declaration into a parameter value.
+fn default_value(ty: ParameterType, value: &toml::Value) -> Option<ParameterDesc> {
+    match ty {
+        ParameterType::Float => Some(ParameterDesc::Float(float_of(value)?)),
+        ParameterType::Int => Some(ParameterDesc::Int(value.as_integer()?)),
+        ParameterType::Bool => Some(ParameterDesc::Bool(value.as_bool()?)),
+        ParameterType::Vector2 => Some(ParameterDesc::Vector2(vector_of(value)?)),
+        ParameterType::Vector3 => Some(ParameterDesc::Vector3(vector_of(value)?)),
+        ParameterType::Vector4 => Some(ParameterDesc::Vector4(vector_of(value)?)),
+        ParameterType::Color => {
+            let color = match value.as_str() {
+                Some(v) => crate::params::parse_color(v)?,
+                None => vector_of(value)?,
+            };
+            Some(ParameterDesc::Color(color))
+        }
+        ParameterType::String => Some(ParameterDesc::String(value.as_str()?.into())),
+        ParameterType::Texture => Some(ParameterDesc::Texture(value.as_str()?.into())),
+    }
+}
+
+impl Template {
+    /// Loads a template from a TOML document.
+    pub fn load(path: &Path) -> Result<Template, TemplateError> {
+        let source = std::fs::read_to_string(path).map_err(TemplateError::Io)?;
+        toml::from_str(&source).map_err(TemplateError::Parse)
+    }
+
+    /// Builds a compiler configuration from this template.
+    ///
+    /// `dir` anchors relative texture paths of the document, usually the
+    /// directory of the template file. `params` supplies the declared
+    /// parameters; entries it holds beyond the declarations pass through to
+    /// the filters unchecked.
+    pub fn into_config(
+        self,
+        dir: &Path,
+        output: PathBuf,
+        params: ParameterMap,
+        n_threads: usize,
+    ) -> Result<Config, TemplateError> {
+        let mut shared = params;
+        for (name, decl) in self.params {
+            match shared.get(&name) {
+                Some(value) => {
+                    if !type_matches(value, decl.ty) {
+                        return Err(TemplateError::InvalidParameter(name, decl.ty));
+                    }
+                }
+                None => {
+                    let ty = decl.ty;
+                    let default = decl
+                        .default
+                        .ok_or_else(|| TemplateError::MissingParameter(name.clone()))?;
+                    let default = default_value(ty, &default)
+                        .ok_or_else(|| TemplateError::InvalidParameter(name.clone(), ty))?;
+                    let value = resolve(default, dir).into_parameter(&name)?;
+                    shared.insert(name, value);
+                }
+            }
+        }
+        let mut filters = Vec::with_capacity(self.filters.len());
+        let mut pass_params = Vec::with_capacity(self.filters.len());
+        for filter in self.filters {
+            let mut overrides = ParameterMap::new();
+            let filter_name = filter.name;
+            for (name, value) in filter.params {
+                let value = match value {
+                    FilterParam::Ref { name: declared } => shared
+                        .get(&declared)
+                        .cloned()
+                        .ok_or_else(|| {
+                            TemplateError::UnknownRef(filter_name.clone(), declared)
+                        })?,
+                    FilterParam::Value(desc) => resolve(desc, dir).into_parameter(&name)?,
+                };
+                overrides.insert(name, value);
+            }
+            filters.push(match filter.publish {
+                Some(publish) => format!("{}:{}", filter_name, publish),
+                None => filter_name,
+            });
+            pass_params.push(overrides);
+        }
+        let container = Container::from_path(&output);
+        Ok(Config {
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            npot: self.npot,
+            layers: self.layers,
+            cubemap: self.cubemap,
+            mipmaps: self.mipmaps,
+            mip_filter: self.mip_filter,
+            alpha_coverage: self.alpha_coverage,
+            mip_passes: self.mip_passes,
+            output,
+            container,
+            encoding: Encoding::Raw,
+            supercompress: false,
+            quality: Quality::Normal,
+            filters,
+            params: shared,
+            pass_params,
+            n_threads,
+            executor: ExecutorKind::default(),
+            debug: false,
+            deterministic: false,
+            seed: None,
+            strict: false,
+            tiling: None,
+            cache: false,
+            max_memory: None,
+            checkpoint: None,
+            cancel: CancelToken::new(),
+        })
+    }
+}
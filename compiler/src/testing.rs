@@ -0,0 +1,221 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Golden image helpers for filter regression suites (feature "testing").
+//!
+//! Renders a filter chain fully in memory, deterministically and on a
+//! single thread, then compares the result against a reference texture.
+//! Both this crate's filters and third party filters can build their
+//! regression tests on these helpers.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::filter::DynamicFilter;
+use crate::params::ParameterMap;
+use crate::pipeline::CancelToken;
+use crate::pipeline::Pass;
+use crate::pipeline::PassDelegate;
+use crate::pipeline::PassStats;
+use crate::pipeline::Pipeline;
+use crate::pipeline::PipelineDelegate;
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texture;
+use crate::Error;
+
+/// A delegate discarding all progress notifications.
+struct Silent;
+
+impl PassDelegate for Silent {
+    fn on_progress(&self, _stats: PassStats) {}
+
+    fn on_end(&self) {}
+}
+
+impl PipelineDelegate for Silent {
+    type Pass = Silent;
+
+    fn on_start_pass(&self, _index: usize, _count: usize, _name: &str) -> Silent {
+        Silent
+    }
+}
+
+/// Tolerance thresholds of a golden image comparison.
+pub struct Tolerance {
+    /// Largest accepted per channel delta, in normalized units.
+    pub max_delta: f32,
+
+    /// Smallest accepted peak signal to noise ratio, in decibels.
+    pub min_psnr: f64,
+}
+
+/// The measured difference between a render and its reference.
+#[derive(Debug)]
+pub struct Comparison {
+    /// Largest per channel delta found, in normalized units.
+    pub max_delta: f32,
+
+    /// Peak signal to noise ratio, in decibels (infinite for equal images).
+    pub psnr: f64,
+}
+
+/// Errors raised by the golden image helpers.
+#[derive(Debug)]
+pub enum TestError {
+    /// The render and its reference have different sizes
+    /// (actual width, actual height, reference width, reference height).
+    SizeMismatch(u32, u32, u32, u32),
+
+    /// The render is out of tolerance; the diff image path is included when
+    /// one was written.
+    OutOfTolerance(Comparison),
+
+    /// The diff image could not be saved.
+    Image(image::ImageError),
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestError::SizeMismatch(aw, ah, rw, rh) => write!(
+                f,
+                "size mismatch (rendered {}x{}, reference {}x{})",
+                aw, ah, rw, rh
+            ),
+            TestError::OutOfTolerance(c) => write!(
+                f,
+                "out of tolerance (max delta {}, psnr {:.2} dB)",
+                c.max_delta, c.psnr
+            ),
+            TestError::Image(e) => write!(f, "image error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TestError {}
+
+/// Renders a filter chain fully in memory and returns the final target.
+///
+/// The pipeline runs deterministically on a single thread so golden images
+/// stay stable across machines and runs.
+pub fn render(
+    width: u32,
+    height: u32,
+    format: Format,
+    filters: &[&str],
+    params: &ParameterMap,
+) -> Result<Arc<OutputTexture>, Error> {
+    let passes: Vec<Pass> = filters
+        .iter()
+        .map(|name| {
+            DynamicFilter::from_name(name)
+                .map(Pass::from)
+                .ok_or_else(|| Error::UnknownFilter((*name).into()))
+        })
+        .collect::<Result<_, _>>()?;
+    let mut pipeline = Pipeline::new(width, height, format, passes, 1);
+    pipeline.set_deterministic(true);
+    pipeline.set_strict(true);
+    let mut warnings = Vec::new();
+    pipeline.run(params, &Silent, &mut warnings, &CancelToken::new())?;
+    Ok(pipeline.into_texture())
+}
+
+/// Measures the difference between a render and its reference.
+pub fn compare(actual: &dyn Texture, reference: &dyn Texture) -> Result<Comparison, TestError> {
+    if actual.width() != reference.width() || actual.height() != reference.height() {
+        return Err(TestError::SizeMismatch(
+            actual.width(),
+            actual.height(),
+            reference.width(),
+            reference.height(),
+        ));
+    }
+    let mut max_delta = 0.0f32;
+    let mut squared_sum = 0.0f64;
+    for y in 0..actual.height() {
+        for x in 0..actual.width() {
+            let lhs = actual.get(x, y).normalize();
+            let rhs = reference.get(x, y).normalize();
+            for (a, r) in lhs.iter().zip(rhs) {
+                let delta = (a - r).abs();
+                max_delta = max_delta.max(delta);
+                squared_sum += delta as f64 * delta as f64;
+            }
+        }
+    }
+    let samples = actual.width() as f64 * actual.height() as f64 * 4.0;
+    let mse = squared_sum / samples;
+    let psnr = if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        -10.0 * mse.log10()
+    };
+    Ok(Comparison { max_delta, psnr })
+}
+
+/// Builds an amplified difference image between a render and its reference.
+///
+/// Each channel holds 8 times the absolute delta, clamped; matching areas
+/// come out black.
+pub fn diff_image(actual: &dyn Texture, reference: &dyn Texture) -> image::RgbaImage {
+    let width = actual.width().min(reference.width());
+    let height = actual.height().min(reference.height());
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let lhs = actual.get(x, y).normalize();
+        let rhs = reference.get(x, y).normalize();
+        let mut texel = [0u8; 4];
+        for (out, (a, r)) in texel.iter_mut().zip(lhs.iter().zip(rhs)) {
+            *out = (((a - r).abs() * 8.0).clamp(0.0, 1.0) * 255.0) as u8;
+        }
+        texel[3] = 255;
+        image::Rgba(texel)
+    })
+}
+
+/// Compares a render against its reference with the given tolerance.
+///
+/// On an out of tolerance result, an amplified diff image is written to
+/// `diff_path` before the error is returned.
+pub fn expect_golden(
+    actual: &dyn Texture,
+    reference: &dyn Texture,
+    tolerance: &Tolerance,
+    diff_path: &Path,
+) -> Result<Comparison, TestError> {
+    let comparison = compare(actual, reference)?;
+    if comparison.max_delta > tolerance.max_delta || comparison.psnr < tolerance.min_psnr {
+        diff_image(actual, reference)
+            .save(diff_path)
+            .map_err(TestError::Image)?;
+        return Err(TestError::OutOfTolerance(comparison));
+    }
+    Ok(comparison)
+}
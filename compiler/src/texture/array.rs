@@ -0,0 +1,131 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::sync::Arc;
+
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texture;
+use crate::texture::TextureError;
+
+/// A texture array: one or more layers sharing a size and format, each
+/// carrying its own mip chain, as used by terrain splat sets and material
+/// arrays.
+pub struct ArrayTexture {
+    layers: Vec<Vec<Arc<OutputTexture>>>,
+}
+
+impl ArrayTexture {
+    /// Builds a texture array from the base level of its layers.
+    ///
+    /// Fails when no layer is given or when a layer does not share the size
+    /// and format of the first one.
+    pub fn new(layers: Vec<Arc<OutputTexture>>) -> Result<ArrayTexture, TextureError> {
+        let first = layers.first().ok_or(TextureError::NoLayers)?;
+        for (index, layer) in layers.iter().enumerate() {
+            if layer.width() != first.width()
+                || layer.height() != first.height()
+                || layer.format() != first.format()
+            {
+                return Err(TextureError::LayerMismatch(index));
+            }
+        }
+        Ok(ArrayTexture {
+            layers: layers.into_iter().map(|layer| vec![layer]).collect(),
+        })
+    }
+
+    /// Returns the width in texels shared by every layer.
+    pub fn width(&self) -> u32 {
+        self.layers[0][0].width()
+    }
+
+    /// Returns the height in texels shared by every layer.
+    pub fn height(&self) -> u32 {
+        self.layers[0][0].height()
+    }
+
+    /// Returns the format shared by every layer and level.
+    pub fn format(&self) -> Format {
+        self.layers[0][0].format()
+    }
+
+    /// Returns the number of layers.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Always false: a texture array holds at least one layer.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns the base level of a layer by index, None past the last
+    /// layer.
+    pub fn layer(&self, index: usize) -> Option<&Arc<OutputTexture>> {
+        self.layers.get(index).map(|chain| &chain[0])
+    }
+
+    /// Returns the mip chain of a layer, from the base level down, None
+    /// past the last layer.
+    pub fn mip_chain(&self, index: usize) -> Option<&[Arc<OutputTexture>]> {
+        self.layers.get(index).map(|chain| chain.as_slice())
+    }
+
+    /// Returns the number of mip levels, identical for every layer.
+    pub fn mip_levels(&self) -> usize {
+        self.layers[0].len()
+    }
+
+    /// Appends the next mip level of every layer at once, so the chains
+    /// always stay the same depth.
+    ///
+    /// Fails when the number of levels does not match the number of layers,
+    /// or when a level is not half the size of the previous one (rounded
+    /// down, never below one texel) or does not share the array format.
+    pub fn push_mip(&mut self, levels: Vec<Arc<OutputTexture>>) -> Result<(), TextureError> {
+        if levels.len() != self.layers.len() {
+            return Err(TextureError::LayerMismatch(levels.len()));
+        }
+        let previous = self.layers[0].last().unwrap();
+        let expected = (previous.width() / 2).max(1);
+        let expected_height = (previous.height() / 2).max(1);
+        for (index, level) in levels.iter().enumerate() {
+            if level.width() != expected
+                || level.height() != expected_height
+                || level.format() != self.format()
+            {
+                return Err(TextureError::MipMismatch(index, self.mip_levels()));
+            }
+        }
+        for (layer, level) in self.layers.iter_mut().zip(levels) {
+            layer.push(level);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+
+use std::sync::Arc;
+
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texture;
+use crate::texture::TextureError;
+
+/// One of the six faces of a [Cubemap], in the +X -X +Y -Y +Z -Z order
+/// shared by the DDS and KTX2 containers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CubeFace {
+    /// The positive X face.
+    PositiveX,
+
+    /// The negative X face.
+    NegativeX,
+
+    /// The positive Y face.
+    PositiveY,
+
+    /// The negative Y face.
+    NegativeY,
+
+    /// The positive Z face.
+    PositiveZ,
+
+    /// The negative Z face.
+    NegativeZ,
+}
+
+impl CubeFace {
+    /// Every face in container order.
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// Returns the container order index of this face.
+    pub fn index(self) -> usize {
+        match self {
+            CubeFace::PositiveX => 0,
+            CubeFace::NegativeX => 1,
+            CubeFace::PositiveY => 2,
+            CubeFace::NegativeY => 3,
+            CubeFace::PositiveZ => 4,
+            CubeFace::NegativeZ => 5,
+        }
+    }
+
+    /// Returns the short name of this face.
+    pub fn name(self) -> &'static str {
+        match self {
+            CubeFace::PositiveX => "+x",
+            CubeFace::NegativeX => "-x",
+            CubeFace::PositiveY => "+y",
+            CubeFace::NegativeY => "-y",
+            CubeFace::PositiveZ => "+z",
+            CubeFace::NegativeZ => "-z",
+        }
+    }
+}
+
+impl std::fmt::Display for CubeFace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A cubemap: six square faces sharing a size and format, each carrying
+/// its own mip chain.
+///
+/// Cubemap producing filters and the output writers exchange this one
+/// representation instead of ad-hoc six layer conventions.
+pub struct Cubemap {
+    faces: [Vec<Arc<OutputTexture>>; 6],
+}
+
+impl Cubemap {
+    /// Builds a cubemap from the base level of its six faces, in
+    /// [ALL](CubeFace::ALL) order.
+    ///
+    /// Fails when the first face is not square or when a face does not
+    /// share the size and format of the first one.
+    pub fn new(faces: [Arc<OutputTexture>; 6]) -> Result<Cubemap, TextureError> {
+        let first = &faces[0];
+        if first.width() != first.height() {
+            return Err(TextureError::NotSquare);
+        }
+        for (index, face) in faces.iter().enumerate() {
+            if face.width() != first.width()
+                || face.height() != first.height()
+                || face.format() != first.format()
+            {
+                return Err(TextureError::FaceMismatch(index));
+            }
+        }
+        Ok(Cubemap {
+            faces: faces.map(|face| vec![face]),
+        })
+    }
+
+    /// Returns the edge length in texels of the base level.
+    pub fn size(&self) -> u32 {
+        self.faces[0][0].width()
+    }
+
+    /// Returns the format shared by every face and level.
+    pub fn format(&self) -> Format {
+        self.faces[0][0].format()
+    }
+
+    /// Returns the base level of a face.
+    pub fn face(&self, face: CubeFace) -> &Arc<OutputTexture> {
+        &self.faces[face.index()][0]
+    }
+
+    /// Returns the mip chain of a face, from the base level down.
+    pub fn mip_chain(&self, face: CubeFace) -> &[Arc<OutputTexture>] {
+        &self.faces[face.index()]
+    }
+
+    /// Returns the number of mip levels, identical for every face.
+    pub fn mip_levels(&self) -> usize {
+        self.faces[0].len()
+    }
+
+    /// Appends the next mip level of every face at once, so the six chains
+    /// always stay the same depth.
+    ///
+    /// Fails when a level is not half the edge length of the previous one
+    /// (rounded down, never below one texel) or does not share the cubemap
+    /// format.
+    pub fn push_mip(&mut self, levels: [Arc<OutputTexture>; 6]) -> Result<(), TextureError> {
+        let previous = self.faces[0].last().unwrap().width();
+        let expected = (previous / 2).max(1);
+        for (index, level) in levels.iter().enumerate() {
+            if level.width() != expected
+                || level.height() != expected
+                || level.format() != self.format()
+            {
+                return Err(TextureError::MipMismatch(index, self.mip_levels()));
+            }
+        }
+        for (face, level) in self.faces.iter_mut().zip(levels) {
+            face.push(level);
+        }
+        Ok(())
+    }
+}
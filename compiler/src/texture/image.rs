@@ -0,0 +1,134 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+
+use image::DynamicImage;
+use image::ImageBuffer;
+use image::ImageFormat;
+use image::ImageReader;
+use image::Luma;
+use image::Rgba;
+use image::Rgba32FImage;
+use image::RgbaImage;
+
+use crate::texture::Format;
+use crate::texture::Texel;
+use crate::texture::Texture;
+
+/// The decoded storage of an image texture.
+///
+/// Float sources (EXR, HDR) and 16 bits sources (heightmap PNGs) keep
+/// their full precision instead of being clamped to 8 bits at load time.
+enum Data {
+    Rgba8(RgbaImage),
+    R16(ImageBuffer<Luma<u16>, Vec<u16>>),
+    Rgba16(ImageBuffer<Rgba<u16>, Vec<u16>>),
+    RgbaF32(Rgba32FImage),
+}
+
+/// A read only texture backed by a decoded source image.
+pub struct ImageTexture {
+    data: Data,
+}
+
+impl ImageTexture {
+    /// Creates a new image texture from a decoded image.
+    ///
+    /// Float typed images are stored as RGBAF32 so HDR sources feed float
+    /// pipelines without losing precision, and 16 bits images are kept at
+    /// 16 bits per channel; everything else becomes RGBA8.
+    pub fn new(image: DynamicImage) -> ImageTexture {
+        let data = match image {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                Data::RgbaF32(image.to_rgba32f())
+            }
+            DynamicImage::ImageLuma16(image) => Data::R16(image),
+            DynamicImage::ImageLumaA16(_) | DynamicImage::ImageRgb16(_) => {
+                Data::Rgba16(image.to_rgba16())
+            }
+            DynamicImage::ImageRgba16(image) => Data::Rgba16(image),
+            _ => Data::Rgba8(image.to_rgba8()),
+        };
+        ImageTexture { data }
+    }
+
+    /// Creates a new image texture by decoding an arbitrary reader.
+    ///
+    /// This allows content to come from archives or virtual file systems;
+    /// the image format cannot be guessed from a file extension so it must
+    /// be given explicitly.
+    pub fn from_reader<R: Read + Seek>(
+        reader: R,
+        format: ImageFormat,
+    ) -> Result<ImageTexture, image::ImageError> {
+        let image = ImageReader::with_format(BufReader::new(reader), format).decode()?;
+        Ok(ImageTexture::new(image))
+    }
+
+}
+
+impl Texture for ImageTexture {
+    fn width(&self) -> u32 {
+        match &self.data {
+            Data::Rgba8(image) => image.width(),
+            Data::R16(image) => image.width(),
+            Data::Rgba16(image) => image.width(),
+            Data::RgbaF32(image) => image.width(),
+        }
+    }
+
+    fn height(&self) -> u32 {
+        match &self.data {
+            Data::Rgba8(image) => image.height(),
+            Data::R16(image) => image.height(),
+            Data::Rgba16(image) => image.height(),
+            Data::RgbaF32(image) => image.height(),
+        }
+    }
+
+    fn format(&self) -> Format {
+        match &self.data {
+            Data::Rgba8(_) => Format::RGBA8,
+            Data::R16(_) => Format::R16,
+            Data::Rgba16(_) => Format::RGBA16,
+            Data::RgbaF32(_) => Format::RGBAF32,
+        }
+    }
+
+    fn get(&self, x: u32, y: u32) -> Texel {
+        match &self.data {
+            Data::Rgba8(image) => Texel::RGBA8(image.get_pixel(x, y).0),
+            Data::R16(image) => Texel::R16(image.get_pixel(x, y).0[0]),
+            Data::Rgba16(image) => Texel::RGBA16(image.get_pixel(x, y).0),
+            Data::RgbaF32(image) => Texel::RGBAF32(image.get_pixel(x, y).0),
+        }
+    }
+}
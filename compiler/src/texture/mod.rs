@@ -0,0 +1,875 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Texture storage and texel manipulation utilities.
+
+mod array;
+mod cube;
+mod image;
+mod output;
+mod swap;
+
+pub use array::ArrayTexture;
+pub use cube::CubeFace;
+pub use cube::Cubemap;
+pub use self::image::ImageTexture;
+pub use output::OutputTexture;
+pub use swap::SwapChain;
+
+use std::fmt;
+
+/// The format of a texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// 8 bits greyscale.
+    L8,
+
+    /// 16 bits greyscale.
+    R16,
+
+    /// 16 bits dual channel (8 bits per channel).
+    RG8,
+
+    /// 32 bits dual channel (16 bits per channel).
+    RG16,
+
+    /// 32 bits float greyscale.
+    F32,
+
+    /// 32 bits RGBA (8 bits per channel).
+    RGBA8,
+
+    /// 32 bits sRGB encoded RGBA (8 bits per channel, linear alpha).
+    ///
+    /// Texels linearize when normalized so filters operate in linear light;
+    /// the stored bytes stay sRGB encoded.
+    RGBA8Srgb,
+
+    /// 64 bits RGBA (16 bits per channel).
+    RGBA16,
+
+    /// 64 bits half float RGBA (16 bits per channel).
+    RGBA16F,
+
+    /// 128 bits float RGBA (32 bits per channel).
+    RGBAF32,
+
+    /// 16 bits packed RGB (5 bits red, 6 bits green, 5 bits blue).
+    RGB565,
+
+    /// 16 bits packed RGBA (4 bits per channel).
+    RGBA4444,
+
+    /// 16 bits packed RGBA (5 bits per color channel, 1 bit alpha).
+    RGB5A1,
+
+    /// 8 bits palette indexed RGBA.
+    ///
+    /// Each texel stores an index into a 256 entry RGBA8 palette held by the
+    /// render target and written alongside the payload by containers that
+    /// support it. The palette is normally extracted by the quantize filter;
+    /// other filters fall back to indexing by the red channel.
+    P8,
+}
+
+impl Format {
+    /// Returns the size in bytes of a single texel in this format.
+    pub fn texel_size(self) -> usize {
+        match self {
+            Format::L8 => 1,
+            Format::R16 => 2,
+            Format::RG8 => 2,
+            Format::RG16 => 4,
+            Format::F32 => 4,
+            Format::RGBA8 => 4,
+            Format::RGBA8Srgb => 4,
+            Format::RGBA16 => 8,
+            Format::RGBA16F => 8,
+            Format::RGBAF32 => 16,
+            Format::RGB565 => 2,
+            Format::RGBA4444 => 2,
+            Format::RGB5A1 => 2,
+            Format::P8 => 1,
+        }
+    }
+
+    /// Returns the name of this format as used by the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::L8 => "l8",
+            Format::R16 => "r16",
+            Format::RG8 => "rg8",
+            Format::RG16 => "rg16",
+            Format::F32 => "f32",
+            Format::RGBA8 => "rgba8",
+            Format::RGBA8Srgb => "rgba8srgb",
+            Format::RGBA16 => "rgba16",
+            Format::RGBA16F => "rgba16f",
+            Format::RGBAF32 => "rgbaf32",
+            Format::RGB565 => "rgb565",
+            Format::RGBA4444 => "rgba4444",
+            Format::RGB5A1 => "rgb5a1",
+            Format::P8 => "p8",
+        }
+    }
+
+    /// Parses a format from its command line name.
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "l8" => Some(Format::L8),
+            "r16" => Some(Format::R16),
+            "rg8" => Some(Format::RG8),
+            "rg16" => Some(Format::RG16),
+            "f32" => Some(Format::F32),
+            "rgba8" => Some(Format::RGBA8),
+            "rgba8srgb" => Some(Format::RGBA8Srgb),
+            "rgba16" => Some(Format::RGBA16),
+            "rgba16f" => Some(Format::RGBA16F),
+            "rgbaf32" => Some(Format::RGBAF32),
+            "rgb565" => Some(Format::RGB565),
+            "rgba4444" => Some(Format::RGBA4444),
+            "rgb5a1" => Some(Format::RGB5A1),
+            "p8" => Some(Format::P8),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl serde::Serialize for Format {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Format {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Format, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Format::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format_args!("unknown texture format '{}'", name)))
+    }
+}
+
+/// A single texel in one of the supported formats.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Texel {
+    /// 8 bits greyscale texel.
+    L8(u8),
+
+    /// 16 bits greyscale texel.
+    R16(u16),
+
+    /// 16 bits dual channel texel.
+    RG8([u8; 2]),
+
+    /// 32 bits dual channel texel.
+    RG16([u16; 2]),
+
+    /// 32 bits float greyscale texel.
+    F32(f32),
+
+    /// 32 bits RGBA texel.
+    RGBA8([u8; 4]),
+
+    /// 32 bits sRGB encoded RGBA texel.
+    RGBA8Srgb([u8; 4]),
+
+    /// 64 bits RGBA texel.
+    RGBA16([u16; 4]),
+
+    /// 64 bits half float RGBA texel, stored as raw half bits.
+    RGBA16F([u16; 4]),
+
+    /// 128 bits float RGBA texel.
+    RGBAF32([f32; 4]),
+
+    /// 16 bits packed RGB565 texel.
+    RGB565(u16),
+
+    /// 16 bits packed RGBA4444 texel.
+    RGBA4444(u16),
+
+    /// 16 bits packed RGB5A1 texel.
+    RGB5A1(u16),
+
+    /// 8 bits palette indexed texel, carrying both the stored index and the
+    /// RGBA8 palette color it refers to.
+    P8 {
+        /// The palette index stored in the payload.
+        index: u8,
+
+        /// The palette color the index resolves to.
+        rgba: [u8; 4],
+    },
+}
+
+impl Texel {
+    /// Returns the format of this texel.
+    pub fn format(&self) -> Format {
+        match self {
+            Texel::L8(_) => Format::L8,
+            Texel::R16(_) => Format::R16,
+            Texel::RG8(_) => Format::RG8,
+            Texel::RG16(_) => Format::RG16,
+            Texel::F32(_) => Format::F32,
+            Texel::RGBA8(_) => Format::RGBA8,
+            Texel::RGBA8Srgb(_) => Format::RGBA8Srgb,
+            Texel::RGBA16(_) => Format::RGBA16,
+            Texel::RGBA16F(_) => Format::RGBA16F,
+            Texel::RGBAF32(_) => Format::RGBAF32,
+            Texel::RGB565(_) => Format::RGB565,
+            Texel::RGBA4444(_) => Format::RGBA4444,
+            Texel::RGB5A1(_) => Format::RGB5A1,
+            Texel::P8 { .. } => Format::P8,
+        }
+    }
+
+    /// Expands this texel into normalized RGBA floats.
+    ///
+    /// Greyscale texels are replicated over RGB with an opaque alpha, and
+    /// sRGB encoded texels decode to linear light.
+    pub fn normalize(&self) -> [f32; 4] {
+        match self {
+            Texel::L8(l) => {
+                let l = *l as f32 / 255.0;
+                [l, l, l, 1.0]
+            }
+            Texel::R16(l) => {
+                let l = *l as f32 / 65535.0;
+                [l, l, l, 1.0]
+            }
+            Texel::RG8([r, g]) => [*r as f32 / 255.0, *g as f32 / 255.0, 0.0, 1.0],
+            Texel::RG16([r, g]) => [*r as f32 / 65535.0, *g as f32 / 65535.0, 0.0, 1.0],
+            Texel::F32(l) => [*l, *l, *l, 1.0],
+            Texel::RGBA8([r, g, b, a]) => [
+                *r as f32 / 255.0,
+                *g as f32 / 255.0,
+                *b as f32 / 255.0,
+                *a as f32 / 255.0,
+            ],
+            Texel::RGBA8Srgb([r, g, b, a]) => [
+                srgb_to_linear(*r as f32 / 255.0),
+                srgb_to_linear(*g as f32 / 255.0),
+                srgb_to_linear(*b as f32 / 255.0),
+                *a as f32 / 255.0,
+            ],
+            Texel::RGBA16([r, g, b, a]) => [
+                *r as f32 / 65535.0,
+                *g as f32 / 65535.0,
+                *b as f32 / 65535.0,
+                *a as f32 / 65535.0,
+            ],
+            Texel::RGBA16F([r, g, b, a]) => [
+                half_to_f32(*r),
+                half_to_f32(*g),
+                half_to_f32(*b),
+                half_to_f32(*a),
+            ],
+            Texel::RGBAF32(v) => *v,
+            Texel::RGB565(w) => [
+                (w >> 11 & 31) as f32 / 31.0,
+                (w >> 5 & 63) as f32 / 63.0,
+                (w & 31) as f32 / 31.0,
+                1.0,
+            ],
+            Texel::RGBA4444(w) => [
+                (w >> 12 & 15) as f32 / 15.0,
+                (w >> 8 & 15) as f32 / 15.0,
+                (w >> 4 & 15) as f32 / 15.0,
+                (w & 15) as f32 / 15.0,
+            ],
+            Texel::RGB5A1(w) => [
+                (w >> 11 & 31) as f32 / 31.0,
+                (w >> 6 & 31) as f32 / 31.0,
+                (w >> 1 & 31) as f32 / 31.0,
+                (w & 1) as f32,
+            ],
+            Texel::P8 { rgba: [r, g, b, a], .. } => [
+                *r as f32 / 255.0,
+                *g as f32 / 255.0,
+                *b as f32 / 255.0,
+                *a as f32 / 255.0,
+            ],
+        }
+    }
+
+    /// Builds a texel of the given format from normalized RGBA floats.
+    ///
+    /// Greyscale formats keep only the red channel, and sRGB formats encode
+    /// the linear light input.
+    pub fn from_normalized(format: Format, rgba: [f32; 4]) -> Texel {
+        Texel::build(format, rgba, 0.0)
+    }
+
+    /// Builds a texel like [from_normalized](Texel::from_normalized), with an
+    /// ordered dither of the quantization error for packed formats.
+    ///
+    /// The 16 bits packed formats have so few levels per channel that plain
+    /// rounding bands badly on gradients; a 4x4 Bayer threshold derived from
+    /// the texel position trades the banding for noise. Other formats
+    /// quantize exactly like [from_normalized](Texel::from_normalized).
+    pub fn from_normalized_dithered(format: Format, rgba: [f32; 4], x: u32, y: u32) -> Texel {
+        const BAYER4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+        let threshold = match format {
+            Format::RGB565 | Format::RGBA4444 | Format::RGB5A1 => {
+                (BAYER4[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0 - 0.5
+            }
+            _ => 0.0,
+        };
+        Texel::build(format, rgba, threshold)
+    }
+
+    /// Quantizes a normalized channel to the given number of levels, biased
+    /// by a dither threshold in [-0.5, 0.5).
+    fn quantize(value: f32, max: u32, threshold: f32) -> u16 {
+        ((value.clamp(0.0, 1.0) * max as f32 + 0.5 + threshold) as u32).min(max) as u16
+    }
+
+    fn build(format: Format, rgba: [f32; 4], threshold: f32) -> Texel {
+        match format {
+            Format::L8 => Texel::L8((rgba[0].clamp(0.0, 1.0) * 255.0) as u8),
+            Format::R16 => Texel::R16((rgba[0].clamp(0.0, 1.0) * 65535.0) as u16),
+            Format::RG8 => Texel::RG8([
+                (rgba[0].clamp(0.0, 1.0) * 255.0) as u8,
+                (rgba[1].clamp(0.0, 1.0) * 255.0) as u8,
+            ]),
+            Format::RG16 => Texel::RG16([
+                (rgba[0].clamp(0.0, 1.0) * 65535.0) as u16,
+                (rgba[1].clamp(0.0, 1.0) * 65535.0) as u16,
+            ]),
+            Format::F32 => Texel::F32(rgba[0]),
+            Format::RGBA8 => Texel::RGBA8([
+                (rgba[0].clamp(0.0, 1.0) * 255.0) as u8,
+                (rgba[1].clamp(0.0, 1.0) * 255.0) as u8,
+                (rgba[2].clamp(0.0, 1.0) * 255.0) as u8,
+                (rgba[3].clamp(0.0, 1.0) * 255.0) as u8,
+            ]),
+            Format::RGBA8Srgb => Texel::RGBA8Srgb([
+                (linear_to_srgb(rgba[0]) * 255.0) as u8,
+                (linear_to_srgb(rgba[1]) * 255.0) as u8,
+                (linear_to_srgb(rgba[2]) * 255.0) as u8,
+                (rgba[3].clamp(0.0, 1.0) * 255.0) as u8,
+            ]),
+            Format::RGBA16 => Texel::RGBA16([
+                (rgba[0].clamp(0.0, 1.0) * 65535.0) as u16,
+                (rgba[1].clamp(0.0, 1.0) * 65535.0) as u16,
+                (rgba[2].clamp(0.0, 1.0) * 65535.0) as u16,
+                (rgba[3].clamp(0.0, 1.0) * 65535.0) as u16,
+            ]),
+            Format::RGBA16F => Texel::RGBA16F([
+                f32_to_half(rgba[0]),
+                f32_to_half(rgba[1]),
+                f32_to_half(rgba[2]),
+                f32_to_half(rgba[3]),
+            ]),
+            Format::RGBAF32 => Texel::RGBAF32(rgba),
+            Format::RGB565 => Texel::RGB565(
+                Texel::quantize(rgba[0], 31, threshold) << 11
+                    | Texel::quantize(rgba[1], 63, threshold) << 5
+                    | Texel::quantize(rgba[2], 31, threshold),
+            ),
+            Format::RGBA4444 => Texel::RGBA4444(
+                Texel::quantize(rgba[0], 15, threshold) << 12
+                    | Texel::quantize(rgba[1], 15, threshold) << 8
+                    | Texel::quantize(rgba[2], 15, threshold) << 4
+                    | Texel::quantize(rgba[3], 15, threshold),
+            ),
+            Format::RGB5A1 => Texel::RGB5A1(
+                Texel::quantize(rgba[0], 31, threshold) << 11
+                    | Texel::quantize(rgba[1], 31, threshold) << 6
+                    | Texel::quantize(rgba[2], 31, threshold) << 1
+                    | Texel::quantize(rgba[3], 1, threshold),
+            ),
+            // Without a palette there is no meaningful index; index by the
+            // red channel like the greyscale formats and carry the full
+            // color so the palette entry still resolves to it.
+            Format::P8 => Texel::P8 {
+                index: (rgba[0].clamp(0.0, 1.0) * 255.0) as u8,
+                rgba: [
+                    (rgba[0].clamp(0.0, 1.0) * 255.0) as u8,
+                    (rgba[1].clamp(0.0, 1.0) * 255.0) as u8,
+                    (rgba[2].clamp(0.0, 1.0) * 255.0) as u8,
+                    (rgba[3].clamp(0.0, 1.0) * 255.0) as u8,
+                ],
+            },
+        }
+    }
+}
+
+/// Decodes a normalized sRGB encoded channel into linear light.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a normalized linear light channel into sRGB.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts a float to half float bits, flushing subnormals to zero and
+/// clamping to the largest finite half.
+pub(crate) fn f32_to_half(value: f32) -> u16 {
+    let sign = ((value.to_bits() >> 16) & 0x8000) as u16;
+    let value = value.abs().min(65504.0);
+    let bits = value.to_bits();
+    let exponent = (bits >> 23) as i32 - 127;
+    if exponent < -14 {
+        return sign;
+    }
+    sign | (((exponent + 15) as u32) << 10 | (bits >> 13) & 0x3FF) as u16
+}
+
+/// Converts half float bits to a float.
+pub(crate) fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    if half & 0x7C00 == 0 {
+        return sign * 0.0;
+    }
+    let exponent = (half >> 10) as u32 & 0x1F;
+    sign * f32::from_bits((exponent + 127 - 15) << 23 | ((half & 0x3FF) as u32) << 13)
+}
+
+/// A reconstruction kernel evaluated while sampling a texture.
+///
+/// [SampleMethod] picks the kernel used for interpolation at unit scale;
+/// downscales stretch a kernel over a wider footprint through
+/// [sample_scaled](Texture::sample_scaled).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleKernel {
+    /// Box kernel over a 1 texel support, a plain average of the footprint.
+    Box,
+
+    /// Triangle (tent) kernel over a 2 texel support, linear interpolation.
+    Triangle,
+
+    /// Catmull-Rom cubic kernel over a 4 texel support.
+    CatmullRom,
+
+    /// Kaiser windowed sinc kernel over a 6 texel support.
+    Kaiser,
+
+    /// Lanczos windowed sinc kernel over a 6 texel support.
+    Lanczos3,
+}
+
+/// The Kaiser window shape parameter; 4 is the usual compromise between
+/// ringing and aliasing for mip downsampling.
+const KAISER_BETA: f64 = 4.0;
+
+/// The zeroth order modified Bessel function of the first kind, by its
+/// power series; converges in a handful of terms for the arguments the
+/// Kaiser window produces.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..32 {
+        term *= (half / k as f64) * (half / k as f64);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+impl SampleKernel {
+    /// Returns the support radius in texels of this kernel at unit scale.
+    pub fn radius(self) -> f64 {
+        match self {
+            SampleKernel::Box => 0.5,
+            SampleKernel::Triangle => 1.0,
+            SampleKernel::CatmullRom => 2.0,
+            SampleKernel::Kaiser => 3.0,
+            SampleKernel::Lanczos3 => 3.0,
+        }
+    }
+
+    /// Evaluates this kernel at the given distance from the sample.
+    pub fn weight(self, x: f64) -> f64 {
+        match self {
+            SampleKernel::Box => match (-0.5..0.5).contains(&x) {
+                true => 1.0,
+                false => 0.0,
+            },
+            SampleKernel::Triangle => (1.0 - x.abs()).max(0.0),
+            SampleKernel::CatmullRom => {
+                let x = x.abs();
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            SampleKernel::Kaiser => {
+                let x = x.abs();
+                if x < 1e-8 {
+                    1.0
+                } else if x < 3.0 {
+                    let pix = std::f64::consts::PI * x;
+                    let window = bessel_i0(KAISER_BETA * (1.0 - (x / 3.0) * (x / 3.0)).sqrt());
+                    pix.sin() / pix * window / bessel_i0(KAISER_BETA)
+                } else {
+                    0.0
+                }
+            }
+            SampleKernel::Lanczos3 => {
+                let x = x.abs();
+                if x < 1e-8 {
+                    1.0
+                } else if x < 3.0 {
+                    let pix = std::f64::consts::PI * x;
+                    3.0 * pix.sin() * (pix / 3.0).sin() / (pix * pix)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// How sampling resolves coordinates outside of a texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Clamps to the nearest edge texel.
+    Clamp,
+
+    /// Tiles the texture, so kernels near a border read across the seam of
+    /// a tileable texture instead of smearing its edge texels.
+    Repeat,
+
+    /// Tiles the texture with every other repetition flipped.
+    Mirror,
+}
+
+impl WrapMode {
+    /// Returns the name of this mode as used by filter parameters.
+    pub fn name(self) -> &'static str {
+        match self {
+            WrapMode::Clamp => "clamp",
+            WrapMode::Repeat => "repeat",
+            WrapMode::Mirror => "mirror",
+        }
+    }
+
+    /// Parses a mode from its parameter name.
+    pub fn from_name(name: &str) -> Option<WrapMode> {
+        match name {
+            "clamp" => Some(WrapMode::Clamp),
+            "repeat" => Some(WrapMode::Repeat),
+            "mirror" => Some(WrapMode::Mirror),
+            _ => None,
+        }
+    }
+
+    /// Resolves a texel coordinate along one axis of the given size.
+    pub fn resolve(self, coord: i64, size: u32) -> u32 {
+        let n = size as i64;
+        (match self {
+            WrapMode::Clamp => coord.clamp(0, n - 1),
+            WrapMode::Repeat => coord.rem_euclid(n),
+            WrapMode::Mirror => {
+                let m = coord.rem_euclid(2 * n);
+                match m < n {
+                    true => m,
+                    false => 2 * n - 1 - m,
+                }
+            }
+        }) as u32
+    }
+}
+
+impl fmt::Display for WrapMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The interpolation method used when sampling a texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleMethod {
+    /// Nearest texel, no interpolation.
+    Nearest,
+
+    /// Linear interpolation of the 4 nearest texels.
+    Bilinear,
+
+    /// Catmull-Rom cubic interpolation over a 4x4 window.
+    Bicubic,
+
+    /// Lanczos windowed sinc interpolation over a 6x6 window.
+    Lanczos3,
+}
+
+impl SampleMethod {
+    /// Returns the name of this method as used by filter parameters.
+    pub fn name(self) -> &'static str {
+        match self {
+            SampleMethod::Nearest => "nearest",
+            SampleMethod::Bilinear => "bilinear",
+            SampleMethod::Bicubic => "bicubic",
+            SampleMethod::Lanczos3 => "lanczos3",
+        }
+    }
+
+    /// Parses a method from its parameter name.
+    pub fn from_name(name: &str) -> Option<SampleMethod> {
+        match name {
+            "nearest" => Some(SampleMethod::Nearest),
+            "bilinear" => Some(SampleMethod::Bilinear),
+            "bicubic" => Some(SampleMethod::Bicubic),
+            "lanczos3" => Some(SampleMethod::Lanczos3),
+            _ => None,
+        }
+    }
+
+    /// Returns the kernel this method interpolates with.
+    pub fn kernel(self) -> SampleKernel {
+        match self {
+            SampleMethod::Nearest | SampleMethod::Bilinear => SampleKernel::Triangle,
+            SampleMethod::Bicubic => SampleKernel::CatmullRom,
+            SampleMethod::Lanczos3 => SampleKernel::Lanczos3,
+        }
+    }
+
+    /// Returns the support radius in texels of this method's kernel.
+    fn radius(self) -> i64 {
+        self.kernel().radius() as i64
+    }
+
+    /// Evaluates this method's kernel at the given distance from the sample.
+    fn weight(self, x: f64) -> f64 {
+        self.kernel().weight(x)
+    }
+}
+
+impl fmt::Display for SampleMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Errors related to texture storage.
+#[derive(Debug)]
+pub enum TextureError {
+    /// The texel format does not match the texture format (expected, actual).
+    FormatMismatch(Format, Format),
+
+    /// The coordinates are outside of the texture (x, y).
+    OutOfBounds(u32, u32),
+
+    /// A texture array was built without any layer.
+    NoLayers,
+
+    /// A layer of a texture array does not share the size and format of the
+    /// first layer (layer index).
+    LayerMismatch(usize),
+
+    /// A cubemap was built from a non square face.
+    NotSquare,
+
+    /// A cubemap face does not share the size and format of the first face
+    /// (face index).
+    FaceMismatch(usize),
+
+    /// A cubemap mip level does not follow the size and format of the chain
+    /// (face index, level index).
+    MipMismatch(usize, usize),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::FormatMismatch(expected, actual) => {
+                write!(f, "texel format mismatch (expected {}, got {})", expected, actual)
+            }
+            TextureError::OutOfBounds(x, y) => write!(f, "coordinates ({}, {}) are out of bounds", x, y),
+            TextureError::NoLayers => f.write_str("a texture array needs at least one layer"),
+            TextureError::LayerMismatch(index) => write!(
+                f,
+                "layer {} does not share the size and format of the first layer",
+                index
+            ),
+            TextureError::NotSquare => f.write_str("cubemap faces must be square"),
+            TextureError::FaceMismatch(index) => write!(
+                f,
+                "face {} does not share the size and format of the first face",
+                index
+            ),
+            TextureError::MipMismatch(face, level) => write!(
+                f,
+                "mip level {} of face {} does not follow the size and format of the chain",
+                level, face
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+/// Base trait of all readable textures.
+pub trait Texture: Send + Sync {
+    /// Returns the width in texels of this texture.
+    fn width(&self) -> u32;
+
+    /// Returns the height in texels of this texture.
+    fn height(&self) -> u32;
+
+    /// Returns the format of this texture.
+    fn format(&self) -> Format;
+
+    /// Reads the texel at the given coordinates.
+    fn get(&self, x: u32, y: u32) -> Texel;
+
+    /// Samples this texture at the given normalized coordinates using nearest filtering.
+    fn sample(&self, u: f64, v: f64) -> Texel {
+        let x = ((u * self.width() as f64) as u32).min(self.width() - 1);
+        let y = ((v * self.height() as f64) as u32).min(self.height() - 1);
+        self.get(x, y)
+    }
+
+    /// Samples this texture at the given normalized coordinates with the
+    /// given interpolation method.
+    ///
+    /// Coordinates outside of the texture are clamped to its edges. The
+    /// returned texel is in this texture's format.
+    fn sample_with(&self, u: f64, v: f64, method: SampleMethod) -> Texel {
+        self.sample_with_wrap(u, v, method, WrapMode::Clamp)
+    }
+
+    /// Samples like [sample_with](Texture::sample_with), resolving
+    /// coordinates outside of the texture with the given wrap mode.
+    fn sample_with_wrap(&self, u: f64, v: f64, method: SampleMethod, wrap: WrapMode) -> Texel {
+        if method == SampleMethod::Nearest {
+            let x = wrap.resolve((u * self.width() as f64).floor() as i64, self.width());
+            let y = wrap.resolve((v * self.height() as f64).floor() as i64, self.height());
+            return self.get(x, y);
+        }
+        let radius = method.radius();
+        let cx = u * self.width() as f64 - 0.5;
+        let cy = v * self.height() as f64 - 0.5;
+        let x0 = cx.floor() as i64;
+        let y0 = cy.floor() as i64;
+        let mut sum = [0.0f64; 4];
+        let mut weight_sum = 0.0f64;
+        for dy in (1 - radius)..=radius {
+            let ty = y0 + dy;
+            let wy = method.weight(ty as f64 - cy);
+            if wy == 0.0 {
+                continue;
+            }
+            let sy = wrap.resolve(ty, self.height());
+            for dx in (1 - radius)..=radius {
+                let tx = x0 + dx;
+                let weight = method.weight(tx as f64 - cx) * wy;
+                if weight == 0.0 {
+                    continue;
+                }
+                let sx = wrap.resolve(tx, self.width());
+                let rgba = self.get(sx, sy).normalize();
+                for (acc, channel) in sum.iter_mut().zip(rgba) {
+                    *acc += channel as f64 * weight;
+                }
+                weight_sum += weight;
+            }
+        }
+        Texel::from_normalized(
+            self.format(),
+            [
+                (sum[0] / weight_sum) as f32,
+                (sum[1] / weight_sum) as f32,
+                (sum[2] / weight_sum) as f32,
+                (sum[3] / weight_sum) as f32,
+            ],
+        )
+    }
+
+    /// Samples this texture with a kernel stretched over a minification
+    /// footprint.
+    ///
+    /// `scale` is the number of source texels covered per output texel
+    /// along the larger axis; one or less interpolates like
+    /// [sample_with](Texture::sample_with). Wider footprints stretch and
+    /// renormalize the kernel, so every covered texel contributes and large
+    /// downscales stop aliasing where plain interpolation only reads the
+    /// texels nearest the sample. The resample filter and the mipmap
+    /// generator downscale through this. Coordinates outside of the
+    /// texture are resolved with the given wrap mode.
+    fn sample_scaled(&self, u: f64, v: f64, kernel: SampleKernel, scale: f64, wrap: WrapMode) -> Texel {
+        let scale = scale.max(1.0);
+        let radius = kernel.radius() * scale;
+        let cx = u * self.width() as f64 - 0.5;
+        let cy = v * self.height() as f64 - 0.5;
+        let mut sum = [0.0f64; 4];
+        let mut weight_sum = 0.0f64;
+        for ty in (cy - radius).ceil() as i64..=(cy + radius).floor() as i64 {
+            let wy = kernel.weight((ty as f64 - cy) / scale);
+            if wy == 0.0 {
+                continue;
+            }
+            let sy = wrap.resolve(ty, self.height());
+            for tx in (cx - radius).ceil() as i64..=(cx + radius).floor() as i64 {
+                let weight = kernel.weight((tx as f64 - cx) / scale) * wy;
+                if weight == 0.0 {
+                    continue;
+                }
+                let sx = wrap.resolve(tx, self.width());
+                let rgba = self.get(sx, sy).normalize();
+                for (acc, channel) in sum.iter_mut().zip(rgba) {
+                    *acc += channel as f64 * weight;
+                }
+                weight_sum += weight;
+            }
+        }
+        Texel::from_normalized(
+            self.format(),
+            [
+                (sum[0] / weight_sum) as f32,
+                (sum[1] / weight_sum) as f32,
+                (sum[2] / weight_sum) as f32,
+                (sum[3] / weight_sum) as f32,
+            ],
+        )
+    }
+}
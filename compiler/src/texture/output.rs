@@ -0,0 +1,277 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use image::RgbaImage;
+
+use crate::texture::Format;
+use crate::texture::Texel;
+use crate::texture::Texture;
+use crate::texture::TextureError;
+
+/// A writable render target texture.
+pub struct OutputTexture {
+    width: u32,
+    height: u32,
+    format: Format,
+    data: Vec<u8>,
+    palette: Box<[[u8; 4]; 256]>,
+}
+
+impl OutputTexture {
+    /// Creates a new output texture.
+    ///
+    /// The size is rounded up to the next power of two.
+    pub fn new(width: u32, height: u32, format: Format) -> OutputTexture {
+        OutputTexture::new_exact(width.next_power_of_two(), height.next_power_of_two(), format)
+    }
+
+    /// Creates a new output texture of exactly the given size, for targets
+    /// authored at exact sizes such as UI atlases.
+    pub fn new_exact(width: u32, height: u32, format: Format) -> OutputTexture {
+        OutputTexture {
+            width,
+            height,
+            format,
+            data: vec![0; width as usize * height as usize * format.texel_size()],
+            palette: Box::new([[0; 4]; 256]),
+        }
+    }
+
+    /// Rebuilds a texture from a checkpointed payload and palette.
+    ///
+    /// The size must already be final and the payload length must match
+    /// it; callers validate both before handing the data over.
+    pub(crate) fn from_parts(
+        width: u32,
+        height: u32,
+        format: Format,
+        data: Vec<u8>,
+        palette: Box<[[u8; 4]; 256]>,
+    ) -> OutputTexture {
+        OutputTexture {
+            width,
+            height,
+            format,
+            data,
+            palette,
+        }
+    }
+
+    fn offset(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * self.format.texel_size()
+    }
+
+    /// Writes a texel at the given coordinates.
+    ///
+    /// Fails if the texel format does not match the texture format or if the
+    /// coordinates are out of bounds.
+    pub fn set(&mut self, x: u32, y: u32, texel: Texel) -> Result<(), TextureError> {
+        if x >= self.width || y >= self.height {
+            return Err(TextureError::OutOfBounds(x, y));
+        }
+        if texel.format() != self.format {
+            return Err(TextureError::FormatMismatch(self.format, texel.format()));
+        }
+        let offset = self.offset(x, y);
+        match texel {
+            Texel::L8(l) => self.data[offset] = l,
+            Texel::R16(l) => self.data[offset..offset + 2].copy_from_slice(&l.to_le_bytes()),
+            Texel::RG8(v) => self.data[offset..offset + 2].copy_from_slice(&v),
+            Texel::RG16(v) => {
+                for (i, c) in v.iter().enumerate() {
+                    self.data[offset + i * 2..offset + i * 2 + 2].copy_from_slice(&c.to_le_bytes());
+                }
+            }
+            Texel::F32(l) => self.data[offset..offset + 4].copy_from_slice(&l.to_le_bytes()),
+            Texel::RGBA8(v) | Texel::RGBA8Srgb(v) => {
+                self.data[offset..offset + 4].copy_from_slice(&v)
+            }
+            Texel::RGBA16(v) | Texel::RGBA16F(v) => {
+                for (i, c) in v.iter().enumerate() {
+                    self.data[offset + i * 2..offset + i * 2 + 2].copy_from_slice(&c.to_le_bytes());
+                }
+            }
+            Texel::RGBAF32(v) => {
+                for (i, c) in v.iter().enumerate() {
+                    self.data[offset + i * 4..offset + i * 4 + 4].copy_from_slice(&c.to_le_bytes());
+                }
+            }
+            Texel::RGB565(w) | Texel::RGBA4444(w) | Texel::RGB5A1(w) => {
+                self.data[offset..offset + 2].copy_from_slice(&w.to_le_bytes())
+            }
+            Texel::P8 { index, rgba } => {
+                // The texel carries its palette color along with the index;
+                // recording it here keeps the palette in sync with the
+                // indices without a separate registration step.
+                self.data[offset] = index;
+                self.palette[index as usize] = rgba;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the RGBA8 palette of a [P8](Format::P8) texture.
+    ///
+    /// Entries never referenced by a texel stay zero. The palette is
+    /// meaningless for other formats.
+    pub fn palette(&self) -> &[[u8; 4]; 256] {
+        &self.palette
+    }
+
+    /// Returns the raw texel payload of this texture.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Converts this texture into a new texture of the given format.
+    ///
+    /// Texels pass through normalized RGBA floats, so every pair of formats
+    /// converts with well defined semantics: narrow formats widen exactly,
+    /// unorm formats narrow with rounding, float formats clamp into [0, 1]
+    /// when quantized, sRGB decodes and re-encodes, and the 16 bits packed
+    /// formats dither like
+    /// [from_normalized_dithered](Texel::from_normalized_dithered).
+    /// Converting to the current format copies the texture.
+    pub fn convert(&self, format: Format) -> OutputTexture {
+        let mut target = OutputTexture::new_exact(self.width, self.height, format);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let rgba = self.get(x, y).normalize();
+                let texel = Texel::from_normalized_dithered(format, rgba, x, y);
+                // Cannot fail: the coordinates are in bounds and the texel
+                // was built in the target format.
+                target.set(x, y, texel).unwrap();
+            }
+        }
+        target
+    }
+
+    /// Converts this texture to a RGBA8 image, losing precision for float formats.
+    ///
+    /// sRGB textures re-encode so the image holds the stored bytes and not
+    /// their linearized expansion.
+    pub fn to_rgba_lossy(&self) -> RgbaImage {
+        let target = match self.format {
+            // Keep sRGB payloads sRGB encoded; convert decodes to linear
+            // and would otherwise store the expansion.
+            Format::RGBA8Srgb => self.convert(Format::RGBA8Srgb),
+            _ => self.convert(Format::RGBA8),
+        };
+        // Both four byte formats lay texels out exactly like the image.
+        RgbaImage::from_raw(self.width, self.height, target.data).unwrap()
+    }
+}
+
+impl Texture for OutputTexture {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn format(&self) -> Format {
+        self.format
+    }
+
+    fn get(&self, x: u32, y: u32) -> Texel {
+        let offset = self.offset(x, y);
+        match self.format {
+            Format::L8 => Texel::L8(self.data[offset]),
+            Format::R16 => {
+                let mut buf = [0; 2];
+                buf.copy_from_slice(&self.data[offset..offset + 2]);
+                Texel::R16(u16::from_le_bytes(buf))
+            }
+            Format::RG8 => Texel::RG8([self.data[offset], self.data[offset + 1]]),
+            Format::RG16 => {
+                let mut v = [0; 2];
+                for (i, c) in v.iter_mut().enumerate() {
+                    let mut buf = [0; 2];
+                    buf.copy_from_slice(&self.data[offset + i * 2..offset + i * 2 + 2]);
+                    *c = u16::from_le_bytes(buf);
+                }
+                Texel::RG16(v)
+            }
+            Format::F32 => {
+                let mut buf = [0; 4];
+                buf.copy_from_slice(&self.data[offset..offset + 4]);
+                Texel::F32(f32::from_le_bytes(buf))
+            }
+            Format::RGBA8 | Format::RGBA8Srgb => {
+                let mut buf = [0; 4];
+                buf.copy_from_slice(&self.data[offset..offset + 4]);
+                if self.format == Format::RGBA8Srgb {
+                    Texel::RGBA8Srgb(buf)
+                } else {
+                    Texel::RGBA8(buf)
+                }
+            }
+            Format::RGBA16 | Format::RGBA16F => {
+                let mut v = [0; 4];
+                for (i, c) in v.iter_mut().enumerate() {
+                    let mut buf = [0; 2];
+                    buf.copy_from_slice(&self.data[offset + i * 2..offset + i * 2 + 2]);
+                    *c = u16::from_le_bytes(buf);
+                }
+                if self.format == Format::RGBA16F {
+                    Texel::RGBA16F(v)
+                } else {
+                    Texel::RGBA16(v)
+                }
+            }
+            Format::RGBAF32 => {
+                let mut v = [0.0; 4];
+                for (i, c) in v.iter_mut().enumerate() {
+                    let mut buf = [0; 4];
+                    buf.copy_from_slice(&self.data[offset + i * 4..offset + i * 4 + 4]);
+                    *c = f32::from_le_bytes(buf);
+                }
+                Texel::RGBAF32(v)
+            }
+            Format::RGB565 | Format::RGBA4444 | Format::RGB5A1 => {
+                let mut buf = [0; 2];
+                buf.copy_from_slice(&self.data[offset..offset + 2]);
+                let word = u16::from_le_bytes(buf);
+                match self.format {
+                    Format::RGB565 => Texel::RGB565(word),
+                    Format::RGBA4444 => Texel::RGBA4444(word),
+                    _ => Texel::RGB5A1(word),
+                }
+            }
+            Format::P8 => {
+                let index = self.data[offset];
+                Texel::P8 {
+                    index,
+                    rgba: self.palette[index as usize],
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::texture::Format;
+use crate::texture::OutputTexture;
+use crate::texture::Texture;
+
+/// Number of buffers a swap chain starts with when no depth is given.
+const CHAIN_DEPTH: usize = 2;
+
+/// A pool of render target buffers cycled between passes.
+///
+/// The most recently presented buffer is the previous pass of the next
+/// render. Buffers retained outside of the pool (published pass results)
+/// are evicted instead of recycled, so holding onto one never sees it
+/// overwritten by a later pass.
+pub struct SwapChain {
+    buffers: VecDeque<Arc<OutputTexture>>,
+    depth: usize,
+}
+
+impl SwapChain {
+    /// Creates a new swap chain of the default depth of 2 buffers.
+    ///
+    /// The size is rounded up to the next power of two unless `npot` keeps
+    /// it exact.
+    pub fn new(width: u32, height: u32, format: Format, npot: bool) -> SwapChain {
+        SwapChain::with_depth(width, height, format, npot, CHAIN_DEPTH)
+    }
+
+    /// Creates a new swap chain retaining `depth` buffers, so the
+    /// `depth - 1` most recently presented passes stay readable through
+    /// [earlier](SwapChain::earlier). Depths below 2 are raised to 2.
+    pub fn with_depth(
+        width: u32,
+        height: u32,
+        format: Format,
+        npot: bool,
+        depth: usize,
+    ) -> SwapChain {
+        let depth = depth.max(CHAIN_DEPTH);
+        let mut buffers = VecDeque::with_capacity(depth);
+        for _ in 0..depth {
+            let buffer = match npot {
+                true => OutputTexture::new_exact(width, height, format),
+                false => OutputTexture::new(width, height, format),
+            };
+            buffers.push_back(Arc::new(buffer));
+        }
+        SwapChain { buffers, depth }
+    }
+
+    /// Acquires a free buffer of the pool for rendering.
+    ///
+    /// The `depth - 1` most recently presented buffers are retained as
+    /// history and never handed out; spares still referenced elsewhere are
+    /// dropped from the pool and a fresh buffer is allocated when no spare
+    /// is free.
+    pub fn acquire(&mut self) -> OutputTexture {
+        while self.buffers.len() >= self.depth {
+            let buffer = self.buffers.pop_back().expect("Empty swap chain");
+            if let Ok(buffer) = Arc::try_unwrap(buffer) {
+                return buffer;
+            }
+        }
+        let previous = self.previous();
+        // The previous buffer's size is already final, so no rounding.
+        OutputTexture::new_exact(previous.width(), previous.height(), previous.format())
+    }
+
+    /// Presents a rendered buffer, making it the previous pass of the next render.
+    pub fn present(&mut self, buffer: OutputTexture) {
+        self.buffers.push_front(Arc::new(buffer));
+    }
+
+    /// Presents an already shared buffer, making it the previous pass of
+    /// the next render.
+    pub fn present_shared(&mut self, buffer: Arc<OutputTexture>) {
+        self.buffers.push_front(buffer);
+    }
+
+    /// Returns the last presented buffer.
+    pub fn previous(&self) -> &Arc<OutputTexture> {
+        self.buffers.front().expect("Empty swap chain")
+    }
+
+    /// Returns the `index` most recently presented buffer, 0 being the
+    /// previous pass, None past the retained history.
+    pub fn earlier(&self, index: usize) -> Option<&Arc<OutputTexture>> {
+        self.buffers.get(index)
+    }
+
+    /// Returns the retained history, most recent first, starting at the
+    /// previous pass.
+    pub fn history(&self) -> Vec<Arc<OutputTexture>> {
+        self.buffers.iter().cloned().collect()
+    }
+
+    /// Consumes the chain and returns the last presented buffer.
+    pub fn into_previous(mut self) -> Arc<OutputTexture> {
+        self.buffers.pop_front().expect("Empty swap chain")
+    }
+}
@@ -26,3 +26,572 @@
 // NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use clap::Parser;
+use texturec_compiler::encode::Encoding;
+use texturec_compiler::encode::Quality;
+use texturec_compiler::filter;
+use texturec_compiler::mipmap::MipFilter;
+use texturec_compiler::output::Container;
+use texturec_compiler::params::ParameterError;
+use texturec_compiler::params::ParameterMap;
+use texturec_compiler::pipeline::CancelToken;
+use texturec_compiler::pipeline::ExecutorKind;
+use texturec_compiler::pipeline::PassDelegate;
+use texturec_compiler::pipeline::PassStats;
+use texturec_compiler::pipeline::PipelineDelegate;
+use texturec_compiler::pipeline::Tiling;
+use texturec_compiler::texture::Format;
+use texturec_compiler::Compiler;
+use texturec_compiler::Config;
+
+/// The BlockProject 3D texture compiler.
+#[derive(Parser)]
+#[command(name = "texturec", version, about)]
+struct Args {
+    /// Width in texels of the output texture.
+    #[arg(short = 'w', long, default_value_t = 256)]
+    width: u32,
+
+    /// Height in texels of the output texture.
+    #[arg(short = 'H', long, default_value_t = 256)]
+    height: u32,
+
+    /// Format of the output texture (l8, r16, rg8, rg16, f32, rgba8,
+    /// rgba8srgb, rgba16, rgba16f, rgbaf32, rgb565, rgba4444, rgb5a1, p8).
+    #[arg(short, long, default_value = "rgba8")]
+    format: String,
+
+    /// Path of the output texture file.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Block compression of the output payload
+    /// (raw, bc1, bc3, bc4, bc5, bc6h, bc7, astc4x4, astc5x5, astc6x6, astc8x8,
+    /// etc2rgb, etc2rgba, eacr11, eacrg11).
+    #[arg(short, long, default_value = "raw")]
+    encode: String,
+
+    /// Effort spent searching block compression endpoints (fast, normal, slow).
+    #[arg(short, long, default_value = "normal")]
+    quality: String,
+
+    /// Container format of the output file (auto, bpx, ktx2, dds); auto
+    /// picks from the output extension.
+    #[arg(short, long, default_value = "auto")]
+    container: String,
+
+    /// Supercompresses the payload of containers that support it.
+    #[arg(long)]
+    supercompress: bool,
+
+    /// Keeps the exact output size instead of rounding it up to the next
+    /// power of two, for assets authored at exact sizes such as UI atlases.
+    #[arg(long)]
+    allow_npot: bool,
+
+    /// Number of layers of the output texture array; each layer renders the
+    /// filter chain with its index as the int parameter `layer`.
+    #[arg(long, default_value_t = 1)]
+    layers: u32,
+
+    /// Assembles the rendered layers into a cubemap instead of a texture
+    /// array; requires --layers 6 and a square output, one face per layer
+    /// in +X -X +Y -Y +Z -Z order.
+    #[arg(long)]
+    cubemap: bool,
+
+    /// Generates a full mip chain down to 1x1 and stores it in the output
+    /// container.
+    #[arg(long)]
+    mipmaps: bool,
+
+    /// Downsampling filter generating mip levels (box, triangle, kaiser,
+    /// lanczos); box blurs normal and detail maps, kaiser and lanczos keep
+    /// them sharp.
+    #[arg(long, default_value = "box")]
+    mip_filter: String,
+
+    /// Rescales the alpha of every mip level so the coverage of an alpha
+    /// test at this reference value stays constant across the chain.
+    #[arg(long)]
+    alpha_coverage: Option<f64>,
+
+    /// Name of a filter re-run on every generated mip level below the base
+    /// (repeatable); the passes read the downsampled level and its index as
+    /// the int parameter `mip`.
+    #[arg(long = "mip-pass")]
+    mip_pass: Vec<String>,
+
+    /// A named filter parameter (-p <NAME> <VALUE>).
+    #[arg(short, long, num_args = 2, value_names = ["NAME", "VALUE"])]
+    param: Vec<OsString>,
+
+    /// Number of threads to render with.
+    #[arg(short = 't', long, default_value_t = 4)]
+    threads: usize,
+
+    /// Execution backend, one of threadpool or rayon (the latter requires
+    /// the rayon build feature).
+    #[arg(long, default_value = "threadpool")]
+    executor: String,
+
+    /// Additionally saves the output as a debug PNG.
+    #[arg(short, long)]
+    debug: bool,
+
+    /// Forces fixed seeds and byte-identical outputs for reproducible builds.
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Seed driving every stochastic filter, making runs with the same seed
+    /// and inputs bit-identical; omit to draw fresh randomness per pass.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Fails a pass on texel format mismatches instead of warning
+    /// (always on when the CI environment variable is set).
+    #[arg(long)]
+    strict: bool,
+
+    /// Renders each pass in square tiles of this size instead of over the
+    /// whole target at once, lowering peak memory on very large textures.
+    #[arg(long)]
+    tile_size: Option<u32>,
+
+    /// Extra texels of context around each tile; should cover the widest
+    /// kernel radius of the pipeline to avoid seams along tile edges.
+    #[arg(long, default_value_t = 0)]
+    tile_apron: u32,
+
+    /// Reuses the existing output when nothing affecting it changed.
+    #[arg(long)]
+    cache: bool,
+
+    /// Upper bound in bytes on render target buffers and per pass working
+    /// memory; enables tiled rendering or fails instead of being OOM-killed.
+    #[arg(long)]
+    max_memory: Option<u64>,
+
+    /// Saves the render state to this file after every completed pass and
+    /// resumes an interrupted compilation from it.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Compiles once per file matching GLOB, each file loaded as the
+    /// texture parameter NAME, instead of a single run; `{}` in the output
+    /// path is replaced by the source file stem. The wildcards `*` and `?`
+    /// apply to the file name, the directory part is literal.
+    #[arg(long, num_args = 2, value_names = ["NAME", "GLOB"])]
+    batch: Option<Vec<String>>,
+
+    /// Recompiles whenever a watched input changes: every parameter naming
+    /// an existing file and the files matching the batch glob are polled
+    /// twice a second, and each rebuild reports its timing.
+    #[arg(long)]
+    watch: bool,
+
+    /// Lists every registered filter with its parameters, types and
+    /// defaults, then exits.
+    #[arg(long, exclusive = true)]
+    list_filters: bool,
+
+    /// Names of the filters to run in order; append `:buffer` to a name to
+    /// publish that pass's output, which later filters can reference by
+    /// passing the buffer name as the value of a texture parameter.
+    filters: Vec<String>,
+}
+
+/// Matches a file name against a glob pattern where `*` spans any run of
+/// characters and `?` exactly one.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let mut p = 0;
+    let mut n = 0;
+    let mut star = None;
+    let mut restart = 0;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some(p);
+            restart = n;
+            p += 1;
+        } else if let Some(position) = star {
+            // Let the last `*` swallow one more character and retry.
+            p = position + 1;
+            restart += 1;
+            n = restart;
+        } else {
+            return false;
+        }
+    }
+    while p < pattern.len() && pattern[p] == '*' {
+        p += 1;
+    }
+    p == pattern.len()
+}
+
+/// Returns the files matching a glob pattern, sorted so batches run in a
+/// stable order; wildcards apply to the file name only, the directory part
+/// is taken literally.
+fn glob_expand(pattern: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let name = match pattern.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return Ok(Vec::new()),
+    };
+    if !name.contains(['*', '?']) {
+        return Ok(match pattern.is_file() {
+            true => vec![pattern.into()],
+            false => Vec::new(),
+        });
+    }
+    let dir = match pattern.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut matches = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if let Some(candidate) = entry.file_name().to_str() {
+            if glob_match(name, candidate) && entry.path().is_file() {
+                matches.push(entry.path());
+            }
+        }
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+/// Parses the shared `-p` parameters, with the batch source appended when
+/// given.
+fn parse_params(args: &Args, extra: Option<(&str, &OsStr)>) -> Result<ParameterMap, ParameterError> {
+    ParameterMap::parse(
+        args.param
+            .chunks(2)
+            .map(|pair| (pair[0].to_str().expect("Invalid parameter name"), &*pair[1]))
+            .chain(extra),
+    )
+}
+
+/// Returns the modification time of every watched input: parameter values
+/// naming an existing file and the files matching the batch glob, so
+/// appearing and disappearing files register as changes too.
+fn watch_snapshot(args: &Args) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut paths: Vec<PathBuf> = args
+        .param
+        .chunks(2)
+        .filter_map(|pair| {
+            let value = pair[1].to_str().unwrap_or_default();
+            let path = Path::new(value.strip_prefix("tex:").unwrap_or(value));
+            match path.is_file() {
+                true => Some(path.into()),
+                false => None,
+            }
+        })
+        .collect();
+    if let Some(pair) = &args.batch {
+        paths.extend(glob_expand(Path::new(&pair[1])).unwrap_or_default());
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+/// Blocks until a watched input differs from the snapshot taken before the
+/// last rebuild, polling twice a second.
+fn wait_for_change(args: &Args, before: &[(PathBuf, Option<SystemTime>)]) {
+    loop {
+        std::thread::sleep(Duration::from_millis(500));
+        if watch_snapshot(args) != *before {
+            // Give an editor a moment to finish writing the file.
+            std::thread::sleep(Duration::from_millis(100));
+            return;
+        }
+    }
+}
+
+fn print_filters() {
+    for info in filter::filters() {
+        println!("{}: {}", info.name, info.description);
+        for param in info.params {
+            match (param.default, param.required) {
+                (Some(default), _) => {
+                    println!("  {}: {} (default {})", param.name, param.ty, default)
+                }
+                (None, true) => println!("  {}: {} (required)", param.name, param.ty),
+                (None, false) => println!("  {}: {}", param.name, param.ty),
+            }
+        }
+    }
+}
+
+fn print_progress(stats: PassStats) {
+    let percent = stats.processed as f64 / stats.total as f64 * 100.0;
+    print!(
+        "\r{:.1}% ({}/{} texels, {:.2}M texels/s",
+        percent,
+        stats.processed,
+        stats.total,
+        stats.texels_per_second() / 1e6
+    );
+    match stats.remaining() {
+        Some(remaining) => print!(", {:.0?} left)", remaining),
+        None => print!(")"),
+    }
+    let _ = std::io::stdout().flush();
+}
+
+struct PassProgress;
+
+impl PassDelegate for PassProgress {
+    fn on_progress(&self, stats: PassStats) {
+        print_progress(stats);
+    }
+
+    fn on_end(&self) {
+        println!();
+    }
+}
+
+struct Progress;
+
+impl PipelineDelegate for Progress {
+    type Pass = PassProgress;
+
+    fn on_start_pass(&self, index: usize, count: usize, name: &str) -> PassProgress {
+        println!("Rendering pass {}/{} ({})...", index + 1, count, name);
+        PassProgress
+    }
+}
+
+fn main() {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+    if args.list_filters {
+        print_filters();
+        return;
+    }
+    let output = match args.output.clone() {
+        Some(v) => v,
+        None => {
+            eprintln!("The --output option is required");
+            std::process::exit(1);
+        }
+    };
+    let format = match Format::from_name(&args.format) {
+        Some(v) => v,
+        None => {
+            eprintln!("Unknown texture format '{}'", args.format);
+            std::process::exit(1);
+        }
+    };
+    let encoding = match Encoding::from_name(&args.encode) {
+        Some(v) => v,
+        None => {
+            eprintln!("Unknown payload encoding '{}'", args.encode);
+            std::process::exit(1);
+        }
+    };
+    let quality = match Quality::from_name(&args.quality) {
+        Some(v) => v,
+        None => {
+            eprintln!("Unknown encoding quality '{}'", args.quality);
+            std::process::exit(1);
+        }
+    };
+    let container = match args.container.as_str() {
+        "auto" => Container::from_path(&output),
+        name => match Container::from_name(name) {
+            Some(v) => v,
+            None => {
+                eprintln!("Unknown container format '{}'", name);
+                std::process::exit(1);
+            }
+        },
+    };
+    let mip_filter = match MipFilter::from_name(&args.mip_filter) {
+        Some(v) => v,
+        None => {
+            eprintln!("Unknown mip filter '{}'", args.mip_filter);
+            std::process::exit(1);
+        }
+    };
+    let executor = match ExecutorKind::from_name(&args.executor) {
+        Some(v) => v,
+        None => {
+            eprintln!("Unknown or compiled out executor '{}'", args.executor);
+            std::process::exit(1);
+        }
+    };
+    let apron = args.tile_apron;
+    let tiling = args.tile_size.map(|size| Tiling { size, apron });
+    let watch = args.watch;
+    loop {
+        // The snapshot predates the rebuild, so a file edited while a
+        // rebuild runs still registers as a change.
+        let snapshot = match watch {
+            true => watch_snapshot(&args),
+            false => Vec::new(),
+        };
+        let start = Instant::now();
+        // One compilation per job; a single (source-less) job outside of
+        // batch mode.
+        let jobs: Vec<(Option<PathBuf>, PathBuf, ParameterMap)> = match &args.batch {
+            Some(pair) => {
+                let name = pair[0].as_str();
+                let glob = Path::new(&pair[1]);
+                let pattern = match output.to_str() {
+                    Some(v) if v.contains("{}") => v,
+                    _ => {
+                        eprintln!("A batch output path must contain a '{{}}' placeholder for the source file stem");
+                        std::process::exit(1);
+                    }
+                };
+                let sources = match glob_expand(glob) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        eprintln!("Unable to expand '{}': {}", glob.display(), e);
+                        match watch {
+                            true => Vec::new(),
+                            false => std::process::exit(1),
+                        }
+                    }
+                };
+                if sources.is_empty() && !watch {
+                    eprintln!("No file matches '{}'", glob.display());
+                    std::process::exit(1);
+                }
+                sources
+                    .into_iter()
+                    .filter_map(|source| {
+                        let stem = source
+                            .file_stem()
+                            .and_then(|stem| stem.to_str())
+                            .unwrap_or_default();
+                        let out = PathBuf::from(pattern.replace("{}", stem));
+                        // Re-parsed per source so every job loads its own
+                        // texture under the batch parameter name.
+                        match parse_params(&args, Some((name, source.as_os_str()))) {
+                            Ok(params) => Some((Some(source), out, params)),
+                            Err(e) => {
+                                eprintln!("Unable to parse parameters: {}", e);
+                                match watch {
+                                    true => None,
+                                    false => std::process::exit(1),
+                                }
+                            }
+                        }
+                    })
+                    .collect()
+            }
+            None => match parse_params(&args, None) {
+                Ok(params) => vec![(None, output.clone(), params)],
+                Err(e) => {
+                    eprintln!("Unable to parse parameters: {}", e);
+                    match watch {
+                        true => Vec::new(),
+                        false => std::process::exit(1),
+                    }
+                }
+            },
+        };
+        let count = jobs.len();
+        for (source, output, params) in jobs {
+            if let Some(source) = &source {
+                println!("Compiling '{}' into '{}'...", source.display(), output.display());
+            }
+            let config = Config {
+                width: args.width,
+                height: args.height,
+                format,
+                npot: args.allow_npot,
+                layers: args.layers,
+                cubemap: args.cubemap,
+                mipmaps: args.mipmaps,
+                mip_filter,
+                alpha_coverage: args.alpha_coverage,
+                mip_passes: args.mip_pass.clone(),
+                output,
+                container,
+                encoding,
+                quality,
+                supercompress: args.supercompress,
+                filters: args.filters.clone(),
+                params,
+                pass_params: Vec::new(),
+                n_threads: args.threads,
+                executor,
+                debug: args.debug,
+                deterministic: args.deterministic,
+                seed: args.seed,
+                strict: args.strict || std::env::var_os("CI").is_some(),
+                tiling,
+                cache: args.cache,
+                max_memory: args.max_memory,
+                checkpoint: args.checkpoint.clone(),
+                cancel: CancelToken::new(),
+            };
+            match Compiler::new(config).run(&Progress) {
+                Ok(report) => {
+                    for warning in &report.warnings {
+                        eprintln!("Warning: {}", warning);
+                    }
+                    if report.cached {
+                        println!(
+                            "Reused the up to date {}x{} {} texture (content hash {:016x})",
+                            report.width, report.height, report.format, report.content_hash
+                        );
+                    } else {
+                        for pass in &report.passes {
+                            println!(
+                                "Pass {}: {:.2?} ({:.2}M texels/s, {:.1} threads busy)",
+                                pass.name,
+                                pass.duration,
+                                pass.throughput() / 1e6,
+                                pass.parallelism()
+                            );
+                        }
+                        println!(
+                            "Compiled a {}x{} {} texture in {:.2?} (content hash {:016x})",
+                            report.width,
+                            report.height,
+                            report.format,
+                            report.duration(),
+                            report.content_hash
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Compilation failed: {}", e);
+                    if !watch {
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        if !watch {
+            break;
+        }
+        println!(
+            "Rebuilt {} texture(s) in {:.2?}; watching for changes...",
+            count,
+            start.elapsed()
+        );
+        wait_for_change(&args, &snapshot);
+    }
+}